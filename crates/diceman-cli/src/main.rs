@@ -1,7 +1,11 @@
 // ABOUTME: Command-line interface for the diceman dice roller.
-// ABOUTME: Provides roll and simulation commands with optional JSON output.
+// ABOUTME: Provides roll, simulation and random table commands with optional JSON output.
 
 use clap::{Parser, Subcommand};
+use diceman::systems::fate::{FateOutcome, Rung};
+use diceman::table::TableSet;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "diceman")]
@@ -18,6 +22,18 @@ enum Commands {
     Roll {
         /// Dice expression (e.g., "4d6kh3", "2d6 + 5")
         expression: String,
+
+        /// Set a variable used as @NAME (e.g., --var level=3)
+        #[arg(long = "var", value_name = "NAME=VALUE")]
+        vars: Vec<String>,
+
+        /// Print Fudge dice as Fate glyphs and the total on the Fate ladder
+        #[arg(long)]
+        fate: bool,
+
+        /// Compare the total against a Fate difficulty and report shifts (implies --fate)
+        #[arg(long, value_name = "DIFFICULTY", allow_hyphen_values = true)]
+        vs: Option<i64>,
     },
     /// Simulate rolling dice many times
     Sim {
@@ -31,17 +47,61 @@ enum Commands {
         /// Output as JSON
         #[arg(long)]
         json: bool,
+
+        /// Report how often sets of matching dice come up (needs `m` or `mt`)
+        #[arg(long)]
+        sets: bool,
+
+        /// Set a variable used as @NAME (e.g., --var level=3)
+        #[arg(long = "var", value_name = "NAME=VALUE")]
+        vars: Vec<String>,
+    },
+    /// Check an expression for mistakes without rolling it
+    Check {
+        /// Dice expression (e.g., "3d6kh5")
+        expression: String,
+    },
+    /// Roll on random tables loaded from a file
+    Table {
+        #[command(subcommand)]
+        command: TableCommand,
     },
     /// Show dice notation reference
     Notation,
 }
 
+#[derive(Subcommand)]
+enum TableCommand {
+    /// Roll on a table from a .toml, .json or .csv file
+    Roll {
+        /// File holding the tables
+        file: PathBuf,
+
+        /// Table to roll on (defaults to the only table in the file)
+        table: Option<String>,
+    },
+}
+
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Roll { expression } => {
-            match diceman::roll(&expression) {
+        Commands::Roll {
+            expression,
+            vars,
+            fate,
+            vs,
+        } => {
+            let config = diceman::EvalConfig {
+                fate: fate || vs.is_some(),
+                ..diceman::EvalConfig::default()
+            };
+            let rolled = diceman::parse(&expression).and_then(|parsed| {
+                let bound = parsed.substitute(&parse_vars(&vars));
+                diceman::roller::evaluate_with_config(&bound, &mut diceman::FastRng::new(), &config)
+            });
+            match rolled {
+                Ok(result) if config.fate => print_fate(result, vs),
                 Ok(result) => {
                     println!("{}", result.expression);
                 }
@@ -51,8 +111,40 @@ fn main() {
                 }
             }
         }
-        Commands::Sim { expression, n, json } => {
-            match diceman::simulate(&expression, n) {
+        Commands::Sim {
+            expression,
+            n,
+            json,
+            sets,
+            vars,
+        } => {
+            // Simulate the bound expression itself, so values the notation
+            // can't spell survive
+            let parsed = match diceman::parse(&expression) {
+                Ok(parsed) => parsed.substitute(&parse_vars(&vars)),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let expression = if vars.is_empty() { expression } else { parsed.to_string() };
+            if sets {
+                match diceman::simulate_sets_expr(&parsed, n) {
+                    Ok(result) => {
+                        if json {
+                            print_sets_json(&result);
+                        } else {
+                            print_sets(&expression, &result);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            match diceman::simulate_expr(&parsed, n) {
                 Ok(result) => {
                     if json {
                         print_sim_json(&result);
@@ -66,12 +158,109 @@ fn main() {
                 }
             }
         }
+        Commands::Check { expression } => {
+            let parsed = match diceman::parse(&expression) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let diagnostics = diceman::validate(&parsed);
+            if diagnostics.is_empty() {
+                println!("{}: ok", expression);
+            }
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+            if diagnostics.iter().any(|d| d.is_error()) {
+                std::process::exit(1);
+            }
+        }
+        Commands::Table {
+            command: TableCommand::Roll { file, table },
+        } => {
+            let tables = load_tables(&file).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            let name = match table {
+                Some(name) => name,
+                None => match tables.tables() {
+                    [only] => only.name.clone(),
+                    all => {
+                        let names: Vec<_> = all.iter().map(|t| t.name.as_str()).collect();
+                        eprintln!("Error: {} has several tables, pick one of: {}", file.display(), names.join(", "));
+                        std::process::exit(1);
+                    }
+                },
+            };
+            match tables.roll(&name, &mut diceman::FastRng::new()) {
+                Ok(result) => println!("{}", result),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         Commands::Notation => {
             print_notation_reference();
         }
     }
 }
 
+/// Load tables from a file, reading its format from the extension. A CSV
+/// file holds one table named after the file.
+fn load_tables(file: &Path) -> Result<TableSet, String> {
+    let source = std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let loaded = match file.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => TableSet::from_toml(&source),
+        Some("json") => TableSet::from_json(&source),
+        Some("csv") => {
+            let name = file.file_stem().and_then(|stem| stem.to_str()).unwrap_or("table");
+            TableSet::from_csv(name, &source)
+        }
+        _ => return Err(format!("{}: expected a .toml, .json or .csv file", file.display())),
+    };
+    loaded.map_err(|e| format!("{}: {}", file.display(), e))
+}
+
+/// Parse `NAME=VALUE` arguments, exiting with an error on a malformed one.
+fn parse_vars(vars: &[String]) -> HashMap<String, i64> {
+    vars.iter()
+        .map(|var| {
+            let parsed = var
+                .split_once('=')
+                .and_then(|(name, value)| Some((name.trim_start_matches('@').to_string(), value.trim().parse().ok()?)));
+            parsed.unwrap_or_else(|| {
+                eprintln!("Error: expected NAME=VALUE with a whole number value, found '{}'", var);
+                std::process::exit(1);
+            })
+        })
+        .collect()
+}
+
+/// Print a roll with its total on the Fate ladder, and the outcome against `vs`.
+fn print_fate(result: diceman::RollResult, vs: Option<i64>) {
+    let rung = Rung(result.total);
+    match vs {
+        Some(difficulty) => {
+            let action = FateOutcome::against(result, difficulty);
+            let shift_word = if action.shifts.abs() == 1 { "shift" } else { "shifts" };
+            println!(
+                "{}, {} vs {}: {} ({} {})",
+                action.result.expression,
+                rung,
+                Rung(difficulty),
+                action.outcome,
+                action.shifts,
+                shift_word
+            );
+        }
+        None => println!("{}, {}", result.expression, rung),
+    }
+}
+
 fn print_sim_json(result: &diceman::SimResult) {
     use serde_json::json;
 
@@ -82,25 +271,70 @@ fn print_sim_json(result: &diceman::SimResult) {
         "mean": result.mean,
         "std_dev": result.std_dev,
         "distribution": result.distribution,
+        "bands": result
+            .bands
+            .iter()
+            .map(|(label, count)| json!({ "label": label, "count": count }))
+            .collect::<Vec<_>>(),
     });
 
     println!("{}", serde_json::to_string_pretty(&output).unwrap());
 }
 
+fn print_sets_json(result: &diceman::SetSimResult) {
+    use serde_json::json;
+
+    let output = json!({
+        "n": result.n,
+        "widest": result.widest,
+    });
+
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+fn print_sets(expression: &str, result: &diceman::SetSimResult) {
+    println!("{} (n={})", expression, result.n);
+    println!();
+
+    let widest = result.widest.keys().copied().max().unwrap_or(0);
+    if widest == 0 {
+        println!("no sets");
+        return;
+    }
+    for width in 2..=widest.max(2) {
+        let name = match width {
+            2 => "pair".to_string(),
+            3 => "triple".to_string(),
+            n => format!("set of {}", n),
+        };
+        println!("at least one {:10} {:5.1}%", format!("{}:", name), result.at_least(width) * 100.0);
+    }
+}
+
 fn print_sim_histogram(expression: &str, result: &diceman::SimResult) {
     println!("{} (n={})", expression, result.n);
     println!();
 
-    let outcomes = result.sorted_outcomes();
-    let max_count = outcomes.iter().map(|(_, c)| *c).max().unwrap_or(1);
+    // With an outcome table, show how often each band comes up instead of each total
+    let outcomes: Vec<(String, usize)> = if result.bands.is_empty() {
+        result
+            .sorted_outcomes()
+            .into_iter()
+            .map(|(value, count)| (value.to_string(), count))
+            .collect()
+    } else {
+        result.bands.clone()
+    };
+    let max_count = outcomes.iter().map(|(_, c)| *c).max().unwrap_or(1).max(1);
     let max_bar_width = 40;
+    let label_width = outcomes.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0).max(4);
 
-    for (value, count) in outcomes {
+    for (label, count) in outcomes {
         let pct = (count as f64 / result.n as f64) * 100.0;
         let bar_width = (count as f64 / max_count as f64 * max_bar_width as f64) as usize;
         let bar: String = "█".repeat(bar_width);
 
-        println!("{:>4}: {:40} {:5.1}%", value, bar, pct);
+        println!("{:>width$}: {:40} {:5.1}%", label, bar, pct, width = label_width);
     }
 
     println!();
@@ -114,19 +348,43 @@ fn print_notation_reference() {
 BASIC ROLLS
   NdS       Roll N dice with S sides (2d6, 1d20)
   dS        Roll 1 die (d20 = 1d20)
-  d%        Percentile die (d100)
-  dF        Fudge die (-1, 0, +1)
+  d%        Percentile die (d100), shown as tens+units: 1d%[47 (40+7)]
+  dF        Fudge die (-1, 0, +1); roll --fate prints them as [+ - 0 +]
+
+ROLLED COUNTS AND SIDES
+  (X)dS     Roll X dice, where X is rolled first ((1d4)d6)
+  Nd(X)     Roll dice with X sides (1d(2d4))
+  @NAME     A variable, set with --var NAME=VALUE (@level d8)
+
+  A negative count or fewer than one side fails the roll.
 
 ARITHMETIC
   + - * /   Basic operations (2d6 + 5, (1d6 + 2) * 3)
+  //        Floor division (-7 // 2 = -4; / truncates to -3)
   (...)     Grouping
 
+ROUNDING
+  floor(X)  Round down (floor(8d6 / 2) for half damage)
+  ceil(X)   Round up
+  round(X)  Round to nearest, halves away from zero
+
+  Division inside a rounding function is exact, so
+  ceil(1/2 + 1/2) = 1.
+
+FUNCTIONS
+  min(A, B, ...)    Smallest value
+  max(A, B, ...)    Largest value (max(1d6 - 2, 1) for minimum 1)
+  abs(X)            Absolute value
+  clamp(X, LO, HI)  Limit X to the range LO..HI
+
 KEEP AND DROP
   khN       Keep highest N dice (4d6kh3)
   klN       Keep lowest N dice (2d20kl1 for disadvantage)
   kN        Keep highest N (shorthand for khN)
   dhN       Drop highest N dice
   dlN       Drop lowest N dice (4d6dl1)
+  k>=N      Keep only dice matching a condition (6d6k>=4)
+  d<N       Drop dice matching a condition (6d6d<3)
 
 EXPLODING DICE
   !         Explode on max, new die per explosion (Roll20 style)
@@ -139,6 +397,7 @@ EXPLODING DICE
   !>=N      Explode on greater than or equal to N
   !<N       Explode on less than N
   !=N       Explode on equal to N
+  !=        Explode on max, written out before another modifier (1d(1d4)!=!>3)
 
   Examples:
   1d6!      Standard exploding d6
@@ -146,16 +405,44 @@ EXPLODING DICE
   1d6!p     Penetrating (6+5+3 shows as [6, 4, 2])
   1d10!>=8  Explode on 8, 9, or 10
 
+WILD DIE
+  w         Add an acing d6 wild die, keep the highest (Savage Worlds)
+  wN        Add an acing dN wild die
+
+  Examples:
+  1d8w      Trait d8 with a d6 wild die: [(5), 9w]
+  1d10w6+2  Trait d10, wild d6, plus 2
+
 REROLL
   r         Reroll 1s until not 1
   ro        Reroll once only
   r<N       Reroll below N
   r<=N      Reroll at or below N
+  rb<N      Reroll once, keep the better roll
+  rw>N      Reroll once, keep the worse roll
 
   Examples:
   1d6r      Reroll 1s
   2d6r<3    Reroll 1s and 2s
   1d20ro    Reroll first 1 only
+  1d20rb<10 Reroll under 10, keep the better: [15/(3)]
+
+MINIMUM AND MAXIMUM PER DIE
+  minN      Count any die below N as N
+  maxN      Count any die above N as N
+
+  Examples:
+  2d6min3   Treat 1s and 2s as 3s: [3/(1), 5]
+  1d20min10 Never lower than 10
+
+MATCHING SETS
+  m         Report sets of matching dice (pairs and up)
+  mN        Report sets of N or more matching dice
+  mt, mtN   Total the number of sets instead of the dice
+
+  Sets are listed as width x value, widest first.
+  Example: 6d10m shows [7, 3, 7, 1, 7, 3] = 28 (3x7, 2x3)
+  Use `diceman sim --sets 5d10m` for the chance of a pair or triple.
 
 SUCCESS COUNTING
   >N        Count dice greater than N
@@ -164,13 +451,40 @@ SUCCESS COUNTING
   <=N       Count dice less than or equal to N
   =N        Count dice equal to N
 
+  After a success condition:
+  fN, f<N   Subtract a success per matching die (marked x)
+  ddN, dd>N Count matching successes twice (marked **)
+
   Examples:
   5d10>=8   World of Darkness (count 8, 9, 10)
   6d6>4     Count 5s and 6s
   8d6=6     Count only 6s
+  10d10>7f<3  Count 8-10, subtract 1s and 2s
+
+SORTING
+  s, sa     Sort dice lowest first
+  sd        Sort dice highest first
+
+  Sorting only changes the output, never which dice are kept.
+  Example: 4d6kh3sd shows [6, 5, 2, (1)]
 
 MODIFIER ORDER
-  Modifiers apply: reroll -> explode -> keep/drop -> success count
-  Example: 4d6r!kh3 rerolls 1s, explodes 6s, then keeps highest 3"#
+  Modifiers apply in the order written.
+  Example: 4d6r!kh3 rerolls 1s, explodes 6s, then keeps highest 3
+           4d6kh3! keeps highest 3, then explodes the kept 6s
+  `diceman check` warns when the order differs from
+  reroll/min/max -> explode -> keep/drop -> success count
+
+OUTCOME BANDS
+  EXPR {{RANGE: LABEL, ...}}  Name ranges of the total
+  <=N, <N   Totals up to N
+  >=N, >N   Totals from N up
+  N..M      Totals from N to M
+  N, =N     Exactly N
+
+  Example: 2d6+1 {{<=6: miss, 7..9: weak hit, >=10: strong}}
+           shows [2, 4] = 6 + 1 = 7, weak hit
+  Quote a label holding ',' or '}}': {{<=3: "low, but alive"}}
+  `diceman sim` shows the chance of each band; the first matching band wins."#
     );
 }
@@ -14,6 +14,8 @@ pub struct RollResult {
     pub total: i64,
     #[pyo3(get)]
     pub expression: String,
+    #[pyo3(get)]
+    pub band: Option<String>,
 }
 
 #[pymethods]
@@ -43,6 +45,8 @@ pub struct SimResult {
     pub std_dev: f64,
     #[pyo3(get)]
     pub n: usize,
+    #[pyo3(get)]
+    pub bands: Vec<(String, usize)>,
 }
 
 #[pymethods]
@@ -70,6 +74,14 @@ impl SimResult {
             .collect()
     }
 
+    /// Get probability of each outcome band, in the order they are written.
+    fn band_probabilities(&self) -> Vec<(String, f64)> {
+        self.bands
+            .iter()
+            .map(|(label, count)| (label.clone(), *count as f64 / self.n as f64))
+            .collect()
+    }
+
     fn __repr__(&self) -> String {
         format!(
             "SimResult(n={}, mean={:.2}, std_dev={:.2}, min={}, max={})",
@@ -98,6 +110,7 @@ fn roll(expr: &str) -> PyResult<RollResult> {
         .map(|r| RollResult {
             total: r.total,
             expression: r.expression,
+            band: r.band,
         })
         .map_err(|e| PyValueError::new_err(e.to_string()))
 }
@@ -126,6 +139,7 @@ fn simulate(expr: &str, n: usize) -> PyResult<SimResult> {
             mean: r.mean,
             std_dev: r.std_dev,
             n: r.n,
+            bands: r.bands,
         })
         .map_err(|e| PyValueError::new_err(e.to_string()))
 }
@@ -0,0 +1,51 @@
+// ABOUTME: Compares full evaluation against the totals-only rolling paths.
+// ABOUTME: Run with `cargo bench -p diceman`; uses std timing, no bench framework.
+
+use diceman::{parse, roll_total, roller::evaluate_with_rng, FastRng, RollPlan};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const EXPRESSIONS: [&str; 4] = ["100d6", "4d6kh3", "10d10!>=8", "10d10>=8f1"];
+const ITERATIONS: u32 = 200_000;
+
+/// Time `ITERATIONS` calls of `f` after a short warm-up.
+fn time(mut f: impl FnMut() -> i64) -> Duration {
+    for _ in 0..ITERATIONS / 10 {
+        black_box(f());
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+fn per_roll(elapsed: Duration) -> f64 {
+    elapsed.as_nanos() as f64 / ITERATIONS as f64
+}
+
+fn main() {
+    println!(
+        "{:<12} {:>12} {:>12} {:>12} {:>9} {:>9}",
+        "expression", "evaluate", "roll_total", "RollPlan", "total x", "plan x"
+    );
+    for input in EXPRESSIONS {
+        let expr = parse(input).unwrap();
+        let mut rng = FastRng::with_seed(1);
+
+        let evaluate = time(|| evaluate_with_rng(&expr, &mut rng).unwrap().total);
+        let total = time(|| roll_total(&expr, &mut rng).unwrap());
+        let mut plan = RollPlan::new(&expr);
+        let planned = time(|| plan.roll(&mut rng).unwrap());
+
+        println!(
+            "{:<12} {:>9.1} ns {:>9.1} ns {:>9.1} ns {:>8.1}x {:>8.1}x",
+            input,
+            per_roll(evaluate),
+            per_roll(total),
+            per_roll(planned),
+            evaluate.as_secs_f64() / total.as_secs_f64(),
+            evaluate.as_secs_f64() / planned.as_secs_f64(),
+        );
+    }
+}
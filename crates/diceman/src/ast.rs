@@ -1,7 +1,9 @@
 // ABOUTME: Abstract Syntax Tree types for dice notation expressions.
 // ABOUTME: Represents parsed dice expressions like "4d6kh3+5".
 
+use std::collections::HashMap;
 use std::fmt;
+use std::ops::RangeInclusive;
 
 /// A complete dice expression.
 #[derive(Debug, Clone, PartialEq)]
@@ -18,6 +20,230 @@ pub enum Expr {
     },
     /// A parenthesized group.
     Group(Box<Expr>),
+    /// A built-in function call (e.g., "floor(8d6 / 2)").
+    Call { func: Func, args: Vec<Expr> },
+    /// A named value (e.g., "@level"), bound with [`Expr::substitute`].
+    Var(String),
+    /// A dice roll whose count or sides are evaluated first (e.g., "(1d4)d6").
+    DynamicRoll(DynamicRoll),
+    /// An expression whose total is read off a table of outcome bands
+    /// (e.g., "2d6+1 {<=6: miss, 7..9: weak, >=10: strong}"). The parser
+    /// only accepts a table at the end of the whole expression.
+    Bands { expr: Box<Expr>, bands: Vec<Band> },
+}
+
+impl Expr {
+    /// Returns true if evaluating this expression rolls no dice.
+    pub fn is_constant(&self) -> bool {
+        match self {
+            Expr::Number(_) => true,
+            Expr::Roll(_) => false,
+            Expr::BinOp { left, right, .. } => left.is_constant() && right.is_constant(),
+            Expr::Group(inner) => inner.is_constant(),
+            Expr::Call { args, .. } => args.iter().all(Expr::is_constant),
+            Expr::Bands { expr, .. } => expr.is_constant(),
+            // A variable's value isn't known until it is bound
+            Expr::Var(_) | Expr::DynamicRoll(_) => false,
+        }
+    }
+
+    /// Replace each variable with its value in `vars`.
+    ///
+    /// Variables missing from `vars` are left in place, and fail the roll
+    /// with [`Error::UnknownVariable`](crate::Error::UnknownVariable).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// let expr = diceman::parse("@level d8 + @con").unwrap();
+    /// let vars = HashMap::from([("level".to_string(), 3), ("con".to_string(), 2)]);
+    /// assert_eq!(expr.substitute(&vars).to_string(), "3d8 + 2");
+    /// ```
+    pub fn substitute(&self, vars: &HashMap<String, i64>) -> Expr {
+        match self {
+            Expr::Number(_) | Expr::Roll(_) => self.clone(),
+            Expr::Var(name) => match vars.get(name) {
+                Some(value) => Expr::Number(*value),
+                None => self.clone(),
+            },
+            Expr::BinOp { op, left, right } => Expr::BinOp {
+                op: *op,
+                left: Box::new(left.substitute(vars)),
+                right: Box::new(right.substitute(vars)),
+            },
+            Expr::Group(inner) => Expr::Group(Box::new(inner.substitute(vars))),
+            Expr::Call { func, args } => Expr::Call {
+                func: *func,
+                args: args.iter().map(|arg| arg.substitute(vars)).collect(),
+            },
+            Expr::DynamicRoll(roll) => Expr::DynamicRoll(DynamicRoll {
+                count: Box::new(roll.count.substitute(vars)),
+                sides: match &roll.sides {
+                    DynamicSides::Fixed(sides) => DynamicSides::Fixed(*sides),
+                    DynamicSides::Expr(sides) => DynamicSides::Expr(Box::new(sides.substitute(vars))),
+                },
+                modifiers: roll.modifiers.clone(),
+            }),
+            Expr::Bands { expr, bands } => Expr::Bands {
+                expr: Box::new(expr.substitute(vars)),
+                bands: bands.clone(),
+            },
+        }
+    }
+
+    /// The outcome table this expression's total is read off, looking
+    /// through parentheses, as in `(2d6 {<=6: miss, >=7: hit})`.
+    pub fn outcome_table(&self) -> Option<&[Band]> {
+        match self {
+            Expr::Bands { bands, .. } => Some(bands),
+            Expr::Group(inner) => inner.outcome_table(),
+            _ => None,
+        }
+    }
+
+    /// The label of the band `total` falls in, if this expression has an
+    /// outcome table. When bands overlap, the first one written wins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let expr = diceman::parse("2d6 {<=6: miss, 7..9: weak, >=10: strong}").unwrap();
+    /// assert_eq!(expr.band(8), Some("weak"));
+    /// assert_eq!(diceman::parse("(2d6 {<=6: miss, >=7: hit})").unwrap().band(8), Some("hit"));
+    /// assert_eq!(diceman::parse("2d6").unwrap().band(8), None);
+    /// ```
+    pub fn band(&self, total: i64) -> Option<&str> {
+        let bands = self.outcome_table()?;
+        bands.iter().find(|band| band.contains(total)).map(|band| band.label.as_str())
+    }
+
+    /// How tightly this expression binds when printed: 1 for `+`/`-`,
+    /// 2 for `*`/`/`/`//`, and 3 for anything that never needs parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::BinOp { op, left, right } if !is_negation(*op, left, right) => op.precedence(),
+            Expr::Bands { .. } => 0,
+            _ => 3,
+        }
+    }
+}
+
+/// Returns true if this operation is how the parser represents unary minus (`-x`).
+fn is_negation(op: Op, left: &Expr, right: &Expr) -> bool {
+    op == Op::Sub && *left == Expr::Number(0) && right.precedence() == 3
+}
+
+/// Prints canonical notation that re-parses to the same AST.
+///
+/// Parentheses are only added where the tree's shape requires them, so an
+/// expression built by hand as `(1 + 2) * 3` without a `Group` still prints
+/// correctly.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Negative literals print as negation, and the lexer can't read
+            // the magnitude of i64::MIN on its own
+            Expr::Number(i64::MIN) => write!(f, "(-{} - 1)", i64::MAX),
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Roll(roll) => write!(f, "{}", roll),
+            Expr::BinOp { op, left, right } => {
+                if is_negation(*op, left, right) {
+                    return write!(f, "-{}", right);
+                }
+                // Operators are left-associative, so a right operand of equal
+                // precedence needs parentheses too.
+                let precedence = op.precedence();
+                if left.precedence() < precedence {
+                    write!(f, "({})", left)?;
+                } else {
+                    write!(f, "{}", left)?;
+                }
+                write!(f, " {} ", op)?;
+                if right.precedence() <= precedence {
+                    write!(f, "({})", right)
+                } else {
+                    write!(f, "{}", right)
+                }
+            }
+            Expr::Group(inner) => write!(f, "({})", inner),
+            Expr::Call { func, args } => {
+                write!(f, "{}(", func)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    if arg.precedence() == 0 {
+                        write!(f, "({})", arg)?;
+                    } else {
+                        write!(f, "{}", arg)?;
+                    }
+                }
+                write!(f, ")")
+            }
+            Expr::Var(name) => write!(f, "@{}", name),
+            Expr::DynamicRoll(roll) => write!(f, "{}", roll),
+            Expr::Bands { expr, bands } => {
+                if expr.precedence() == 0 {
+                    write!(f, "({}) {{", expr)?;
+                } else {
+                    write!(f, "{} {{", expr)?;
+                }
+                for (i, band) in bands.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", band)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// A labelled range of totals in an outcome table (e.g., "7..9: weak").
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Band {
+    /// The totals this band covers.
+    pub range: RangeInclusive<i64>,
+    /// The name reported when a total falls in the range.
+    pub label: String,
+}
+
+impl Band {
+    /// Returns true if `total` falls in this band.
+    pub fn contains(&self, total: i64) -> bool {
+        self.range.contains(&total)
+    }
+}
+
+impl fmt::Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (*self.range.start(), *self.range.end()) {
+            (i64::MIN, end) => write!(f, "<={}", end)?,
+            (start, i64::MAX) => write!(f, ">={}", start)?,
+            (start, end) if start == end => write!(f, "{}", start)?,
+            (start, end) => write!(f, "{}..{}", start, end)?,
+        }
+        // Quote a label that plain text can't spell: one that is empty,
+        // ends a band early, or would lose its outer whitespace
+        let plain = !self.label.is_empty()
+            && self.label.trim() == self.label
+            && !self.label.starts_with('"')
+            && !self.label.contains([',', '}']);
+        if plain {
+            return write!(f, ": {}", self.label);
+        }
+        write!(f, ": \"")?;
+        for ch in self.label.chars() {
+            if ch == '"' || ch == '\\' {
+                write!(f, "\\")?;
+            }
+            write!(f, "{}", ch)?;
+        }
+        write!(f, "\"")
+    }
 }
 
 /// A dice roll expression (e.g., "4d6kh3").
@@ -31,6 +257,141 @@ pub struct Roll {
     pub modifiers: Vec<Modifier>,
 }
 
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.count, self.sides)?;
+        write_modifiers(f, &self.modifiers, Some(&self.sides))
+    }
+}
+
+/// Prints a roll's modifiers. `sides` is `None` when they are rolled, in
+/// which case a default explode condition is spelled out as a bare `=`.
+fn write_modifiers(f: &mut fmt::Formatter<'_>, modifiers: &[Modifier], sides: Option<&Sides>) -> fmt::Result {
+    for (i, modifier) in modifiers.iter().enumerate() {
+        // A modifier relying on its default condition would swallow a
+        // following "!" or comparison ("1d6!" then ">4" reads as "1d6!>4"),
+        // so spell the default out in that case.
+        let next_is_absorbed = matches!(
+            (modifier, modifiers.get(i + 1)),
+            (_, Some(Modifier::CountSuccesses(_)))
+                | (
+                    Modifier::Explode {
+                        compounding: false,
+                        penetrating: false,
+                        ..
+                    },
+                    // "!w6" reads as the wild die's ace
+                    Some(Modifier::Explode { .. } | Modifier::WildDie(_)),
+                )
+                // "rw6" reads as a keep-worse reroll
+                | (Modifier::Reroll { .. }, Some(Modifier::WildDie(_)))
+        );
+        match (modifier, sides) {
+            // "s" then "d<3" would read as a descending sort
+            (Modifier::Sort { descending: false }, _)
+                if matches!(modifiers.get(i + 1), Some(Modifier::DropWhere(_))) =>
+            {
+                write!(f, "sa")?;
+            }
+            (
+                Modifier::Explode {
+                    compounding,
+                    penetrating,
+                    condition: None,
+                },
+                Some(sides),
+            ) if next_is_absorbed => {
+                let explicit = Modifier::Explode {
+                    compounding: *compounding,
+                    penetrating: *penetrating,
+                    condition: Some(Condition::explode_default(sides)),
+                };
+                write!(f, "{}", explicit)?;
+            }
+            (Modifier::Explode { condition: None, .. }, None) if next_is_absorbed => {
+                write!(f, "{}=", modifier)?;
+            }
+            (
+                Modifier::Reroll {
+                    once,
+                    condition: None,
+                },
+                _,
+            ) if next_is_absorbed => {
+                let explicit = Modifier::Reroll {
+                    once: *once,
+                    condition: Some(Condition::reroll_default()),
+                };
+                write!(f, "{}", explicit)?;
+            }
+            (Modifier::RerollKeep { best, condition: None }, _) if next_is_absorbed => {
+                let explicit = Modifier::RerollKeep {
+                    best: *best,
+                    condition: Some(Condition::reroll_default()),
+                };
+                write!(f, "{}", explicit)?;
+            }
+            _ => write!(f, "{}", modifier)?,
+        }
+    }
+    Ok(())
+}
+
+/// A dice roll whose count or sides come from an expression (e.g.,
+/// "(1d4)d6", "1d(2d4)" or "@level d8").
+///
+/// The count is evaluated first, then the sides, then the dice are rolled as
+/// a plain [`Roll`]. A negative count or fewer than one side fails the roll.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicRoll {
+    /// Number of dice to roll.
+    pub count: Box<Expr>,
+    /// Type of dice, or the expression giving the number of sides.
+    pub sides: DynamicSides,
+    /// Modifiers applied to the roll.
+    pub modifiers: Vec<Modifier>,
+}
+
+/// The sides of a [`DynamicRoll`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicSides {
+    /// A fixed die type, as in "(1d4)d6".
+    Fixed(Sides),
+    /// A number of sides evaluated before rolling, as in "1d(2d4)".
+    Expr(Box<Expr>),
+}
+
+impl fmt::Display for DynamicRoll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.count {
+            Expr::Number(n) if *n >= 0 => write!(f, "{}", n)?,
+            Expr::Group(_) => write!(f, "{}", self.count)?,
+            // "@leveld8" would read as one long name
+            Expr::Var(_) => write!(f, "{} ", self.count)?,
+            count => write!(f, "({})", count)?,
+        }
+        write!(f, "d")?;
+        match &self.sides {
+            DynamicSides::Fixed(sides) => write!(f, "{}", sides)?,
+            DynamicSides::Expr(sides) => match &**sides {
+                Expr::Number(n) if *n > 0 && u32::try_from(*n).is_ok() => write!(f, "{}", n)?,
+                Expr::Group(_) => write!(f, "{}", sides)?,
+                Expr::Var(_) if self.modifiers.is_empty() => write!(f, "{}", sides)?,
+                sides => write!(f, "({})", sides)?,
+            },
+        }
+        // Constant sides print as a plain number, so they have a maximum too
+        let sides = match &self.sides {
+            DynamicSides::Fixed(sides) => Some(*sides),
+            DynamicSides::Expr(sides) => match **sides {
+                Expr::Number(n) if n > 0 => u32::try_from(n).ok().map(Sides::Number),
+                _ => None,
+            },
+        };
+        write_modifiers(f, &self.modifiers, sides.as_ref())
+    }
+}
+
 /// The type of dice to roll.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Sides {
@@ -51,6 +412,25 @@ impl Sides {
             Sides::Fudge => 3, // -1, 0, 1
         }
     }
+
+    /// Returns the lowest and highest face values for this die type.
+    pub fn faces(&self) -> (i64, i64) {
+        match self {
+            Sides::Number(n) => (1, *n as i64),
+            Sides::Percent => (1, 100),
+            Sides::Fudge => (-1, 1),
+        }
+    }
+}
+
+impl fmt::Display for Sides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sides::Number(n) => write!(f, "{}", n),
+            Sides::Percent => write!(f, "%"),
+            Sides::Fudge => write!(f, "F"),
+        }
+    }
 }
 
 /// A binary operator.
@@ -59,7 +439,19 @@ pub enum Op {
     Add,
     Sub,
     Mul,
+    /// Integer division, truncating toward zero.
     Div,
+    /// Integer division, rounding toward negative infinity.
+    FloorDiv,
+}
+
+impl Op {
+    fn precedence(&self) -> u8 {
+        match self {
+            Op::Add | Op::Sub => 1,
+            Op::Mul | Op::Div | Op::FloorDiv => 2,
+        }
+    }
 }
 
 impl fmt::Display for Op {
@@ -69,8 +461,78 @@ impl fmt::Display for Op {
             Op::Sub => write!(f, "-"),
             Op::Mul => write!(f, "*"),
             Op::Div => write!(f, "/"),
+            Op::FloorDiv => write!(f, "//"),
+        }
+    }
+}
+
+/// A built-in function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Func {
+    /// Round down to the nearest integer.
+    Floor,
+    /// Round up to the nearest integer.
+    Ceil,
+    /// Round to the nearest integer, with halves rounding away from zero.
+    Round,
+    /// The smallest of two or more values.
+    Min,
+    /// The largest of two or more values.
+    Max,
+    /// The absolute value.
+    Abs,
+    /// Clamp a value between a lower and upper bound: clamp(x, lo, hi).
+    Clamp,
+}
+
+impl Func {
+    /// Look up a function by name (case-insensitive).
+    pub fn from_name(name: &str) -> Option<Func> {
+        match name.to_ascii_lowercase().as_str() {
+            "floor" => Some(Func::Floor),
+            "ceil" => Some(Func::Ceil),
+            "round" => Some(Func::Round),
+            "min" => Some(Func::Min),
+            "max" => Some(Func::Max),
+            "abs" => Some(Func::Abs),
+            "clamp" => Some(Func::Clamp),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of this function as written in notation.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Func::Floor => "floor",
+            Func::Ceil => "ceil",
+            Func::Round => "round",
+            Func::Min => "min",
+            Func::Max => "max",
+            Func::Abs => "abs",
+            Func::Clamp => "clamp",
         }
     }
+
+    /// Returns the range of argument counts this function accepts.
+    pub fn arity(&self) -> RangeInclusive<usize> {
+        match self {
+            Func::Floor | Func::Ceil | Func::Round | Func::Abs => 1..=1,
+            Func::Min | Func::Max => 2..=usize::MAX,
+            Func::Clamp => 3..=3,
+        }
+    }
+
+    /// Returns true for `floor`, `ceil` and `round`, whose argument is
+    /// evaluated exactly rather than with truncating division.
+    pub fn is_rounding(&self) -> bool {
+        matches!(self, Func::Floor | Func::Ceil | Func::Round)
+    }
+}
+
+impl fmt::Display for Func {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
 }
 
 /// A modifier applied to a dice roll.
@@ -84,6 +546,11 @@ pub enum Modifier {
     DropHighest(u32),
     /// Drop the lowest N dice.
     DropLowest(u32),
+    /// Keep only the dice matching the condition (`k>=4`).
+    KeepWhere(Condition),
+    /// Drop the dice matching the condition (`d<3`). Straight after a success,
+    /// failure or double condition, `d<3` reads as a double instead.
+    DropWhere(Condition),
     /// Explode dice matching the condition.
     Explode {
         /// If true, add explosions to same die (compounding/Shadowrun).
@@ -101,8 +568,119 @@ pub enum Modifier {
         /// The condition for reroll (defaults to 1).
         condition: Option<Condition>,
     },
+    /// Reroll dice matching the condition once, keeping the better (`rb`) or
+    /// worse (`rw`) of the two rolls.
+    RerollKeep {
+        /// If true, keep the higher roll.
+        best: bool,
+        /// The condition for reroll (defaults to 1).
+        condition: Option<Condition>,
+    },
+    /// Savage Worlds wild die (`w`, `w6`): ace (compound-explode) every die,
+    /// roll an aced wild die with this many sides alongside them, and keep
+    /// the highest dice, as many as there were before.
+    WildDie(u32),
+    /// Raise each die below the value to it (`min3`).
+    Min(i64),
+    /// Lower each die above the value to it (`max3`).
+    Max(i64),
     /// Count successes: count dice matching condition instead of summing.
     CountSuccesses(Condition),
+    /// Subtract a success for each die matching the condition (`f1`).
+    /// Only follows a success condition.
+    CountFailures(Condition),
+    /// Count successes matching the condition twice (`dd10`). Only follows a
+    /// success condition.
+    DoubleSuccesses(Condition),
+    /// Sort the dice in the result (`s`, `sd`). Applied after every other
+    /// modifier, so it never changes which dice are kept.
+    Sort {
+        /// If true, sort highest first.
+        descending: bool,
+    },
+    /// Find sets of at least `width` matching dice among the kept dice
+    /// (`m`, `mt3`).
+    Matches {
+        /// The smallest set to report (defaults to 2).
+        width: u32,
+        /// If true, the total is the number of sets instead of the sum.
+        count_sets: bool,
+    },
+}
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Modifier::KeepHighest(n) => write!(f, "kh{}", n),
+            Modifier::KeepLowest(n) => write!(f, "kl{}", n),
+            Modifier::DropHighest(n) => write!(f, "dh{}", n),
+            Modifier::DropLowest(n) => write!(f, "dl{}", n),
+            Modifier::KeepWhere(c) => write!(f, "k{}", c),
+            Modifier::DropWhere(c) => write!(f, "d{}", c),
+            Modifier::Explode {
+                compounding,
+                penetrating,
+                condition,
+            } => {
+                write!(f, "!")?;
+                if *compounding {
+                    write!(f, "!")?;
+                }
+                if *penetrating {
+                    write!(f, "p")?;
+                }
+                if let Some(c) = condition {
+                    write!(f, "{}", c)?;
+                }
+                Ok(())
+            }
+            Modifier::Reroll { once, condition } => {
+                write!(f, "r")?;
+                if *once {
+                    write!(f, "o")?;
+                }
+                if let Some(c) = condition {
+                    write!(f, "{}", c)?;
+                }
+                Ok(())
+            }
+            Modifier::RerollKeep { best, condition } => {
+                write!(f, "{}", if *best { "rb" } else { "rw" })?;
+                if let Some(c) = condition {
+                    write!(f, "{}", c)?;
+                }
+                Ok(())
+            }
+            Modifier::WildDie(n) => write!(f, "w{}", n),
+            Modifier::Min(n) => write!(f, "min{}", n),
+            Modifier::Max(n) => write!(f, "max{}", n),
+            Modifier::CountSuccesses(c) => write!(f, "{}", c),
+            Modifier::CountFailures(c) => write!(f, "f{}", Shorthand(c)),
+            Modifier::DoubleSuccesses(c) => write!(f, "dd{}", Shorthand(c)),
+            Modifier::Sort { descending: false } => write!(f, "s"),
+            Modifier::Sort { descending: true } => write!(f, "sd"),
+            Modifier::Matches {
+                width,
+                count_sets: false,
+            } => write!(f, "m{}", width),
+            Modifier::Matches {
+                width,
+                count_sets: true,
+            } => write!(f, "mt{}", width),
+        }
+    }
+}
+
+/// Prints a condition with `=` left implicit, as in `f1` for `f=1`.
+struct Shorthand<'a>(&'a Condition);
+
+impl fmt::Display for Shorthand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.compare {
+            Compare::Equal => write!(f, "{}", self.0.value),
+            _ => write!(f, "{}", self.0),
+        }
+    }
 }
 
 /// A comparison condition for explode/reroll.
@@ -112,6 +690,30 @@ pub struct Condition {
     pub value: i64,
 }
 
+impl Condition {
+    /// The condition used by explode modifiers without one: the die's maximum.
+    pub fn explode_default(sides: &Sides) -> Condition {
+        Condition {
+            compare: Compare::Equal,
+            value: sides.count() as i64,
+        }
+    }
+
+    /// The condition used by reroll modifiers without one: a roll of 1.
+    pub fn reroll_default() -> Condition {
+        Condition {
+            compare: Compare::Equal,
+            value: 1,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.compare, self.value)
+    }
+}
+
 /// A comparison operator.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Compare {
@@ -149,3 +751,215 @@ impl fmt::Display for Compare {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::roller::{evaluate_with_rng, FastRng};
+    use crate::testing::expr;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_display_canonical() {
+        let cases = [
+            ("d20", "1d20"),
+            ("4d6k3", "4d6kh3"),
+            ("2d6+5", "2d6 + 5"),
+            ("(1d6+2)*3", "(1d6 + 2) * 3"),
+            ("1d6!!p>=5r<2", "1d6!!p>=5r<2"),
+            ("5d10>=8", "5d10>=8"),
+            ("-1d4 - -2", "-1d4 - -2"),
+            ("MAX(1d6-2,1)", "max(1d6 - 2, 1)"),
+            ("floor(7 // 2)", "floor(7 // 2)"),
+            ("d%+dF", "1d% + 1dF"),
+            ("(1d4)d6kh1", "(1d4)d6kh1"),
+            ("@level  D8+@bonus", "@level d8 + @bonus"),
+            ("2d(1d4)!", "2d(1d4)!"),
+            ("d@size", "1d@size"),
+            ("1d20RB<10", "1d20rb<10"),
+            ("2d6rw>4", "2d6rw>4"),
+            ("2d6MIN3 + 1d20max10", "2d6min3 + 1d20max10"),
+            ("4dFsmin0", "4dFsmin0"),
+            ("4dFmin-1max1", "4dFmin-1max1"),
+            ("d8w", "1d8w6"),
+            ("d8!w6 + 1", "1d8w6 + 1"),
+            ("d8!=8W4", "1d8!=8w4"),
+            ("2d6+1{<7:miss,7..9:weak hit ,>9:strong}", "2d6 + 1 {<=6: miss, 7..9: weak hit, >=10: strong}"),
+            ("4dF {=0: even, -2..-1: low}", "4dF {0: even, -2..-1: low}"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_display_adds_needed_parens() {
+        // (1 + 2) * 3 without a Group node
+        let expr = Expr::BinOp {
+            op: Op::Mul,
+            left: Box::new(Expr::BinOp {
+                op: Op::Add,
+                left: Box::new(Expr::Number(1)),
+                right: Box::new(Expr::Number(2)),
+            }),
+            right: Box::new(Expr::Number(3)),
+        };
+        assert_eq!(expr.to_string(), "(1 + 2) * 3");
+
+        // 1 - (2 - 3) without a Group node
+        let expr = Expr::BinOp {
+            op: Op::Sub,
+            left: Box::new(Expr::Number(1)),
+            right: Box::new(Expr::BinOp {
+                op: Op::Sub,
+                left: Box::new(Expr::Number(2)),
+                right: Box::new(Expr::Number(3)),
+            }),
+        };
+        assert_eq!(expr.to_string(), "1 - (2 - 3)");
+
+        // Tables built inside a larger expression
+        let table = || Expr::from(Roll::new(2, 6)).bands([Band::at_most(6, "miss")]);
+        for (expr, printed) in [
+            (table() + 1, "(2d6 {<=6: miss}) + 1"),
+            (Expr::call(Func::Max, [table(), 3.into()]), "max((2d6 {<=6: miss}), 3)"),
+            (table().bands([Band::at_least(7, "hit")]), "(2d6 {<=6: miss}) {>=7: hit}"),
+        ] {
+            assert_eq!(expr.to_string(), printed);
+            assert_eq!(parse(printed).unwrap().to_string(), printed);
+        }
+    }
+
+    #[test]
+    fn test_display_quotes_labels() {
+        for (label, printed) in [
+            ("weak hit", "weak hit"),
+            ("hit, barely", r#""hit, barely""#),
+            ("}", r#""}""#),
+            (" padded ", r#"" padded ""#),
+            ("", r#""""#),
+            (r#""quoted" \ "#, r#""\"quoted\" \\ ""#),
+        ] {
+            let expr = Expr::from(Roll::new(1, 6)).bands([Band::new(1..=6, label)]);
+            assert_eq!(expr.to_string(), format!("1d6 {{1..6: {}}}", printed));
+            assert_eq!(parse(&expr.to_string()).unwrap(), expr);
+        }
+    }
+
+    #[test]
+    fn test_display_large_and_negative_numbers() {
+        for (expr, printed) in [
+            (Expr::Number(5_000_000_000), "5000000000"),
+            (Expr::Number(-7), "-7"),
+            (Expr::Number(i64::MAX), "9223372036854775807"),
+            (Expr::Number(i64::MIN), "(-9223372036854775807 - 1)"),
+            (Expr::Number(-7) * 2, "-7 * 2"),
+        ] {
+            assert_eq!(expr.to_string(), printed);
+            assert_eq!(parse(printed).unwrap().to_string(), printed);
+        }
+        assert!(matches!(parse("9223372036854775808"), Err(crate::Error::NumberTooLarge(0))));
+        // A count too large for a plain roll is left for the roll to reject
+        assert_eq!(parse("5000000000d6").unwrap().to_string(), "5000000000d6");
+        assert!(matches!(parse("1d5000000000"), Err(crate::Error::InvalidDiceSides(5_000_000_000))));
+    }
+
+    #[test]
+    fn test_display_drop_after_success() {
+        // A drop straight after a success condition prints as written
+        let roll = Roll::new(6, 6)
+            .count_successes(Condition::ge(4))
+            .drop_where(Condition::lt(3));
+        assert_eq!(roll.to_string(), "6d6>=4d<3");
+        assert_eq!(parse("6d6>=4d<3").unwrap(), Expr::from(roll));
+        let roll = Roll::new(6, 6)
+            .count_successes(Condition::ge(4))
+            .double_successes(Condition::eq(6))
+            .drop_where(Condition::lt(3));
+        assert_eq!(roll.to_string(), "6d6>=4dd6d<3");
+        assert_eq!(parse("6d6>=4dd6d<3").unwrap(), Expr::from(roll));
+    }
+
+    #[test]
+    fn test_display_ascending_sort_before_drop() {
+        // "6d6sd<3" would read as a descending sort, so the sort is spelled "sa"
+        let roll = Roll::new(6, 6).sort().drop_where(Condition::lt(3));
+        assert_eq!(roll.to_string(), "6d6sad<3");
+        assert_eq!(parse("6d6sad<3").unwrap(), Expr::from(roll));
+        let roll = Roll::new(6, 6).sort_descending().drop_where(Condition::lt(3));
+        assert_eq!(roll.to_string(), "6d6sdd<3");
+        assert_eq!(parse("6d6sdd<3").unwrap(), Expr::from(roll));
+    }
+
+    #[test]
+    fn test_display_spells_out_swallowed_defaults() {
+        let roll = Roll {
+            count: 1,
+            sides: Sides::Number(6),
+            modifiers: vec![
+                Modifier::Explode {
+                    compounding: false,
+                    penetrating: false,
+                    condition: None,
+                },
+                Modifier::CountSuccesses(Condition {
+                    compare: Compare::GreaterThan,
+                    value: 4,
+                }),
+            ],
+        };
+        assert_eq!(roll.to_string(), "1d6!=6>4");
+        assert_eq!(parse(&roll.to_string()).unwrap().to_string(), "1d6!=6>4");
+
+        let roll = Roll::new(2, 20)
+            .modifier(Modifier::RerollKeep {
+                best: true,
+                condition: None,
+            })
+            .count_successes(Condition::ge(10));
+        assert_eq!(roll.to_string(), "2d20rb=1>=10");
+        assert_eq!(Roll::new(1, 8).reroll().wild_die(6).to_string(), "1d8r=1w6");
+
+        // Rolled sides have no maximum to write, so the default is a bare "="
+        let dynamic = |modifiers: Vec<Modifier>| DynamicRoll {
+            count: Box::new(Expr::Number(1)),
+            sides: DynamicSides::Expr(Box::new(Expr::Group(Box::new(Roll::new(1, 4).into())))),
+            modifiers,
+        };
+        let explode = Modifier::Explode {
+            compounding: false,
+            penetrating: false,
+            condition: None,
+        };
+        for (next, printed) in [
+            (explode.clone(), "1d(1d4)!=!"),
+            (Modifier::WildDie(6), "1d(1d4)!=w6"),
+            (Modifier::CountSuccesses(Condition::gt(2)), "1d(1d4)!=>2"),
+        ] {
+            let roll = dynamic(vec![explode.clone(), next]);
+            assert_eq!(roll.to_string(), printed);
+            assert_eq!(parse(printed).unwrap(), Expr::DynamicRoll(roll));
+        }
+        assert_eq!(dynamic(vec![explode.clone(), Modifier::KeepHighest(1)]).to_string(), "1d(1d4)!kh1");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parsed_expressions_round_trip(expr in expr()) {
+            // Hand-built trees may gain Group nodes when printed; once parsed,
+            // printing and parsing again must reproduce the same AST exactly.
+            let parsed = parse(&expr.to_string()).unwrap();
+            prop_assert_eq!(parse(&parsed.to_string()).unwrap(), parsed.clone());
+            prop_assert_eq!(parsed.to_string(), expr.to_string());
+        }
+
+        #[test]
+        fn prop_printing_preserves_meaning(expr in expr(), seed in any::<u64>()) {
+            let parsed = parse(&expr.to_string()).unwrap();
+            let original = evaluate_with_rng(&expr, &mut FastRng::with_seed(seed));
+            let reparsed = evaluate_with_rng(&parsed, &mut FastRng::with_seed(seed));
+            prop_assert_eq!(original.map(|r| r.total).ok(), reparsed.map(|r| r.total).ok());
+        }
+    }
+}
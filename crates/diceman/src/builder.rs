@@ -0,0 +1,506 @@
+// ABOUTME: Fluent builder API for constructing dice expressions in code.
+// ABOUTME: Adds Roll/Condition/Band constructors and arithmetic operators on Expr.
+
+use crate::ast::{Band, Compare, Condition, DynamicRoll, DynamicSides, Expr, Func, Modifier, Op, Roll, Sides};
+use std::ops::{Add, Div, Mul, Neg, RangeInclusive, Sub};
+
+impl Roll {
+    /// A roll of `count` dice with `sides` sides and no modifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::{Expr, Roll};
+    ///
+    /// let expr = Expr::from(Roll::new(4, 6).keep_highest(3)) + 2;
+    /// assert_eq!(expr.to_string(), "4d6kh3 + 2");
+    /// ```
+    pub fn new(count: u32, sides: u32) -> Self {
+        Self::with_sides(count, Sides::Number(sides))
+    }
+
+    /// A roll of `count` percentile dice.
+    pub fn percent(count: u32) -> Self {
+        Self::with_sides(count, Sides::Percent)
+    }
+
+    /// A roll of `count` fudge dice.
+    pub fn fudge(count: u32) -> Self {
+        Self::with_sides(count, Sides::Fudge)
+    }
+
+    /// A roll of `count` dice of the given type.
+    pub fn with_sides(count: u32, sides: Sides) -> Self {
+        Self {
+            count,
+            sides,
+            modifiers: vec![],
+        }
+    }
+
+    /// Append a modifier. Modifiers apply in the order they are added.
+    pub fn modifier(mut self, modifier: Modifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    /// Keep the highest `n` dice.
+    pub fn keep_highest(self, n: u32) -> Self {
+        self.modifier(Modifier::KeepHighest(n))
+    }
+
+    /// Keep the lowest `n` dice.
+    pub fn keep_lowest(self, n: u32) -> Self {
+        self.modifier(Modifier::KeepLowest(n))
+    }
+
+    /// Drop the highest `n` dice.
+    pub fn drop_highest(self, n: u32) -> Self {
+        self.modifier(Modifier::DropHighest(n))
+    }
+
+    /// Drop the lowest `n` dice.
+    pub fn drop_lowest(self, n: u32) -> Self {
+        self.modifier(Modifier::DropLowest(n))
+    }
+
+    /// Keep only the dice matching `condition` (`k>=4`).
+    pub fn keep_where(self, condition: Condition) -> Self {
+        self.modifier(Modifier::KeepWhere(condition))
+    }
+
+    /// Drop the dice matching `condition` (`d<3`).
+    pub fn drop_where(self, condition: Condition) -> Self {
+        self.modifier(Modifier::DropWhere(condition))
+    }
+
+    /// Explode on the maximum value, adding a new die per explosion (`!`).
+    pub fn explode(self) -> Self {
+        self.modifier(Modifier::Explode {
+            compounding: false,
+            penetrating: false,
+            condition: None,
+        })
+    }
+
+    /// Explode on rolls matching `condition` (`!>=8`).
+    pub fn explode_when(self, condition: Condition) -> Self {
+        self.modifier(Modifier::Explode {
+            compounding: false,
+            penetrating: false,
+            condition: Some(condition),
+        })
+    }
+
+    /// Explode on the maximum value, adding explosions to the same die (`!!`).
+    pub fn compound(self) -> Self {
+        self.modifier(Modifier::Explode {
+            compounding: true,
+            penetrating: false,
+            condition: None,
+        })
+    }
+
+    /// Explode on the maximum value, subtracting 1 from each explosion (`!p`).
+    pub fn penetrate(self) -> Self {
+        self.modifier(Modifier::Explode {
+            compounding: false,
+            penetrating: true,
+            condition: None,
+        })
+    }
+
+    /// Reroll 1s until they are no longer 1 (`r`).
+    pub fn reroll(self) -> Self {
+        self.modifier(Modifier::Reroll {
+            once: false,
+            condition: None,
+        })
+    }
+
+    /// Reroll dice matching `condition` until they no longer match (`r<3`).
+    pub fn reroll_when(self, condition: Condition) -> Self {
+        self.modifier(Modifier::Reroll {
+            once: false,
+            condition: Some(condition),
+        })
+    }
+
+    /// Reroll dice matching `condition` once (`ro<3`).
+    pub fn reroll_once_when(self, condition: Condition) -> Self {
+        self.modifier(Modifier::Reroll {
+            once: true,
+            condition: Some(condition),
+        })
+    }
+
+    /// Reroll dice matching `condition` once, keeping the better roll (`rb<3`).
+    pub fn reroll_keep_best(self, condition: Condition) -> Self {
+        self.modifier(Modifier::RerollKeep {
+            best: true,
+            condition: Some(condition),
+        })
+    }
+
+    /// Reroll dice matching `condition` once, keeping the worse roll (`rw>4`).
+    pub fn reroll_keep_worse(self, condition: Condition) -> Self {
+        self.modifier(Modifier::RerollKeep {
+            best: false,
+            condition: Some(condition),
+        })
+    }
+
+    /// Roll a Savage Worlds wild die with `sides` sides alongside, acing
+    /// every die and keeping the highest (`w6`).
+    pub fn wild_die(self, sides: u32) -> Self {
+        self.modifier(Modifier::WildDie(sides))
+    }
+
+    /// Raise each die below `n` to `n` (`min3`).
+    pub fn minimum(self, n: i64) -> Self {
+        self.modifier(Modifier::Min(n))
+    }
+
+    /// Lower each die above `n` to `n` (`max3`).
+    pub fn maximum(self, n: i64) -> Self {
+        self.modifier(Modifier::Max(n))
+    }
+
+    /// Count dice matching `condition` instead of summing (`>=8`).
+    pub fn count_successes(self, condition: Condition) -> Self {
+        self.modifier(Modifier::CountSuccesses(condition))
+    }
+
+    /// Subtract a success for each die matching `condition` (`f1`). Must
+    /// follow [`count_successes`](Roll::count_successes).
+    pub fn count_failures(self, condition: Condition) -> Self {
+        self.modifier(Modifier::CountFailures(condition))
+    }
+
+    /// Count successes matching `condition` twice (`dd10`). Must follow
+    /// [`count_successes`](Roll::count_successes).
+    pub fn double_successes(self, condition: Condition) -> Self {
+        self.modifier(Modifier::DoubleSuccesses(condition))
+    }
+
+    /// Sort the dice in the result lowest first (`s`).
+    pub fn sort(self) -> Self {
+        self.modifier(Modifier::Sort { descending: false })
+    }
+
+    /// Sort the dice in the result highest first (`sd`).
+    pub fn sort_descending(self) -> Self {
+        self.modifier(Modifier::Sort { descending: true })
+    }
+
+    /// Report sets of at least `width` matching dice (`m`).
+    pub fn matches(self, width: u32) -> Self {
+        self.modifier(Modifier::Matches {
+            width,
+            count_sets: false,
+        })
+    }
+
+    /// Total the sets of at least `width` matching dice instead of the dice
+    /// (`mt`).
+    pub fn count_matches(self, width: u32) -> Self {
+        self.modifier(Modifier::Matches {
+            width,
+            count_sets: true,
+        })
+    }
+}
+
+impl Condition {
+    /// A condition comparing rolls against `value`.
+    pub fn new(compare: Compare, value: i64) -> Self {
+        Self { compare, value }
+    }
+
+    /// Rolls equal to `value`.
+    pub fn eq(value: i64) -> Self {
+        Self::new(Compare::Equal, value)
+    }
+
+    /// Rolls not equal to `value`.
+    pub fn ne(value: i64) -> Self {
+        Self::new(Compare::NotEqual, value)
+    }
+
+    /// Rolls less than `value`.
+    pub fn lt(value: i64) -> Self {
+        Self::new(Compare::LessThan, value)
+    }
+
+    /// Rolls less than or equal to `value`.
+    pub fn le(value: i64) -> Self {
+        Self::new(Compare::LessOrEqual, value)
+    }
+
+    /// Rolls greater than `value`.
+    pub fn gt(value: i64) -> Self {
+        Self::new(Compare::GreaterThan, value)
+    }
+
+    /// Rolls greater than or equal to `value`.
+    pub fn ge(value: i64) -> Self {
+        Self::new(Compare::GreaterOrEqual, value)
+    }
+}
+
+impl DynamicRoll {
+    /// A roll of `count` dice with `sides` sides, both evaluated before rolling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::{DynamicRoll, Expr, Roll};
+    ///
+    /// let expr = Expr::from(DynamicRoll::new(Roll::new(1, 4), Expr::var("size")));
+    /// assert_eq!(expr.to_string(), "(1d4)d@size");
+    /// ```
+    pub fn new(count: impl Into<Expr>, sides: impl Into<Expr>) -> Self {
+        Self {
+            count: Box::new(count.into()),
+            sides: DynamicSides::Expr(Box::new(sides.into())),
+            modifiers: vec![],
+        }
+    }
+
+    /// A roll of `count` dice of the given type, with `count` evaluated before rolling.
+    pub fn with_sides(count: impl Into<Expr>, sides: Sides) -> Self {
+        Self {
+            count: Box::new(count.into()),
+            sides: DynamicSides::Fixed(sides),
+            modifiers: vec![],
+        }
+    }
+
+    /// Append a modifier. Modifiers apply in the order they are added.
+    pub fn modifier(mut self, modifier: Modifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+}
+
+impl Band {
+    /// A band covering `range`.
+    pub fn new(range: RangeInclusive<i64>, label: impl Into<String>) -> Self {
+        Self {
+            range,
+            label: label.into(),
+        }
+    }
+
+    /// A band covering every total up to and including `max` (`<=6`).
+    pub fn at_most(max: i64, label: impl Into<String>) -> Self {
+        Self::new(i64::MIN..=max, label)
+    }
+
+    /// A band covering every total from `min` up (`>=10`).
+    pub fn at_least(min: i64, label: impl Into<String>) -> Self {
+        Self::new(min..=i64::MAX, label)
+    }
+}
+
+impl Expr {
+    /// A variable, bound with [`Expr::substitute`].
+    pub fn var(name: impl Into<String>) -> Self {
+        Expr::Var(name.into())
+    }
+
+    /// A binary operation on two expressions.
+    pub fn binop(op: Op, left: impl Into<Expr>, right: impl Into<Expr>) -> Self {
+        Expr::BinOp {
+            op,
+            left: Box::new(left.into()),
+            right: Box::new(right.into()),
+        }
+    }
+
+    /// Floor division (`//`) of this expression by `rhs`.
+    pub fn floor_div(self, rhs: impl Into<Expr>) -> Self {
+        Expr::binop(Op::FloorDiv, self, rhs)
+    }
+
+    /// A call to a built-in function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::{Expr, Func, Roll};
+    ///
+    /// let expr = Expr::call(Func::Max, [Expr::from(Roll::new(1, 6)) - 2, Expr::from(1)]);
+    /// assert_eq!(expr.to_string(), "max(1d6 - 2, 1)");
+    /// ```
+    pub fn call(func: Func, args: impl IntoIterator<Item = Expr>) -> Self {
+        Expr::Call {
+            func,
+            args: args.into_iter().collect(),
+        }
+    }
+
+    /// This expression with an outcome table: its total is read off the
+    /// first band that covers it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::{Band, Expr, Roll};
+    ///
+    /// let expr = (Roll::new(2, 6) + 1).bands([
+    ///     Band::at_most(6, "miss"),
+    ///     Band::new(7..=9, "weak"),
+    ///     Band::at_least(10, "strong"),
+    /// ]);
+    /// assert_eq!(expr.to_string(), "2d6 + 1 {<=6: miss, 7..9: weak, >=10: strong}");
+    /// ```
+    pub fn bands(self, bands: impl IntoIterator<Item = Band>) -> Self {
+        Expr::Bands {
+            expr: Box::new(self),
+            bands: bands.into_iter().collect(),
+        }
+    }
+}
+
+impl From<Roll> for Expr {
+    fn from(roll: Roll) -> Self {
+        Expr::Roll(roll)
+    }
+}
+
+impl From<DynamicRoll> for Expr {
+    fn from(roll: DynamicRoll) -> Self {
+        Expr::DynamicRoll(roll)
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(n: i64) -> Self {
+        Expr::Number(n)
+    }
+}
+
+macro_rules! impl_binop {
+    ($trait:ident, $method:ident, $op:expr) => {
+        impl<T: Into<Expr>> $trait<T> for Expr {
+            type Output = Expr;
+
+            fn $method(self, rhs: T) -> Expr {
+                Expr::binop($op, self, rhs)
+            }
+        }
+
+        impl<T: Into<Expr>> $trait<T> for Roll {
+            type Output = Expr;
+
+            fn $method(self, rhs: T) -> Expr {
+                Expr::binop($op, self, rhs)
+            }
+        }
+    };
+}
+
+impl_binop!(Add, add, Op::Add);
+impl_binop!(Sub, sub, Op::Sub);
+impl_binop!(Mul, mul, Op::Mul);
+impl_binop!(Div, div, Op::Div);
+
+impl Neg for Expr {
+    type Output = Expr;
+
+    /// Negation, represented the same way the parser represents `-x`.
+    fn neg(self) -> Expr {
+        Expr::binop(Op::Sub, 0, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::roller::{evaluate_with_rng, FastRng};
+
+    #[test]
+    fn test_roll_builder_matches_parser() {
+        let cases = [
+            (Roll::new(4, 6).keep_highest(3), "4d6kh3"),
+            (Roll::new(2, 20).keep_lowest(1), "2d20kl1"),
+            (Roll::new(4, 6).drop_lowest(1), "4d6dl1"),
+            (Roll::new(6, 6).keep_where(Condition::ge(4)), "6d6k>=4"),
+            (Roll::new(6, 6).drop_where(Condition::lt(3)), "6d6d<3"),
+            (Roll::new(1, 6).explode(), "1d6!"),
+            (Roll::new(1, 6).compound(), "1d6!!"),
+            (Roll::new(1, 6).penetrate(), "1d6!p"),
+            (Roll::new(1, 10).explode_when(Condition::ge(8)), "1d10!>=8"),
+            (Roll::new(2, 6).reroll_when(Condition::lt(3)), "2d6r<3"),
+            (Roll::new(1, 20).reroll_once_when(Condition::eq(1)), "1d20ro=1"),
+            (Roll::new(1, 20).reroll_keep_best(Condition::lt(10)), "1d20rb<10"),
+            (Roll::new(2, 6).reroll_keep_worse(Condition::gt(4)), "2d6rw>4"),
+            (Roll::new(2, 6).minimum(3), "2d6min3"),
+            (Roll::new(1, 8).wild_die(6), "1d8w6"),
+            (Roll::new(1, 20).maximum(15), "1d20max15"),
+            (Roll::new(5, 10).count_successes(Condition::ge(8)), "5d10>=8"),
+            (
+                Roll::new(10, 10)
+                    .count_successes(Condition::ge(8))
+                    .count_failures(Condition::eq(1))
+                    .double_successes(Condition::eq(10)),
+                "10d10>=8f1dd10",
+            ),
+            (Roll::new(8, 6).sort_descending(), "8d6sd"),
+            (Roll::new(4, 6).sort().drop_highest(1), "4d6sdh1"),
+            (Roll::new(6, 10).matches(2), "6d10m"),
+            (Roll::new(6, 10).count_matches(3), "6d10mt3"),
+            (Roll::fudge(4), "4dF"),
+            (Roll::percent(1), "1d%"),
+        ];
+        for (roll, notation) in cases {
+            assert_eq!(Expr::from(roll), parse(notation).unwrap(), "{}", notation);
+        }
+    }
+
+    #[test]
+    fn test_expr_operators() {
+        let expr = Expr::from(Roll::new(2, 6)) + 5;
+        assert_eq!(expr, parse("2d6 + 5").unwrap());
+
+        let expr = Roll::new(1, 20) - Roll::new(1, 4);
+        assert_eq!(expr, parse("1d20 - 1d4").unwrap());
+
+        let expr = -Expr::from(Roll::new(1, 4));
+        assert_eq!(expr, parse("-1d4").unwrap());
+
+        let expr = Expr::from(8).floor_div(3);
+        assert_eq!(expr, parse("8 // 3").unwrap());
+    }
+
+    #[test]
+    fn test_dynamic_roll_builder_matches_parser() {
+        let built = DynamicRoll::with_sides(Expr::var("level"), Sides::Number(8)).modifier(Modifier::KeepHighest(1));
+        assert_eq!(Expr::from(built), parse("@level d8kh1").unwrap());
+
+        let built = DynamicRoll::new(2, Expr::Group(Box::new(Roll::new(2, 4).into())));
+        assert_eq!(Expr::from(built), parse("2d(2d4)").unwrap());
+    }
+
+    #[test]
+    fn test_built_expressions_print_with_parens() {
+        let expr = (Roll::new(1, 6) + 2) * 3;
+        assert_eq!(expr.to_string(), "(1d6 + 2) * 3");
+
+        let expr = Expr::from(10) / (Expr::from(4) / 2);
+        assert_eq!(expr.to_string(), "10 / (4 / 2)");
+    }
+
+    #[test]
+    fn test_built_expressions_evaluate() {
+        let built = Expr::call(Func::Max, [Roll::new(3, 6).keep_highest(2) * 2, Expr::from(5)]);
+        let parsed = parse("max(3d6kh2 * 2, 5)").unwrap();
+
+        let a = evaluate_with_rng(&built, &mut FastRng::with_seed(9)).unwrap();
+        let b = evaluate_with_rng(&parsed, &mut FastRng::with_seed(9)).unwrap();
+        assert_eq!(a.total, b.total);
+        assert_eq!(a.expression, b.expression);
+    }
+}
@@ -1,5 +1,5 @@
 // ABOUTME: Error types for the diceman library.
-// ABOUTME: Covers lexing, parsing, and evaluation errors.
+// ABOUTME: Covers lexing, parsing, evaluation and random table errors.
 
 use thiserror::Error;
 
@@ -8,6 +8,9 @@ pub enum Error {
     #[error("Unexpected character '{0}' at position {1}")]
     UnexpectedChar(char, usize),
 
+    #[error("Number too large at position {0}")]
+    NumberTooLarge(usize),
+
     #[error("Unexpected end of input")]
     UnexpectedEof,
 
@@ -15,10 +18,10 @@ pub enum Error {
     Expected { expected: String, found: String },
 
     #[error("Invalid dice count: {0}")]
-    InvalidDiceCount(u32),
+    InvalidDiceCount(i64),
 
     #[error("Invalid dice sides: {0}")]
-    InvalidDiceSides(u32),
+    InvalidDiceSides(i64),
 
     #[error("Explode limit exceeded (max {0} explosions)")]
     ExplodeLimit(u32),
@@ -28,6 +31,33 @@ pub enum Error {
 
     #[error("Division by zero")]
     DivisionByZero,
+
+    #[error("Arithmetic overflow")]
+    Overflow,
+
+    #[error("Dice limit exceeded (max {0} dice per roll)")]
+    DiceLimit(u32),
+
+    #[error("Total dice limit exceeded (max {0} dice per expression)")]
+    TotalDiceLimit(u32),
+
+    #[error("Dice sides limit exceeded (max {0} sides)")]
+    SidesLimit(u32),
+
+    #[error("Unknown variable '@{0}'")]
+    UnknownVariable(String),
+
+    #[error("Unknown table '{0}'")]
+    UnknownTable(String),
+
+    #[error("No entry in table '{table}' for a roll of {roll}")]
+    NoTableEntry { table: String, roll: i64 },
+
+    #[error("Table roll limit exceeded (max {0} rolls on tables)")]
+    TableLimit(u32),
+
+    #[error("Invalid table: {0}")]
+    InvalidTable(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
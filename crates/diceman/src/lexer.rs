@@ -6,8 +6,8 @@ use crate::error::{Error, Result};
 /// A token in the dice notation language.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
-    /// A numeric literal.
-    Number(u32),
+    /// A numeric literal, up to `i64::MAX`.
+    Number(i64),
     /// The 'd' or 'D' dice separator.
     D,
     /// Percent sign for d%.
@@ -22,10 +22,26 @@ pub enum Token {
     Star,
     /// Division operator.
     Slash,
+    /// Floor division operator: '//'.
+    DoubleSlash,
     /// Left parenthesis.
     LParen,
     /// Right parenthesis.
     RParen,
+    /// Argument or band separator: ','.
+    Comma,
+    /// Start of an outcome table: '{'.
+    LBrace,
+    /// End of an outcome table: '}'.
+    RBrace,
+    /// Separates a band's range from its label: ':'.
+    Colon,
+    /// Band range separator: '..'.
+    DotDot,
+    /// A keyword such as a function name, stored lowercase.
+    Ident(String),
+    /// A variable: '@' followed by its name.
+    Var(String),
     /// Keep modifier: 'k'.
     K,
     /// High modifier: 'h'.
@@ -38,8 +54,20 @@ pub enum Token {
     R,
     /// Once modifier: 'o'.
     O,
+    /// Keep-best reroll: 'b'.
+    B,
+    /// Keep-worse reroll or wild die: 'w'.
+    W,
     /// Penetrating modifier: 'p'.
     P,
+    /// Sort modifier: 's'.
+    S,
+    /// Ascending sort: 'a'.
+    A,
+    /// Match modifier: 'm'.
+    M,
+    /// Match total: 't'.
+    T,
     /// Equal comparison: '='.
     Eq,
     /// Less than: '<'.
@@ -50,9 +78,14 @@ pub enum Token {
     Eof,
 }
 
+/// Multi-letter words recognized by the lexer.
+///
+/// Any other run of letters is split into single-letter tokens, so "kh3"
+/// still lexes as 'k', 'h', 3.
+const KEYWORDS: &[&str] = &["floor", "ceil", "round", "min", "max", "abs", "clamp"];
+
 /// A lexer for dice notation.
 pub struct Lexer<'a> {
-    #[allow(dead_code)]
     input: &'a str,
     chars: std::iter::Peekable<std::str::CharIndices<'a>>,
     pos: usize,
@@ -93,8 +126,18 @@ impl<'a> Lexer<'a> {
 
         self.pos = pos;
 
+        if ch.is_ascii_alphabetic() {
+            if let Some(word) = self.keyword(pos) {
+                for _ in 0..word.len() {
+                    self.chars.next();
+                }
+                return Ok(Token::Ident(word));
+            }
+        }
+
         match ch {
             '0'..='9' => self.number(),
+            '@' => self.variable(pos),
             'd' | 'D' => {
                 self.chars.next();
                 Ok(Token::D)
@@ -121,7 +164,12 @@ impl<'a> Lexer<'a> {
             }
             '/' => {
                 self.chars.next();
-                Ok(Token::Slash)
+                if let Some(&(_, '/')) = self.chars.peek() {
+                    self.chars.next();
+                    Ok(Token::DoubleSlash)
+                } else {
+                    Ok(Token::Slash)
+                }
             }
             '(' => {
                 self.chars.next();
@@ -131,6 +179,29 @@ impl<'a> Lexer<'a> {
                 self.chars.next();
                 Ok(Token::RParen)
             }
+            ',' => {
+                self.chars.next();
+                Ok(Token::Comma)
+            }
+            '{' => {
+                self.chars.next();
+                Ok(Token::LBrace)
+            }
+            '}' => {
+                self.chars.next();
+                Ok(Token::RBrace)
+            }
+            ':' => {
+                self.chars.next();
+                Ok(Token::Colon)
+            }
+            '.' => {
+                self.chars.next();
+                match self.chars.next() {
+                    Some((_, '.')) => Ok(Token::DotDot),
+                    _ => Err(Error::UnexpectedChar('.', pos)),
+                }
+            }
             'k' | 'K' => {
                 self.chars.next();
                 Ok(Token::K)
@@ -155,10 +226,34 @@ impl<'a> Lexer<'a> {
                 self.chars.next();
                 Ok(Token::O)
             }
+            'b' | 'B' => {
+                self.chars.next();
+                Ok(Token::B)
+            }
+            'w' | 'W' => {
+                self.chars.next();
+                Ok(Token::W)
+            }
             'p' | 'P' => {
                 self.chars.next();
                 Ok(Token::P)
             }
+            's' | 'S' => {
+                self.chars.next();
+                Ok(Token::S)
+            }
+            'a' | 'A' => {
+                self.chars.next();
+                Ok(Token::A)
+            }
+            'm' | 'M' => {
+                self.chars.next();
+                Ok(Token::M)
+            }
+            't' | 'T' => {
+                self.chars.next();
+                Ok(Token::T)
+            }
             '=' => {
                 self.chars.next();
                 Ok(Token::Eq)
@@ -175,6 +270,49 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Read an outcome band's label: the text up to the next ',' or '}',
+    /// with surrounding whitespace trimmed, or a double-quoted string in
+    /// which '\' escapes the next character.
+    pub fn label(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let start = self.chars.peek().map_or(self.input.len(), |&(pos, _)| pos);
+        self.pos = start;
+        if self.chars.next_if(|&(_, ch)| ch == '"').is_some() {
+            return self.quoted_label();
+        }
+        while let Some(&(_, ch)) = self.chars.peek() {
+            if ch == ',' || ch == '}' {
+                break;
+            }
+            self.chars.next();
+        }
+        let end = self.chars.peek().map_or(self.input.len(), |&(pos, _)| pos);
+        let label = self.input[start..end].trim_end();
+        if label.is_empty() {
+            return Err(Error::Expected {
+                expected: "band label".to_string(),
+                found: self.input[end..].chars().next().map_or("end of input".to_string(), String::from),
+            });
+        }
+        Ok(label.to_string())
+    }
+
+    /// Read the rest of a quoted label after its opening '"'.
+    fn quoted_label(&mut self) -> Result<String> {
+        let mut label = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(label),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, ch)) => label.push(ch),
+                    None => return Err(Error::UnexpectedEof),
+                },
+                Some((_, ch)) => label.push(ch),
+                None => return Err(Error::UnexpectedEof),
+            }
+        }
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(&(_, ch)) = self.chars.peek() {
             if ch.is_whitespace() {
@@ -185,19 +323,49 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Returns the keyword starting at `pos`, if the run of letters there is one.
+    fn keyword(&self, pos: usize) -> Option<String> {
+        let rest = &self.input[pos..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let word = rest[..len].to_ascii_lowercase();
+        KEYWORDS.contains(&word.as_str()).then_some(word)
+    }
+
+    /// Lex '@' and the letters, digits and underscores that follow it.
+    fn variable(&mut self, pos: usize) -> Result<Token> {
+        self.chars.next();
+        let rest = &self.input[pos + 1..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(Error::UnexpectedChar('@', pos));
+        }
+        for _ in 0..len {
+            self.chars.next();
+        }
+        Ok(Token::Var(rest[..len].to_string()))
+    }
+
+    /// Lex a run of digits, rejecting literals too large for an `i64`
+    /// rather than silently capping them.
     fn number(&mut self) -> Result<Token> {
-        let mut value: u32 = 0;
+        let mut value: Option<i64> = Some(0);
 
         while let Some(&(_, ch)) = self.chars.peek() {
             if let Some(digit) = ch.to_digit(10) {
                 self.chars.next();
-                value = value.saturating_mul(10).saturating_add(digit);
+                value = value
+                    .and_then(|v| v.checked_mul(10))
+                    .and_then(|v| v.checked_add(digit as i64));
             } else {
                 break;
             }
         }
 
-        Ok(Token::Number(value))
+        value.map(Token::Number).ok_or(Error::NumberTooLarge(self.pos))
     }
 }
 
@@ -257,6 +425,108 @@ mod tests {
         assert_eq!(lexer.next_token().unwrap(), Token::Eof);
     }
 
+    #[test]
+    fn test_floor_division() {
+        let mut lexer = Lexer::new("7 // 2 / 1");
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(7));
+        assert_eq!(lexer.next_token().unwrap(), Token::DoubleSlash);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(2));
+        assert_eq!(lexer.next_token().unwrap(), Token::Slash);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(1));
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_function_keyword() {
+        let mut lexer = Lexer::new("Floor(8d6)");
+        assert_eq!(lexer.next_token().unwrap(), Token::Ident("floor".to_string()));
+        assert_eq!(lexer.next_token().unwrap(), Token::LParen);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(8));
+        assert_eq!(lexer.next_token().unwrap(), Token::D);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(6));
+        assert_eq!(lexer.next_token().unwrap(), Token::RParen);
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_keyword_after_modifier() {
+        let mut lexer = Lexer::new("2d6smin3");
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(2));
+        assert_eq!(lexer.next_token().unwrap(), Token::D);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(6));
+        assert_eq!(lexer.next_token().unwrap(), Token::S);
+        assert_eq!(lexer.next_token().unwrap(), Token::Ident("min".to_string()));
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(3));
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut lexer = Lexer::new("8d6sa sd");
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(8));
+        assert_eq!(lexer.next_token().unwrap(), Token::D);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(6));
+        assert_eq!(lexer.next_token().unwrap(), Token::S);
+        assert_eq!(lexer.next_token().unwrap(), Token::A);
+        assert_eq!(lexer.next_token().unwrap(), Token::S);
+        assert_eq!(lexer.next_token().unwrap(), Token::D);
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_reroll_keep() {
+        let mut lexer = Lexer::new("rb rW");
+        assert_eq!(lexer.next_token().unwrap(), Token::R);
+        assert_eq!(lexer.next_token().unwrap(), Token::B);
+        assert_eq!(lexer.next_token().unwrap(), Token::R);
+        assert_eq!(lexer.next_token().unwrap(), Token::W);
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut lexer = Lexer::new("6d10mt3");
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(6));
+        assert_eq!(lexer.next_token().unwrap(), Token::D);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(10));
+        assert_eq!(lexer.next_token().unwrap(), Token::M);
+        assert_eq!(lexer.next_token().unwrap(), Token::T);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(3));
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+
+        // Function names still lex as keywords
+        let mut lexer = Lexer::new("max");
+        assert_eq!(lexer.next_token().unwrap(), Token::Ident("max".to_string()));
+    }
+
+    #[test]
+    fn test_function_arguments() {
+        let mut lexer = Lexer::new("max(1d6, 1)");
+        assert_eq!(lexer.next_token().unwrap(), Token::Ident("max".to_string()));
+        assert_eq!(lexer.next_token().unwrap(), Token::LParen);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(1));
+        assert_eq!(lexer.next_token().unwrap(), Token::D);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(6));
+        assert_eq!(lexer.next_token().unwrap(), Token::Comma);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(1));
+        assert_eq!(lexer.next_token().unwrap(), Token::RParen);
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_variable() {
+        let mut lexer = Lexer::new("@level_2 d8");
+        assert_eq!(lexer.next_token().unwrap(), Token::Var("level_2".to_string()));
+        assert_eq!(lexer.next_token().unwrap(), Token::D);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(8));
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+
+        let mut lexer = Lexer::new("1 + @");
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(1));
+        assert_eq!(lexer.next_token().unwrap(), Token::Plus);
+        assert!(matches!(lexer.next_token(), Err(Error::UnexpectedChar('@', 4))));
+    }
+
     #[test]
     fn test_penetrating() {
         let mut lexer = Lexer::new("1d6!p");
@@ -267,4 +537,44 @@ mod tests {
         assert_eq!(lexer.next_token().unwrap(), Token::P);
         assert_eq!(lexer.next_token().unwrap(), Token::Eof);
     }
+
+    #[test]
+    fn test_bands() {
+        let mut lexer = Lexer::new("{7..9: weak hit, >=10:strong}");
+        assert_eq!(lexer.next_token().unwrap(), Token::LBrace);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(7));
+        assert_eq!(lexer.next_token().unwrap(), Token::DotDot);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(9));
+        assert_eq!(lexer.next_token().unwrap(), Token::Colon);
+        assert_eq!(lexer.label().unwrap(), "weak hit");
+        assert_eq!(lexer.next_token().unwrap(), Token::Comma);
+        assert_eq!(lexer.next_token().unwrap(), Token::Gt);
+        assert_eq!(lexer.next_token().unwrap(), Token::Eq);
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(10));
+        assert_eq!(lexer.next_token().unwrap(), Token::Colon);
+        assert_eq!(lexer.label().unwrap(), "strong");
+        assert_eq!(lexer.next_token().unwrap(), Token::RBrace);
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+
+        assert!(Lexer::new(" }").label().is_err());
+
+        let mut lexer = Lexer::new(r#" "hit, \"hard\" \\ }" }"#);
+        assert_eq!(lexer.label().unwrap(), r#"hit, "hard" \ }"#);
+        assert_eq!(lexer.next_token().unwrap(), Token::RBrace);
+        assert_eq!(Lexer::new(r#""""#).label().unwrap(), "");
+        assert!(matches!(Lexer::new(r#""miss"#).label(), Err(Error::UnexpectedEof)));
+        assert!(matches!(Lexer::new(".9").next_token(), Err(Error::UnexpectedChar('.', 0))));
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let mut lexer = Lexer::new("5000000000 9223372036854775807");
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(5_000_000_000));
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(i64::MAX));
+
+        let mut lexer = Lexer::new("1 + 9223372036854775808");
+        assert_eq!(lexer.next_token().unwrap(), Token::Number(1));
+        assert_eq!(lexer.next_token().unwrap(), Token::Plus);
+        assert!(matches!(lexer.next_token(), Err(Error::NumberTooLarge(4))));
+    }
 }
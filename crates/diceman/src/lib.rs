@@ -14,6 +14,11 @@
 //! let result = roll("4d6kh3").unwrap();
 //! println!("{}", result);  // e.g., "4d6kh3[6, 5, 4, (1)] = 15"
 //!
+//! // Build expressions in code
+//! use diceman::{Expr, Roll};
+//! let expr = Expr::from(Roll::new(4, 6).keep_highest(3)) + 2;
+//! assert_eq!(expr.to_string(), "4d6kh3 + 2");
+//!
 //! // Simulate probability distribution
 //! let sim = simulate("2d6", 10000).unwrap();
 //! println!("Mean: {:.2}", sim.mean);  // ~7.0
@@ -21,24 +26,57 @@
 //!
 //! ## Supported Notation
 //!
-//! - Basic rolls: `2d6`, `1d20`, `d%`, `4dF`
-//! - Arithmetic: `2d6 + 5`, `(1d6 + 2) * 3`
+//! - Basic rolls: `2d6`, `1d20`, `d%`, `4dF` (see [`systems::fate`] for Fate glyphs and the ladder,
+//!   and [`systems::percentile`] for roll-under checks with bonus and penalty dice)
+//! - Rolled or computed dice: `(1d4)d6`, `1d(2d4)`, `@level d8` (see [`roll_with_vars`])
+//! - Arithmetic: `2d6 + 5`, `(1d6 + 2) * 3`, `7 // 2`
+//! - Rounding: `floor(8d6 / 2)`, `ceil(...)`, `round(...)`
+//! - Functions: `max(1d6 - 2, 1)`, `min(...)`, `abs(...)`, `clamp(2d6, 3, 10)`
 //! - Keep highest/lowest: `4d6kh3`, `2d20kl1`
 //! - Drop highest/lowest: `4d6dh1`, `4d6dl1`
+//! - Keep/drop by condition: `6d6k>=4`, `6d6d<3`
 //! - Exploding dice: `1d6!`, `1d6!>5`
-//! - Reroll: `1d6r`, `1d6r<3`
+//! - Savage Worlds wild die: `1d8w`, `1d8w6`
+//! - Reroll: `1d6r`, `1d6r<3`, keeping the better or worse roll: `1d20rb<10`, `1d20rw>10`
+//! - Per-die minimum and maximum: `2d6min3`, `1d20max15`
+//! - Sorting: `8d6s`, `8d6sd`
+//! - Matching sets: `6d10m`, `6d10mt3`
+//! - Success counting: `5d10>=8`, with failures and doubles: `10d10>=8f1dd10`
+//! - Outcome bands: `2d6+1 {<=6: miss, 7..9: weak, >=10: strong}` (see [`RollResult::band`])
+//!
+//! Random tables with inline rolls, nested tables and "roll again" entries
+//! live in [`table`]; the `toml`, `json` and `csv` features load them from files.
 
 pub mod ast;
+pub mod builder;
 pub mod error;
 pub mod lexer;
+pub mod optimize;
 pub mod parser;
+pub mod plan;
 pub mod roller;
 pub mod sim;
+pub mod stats;
+pub mod systems;
+pub mod table;
+pub mod validate;
+
+#[cfg(test)]
+mod testing;
 
-pub use ast::{Compare, Condition, Expr, Modifier, Op, Roll, Sides};
+pub use ast::{Band, Compare, Condition, DynamicRoll, DynamicSides, Expr, Func, Modifier, Op, Roll, Sides};
 pub use error::{Error, Result};
-pub use roller::{DieResult, FastRng, Rng, RollResult};
-pub use sim::{simulate, simulate_seeded, SimResult};
+pub use optimize::{optimize, optimize_with_limits};
+pub use plan::{roll_total, RollPlan};
+pub use roller::{DieResult, EvalConfig, EvalLimits, FastRng, LimitPolicy, ModifierOrder, Rng, RollResult};
+pub use sim::{
+    simulate, simulate_expr, simulate_seeded, simulate_sets, simulate_sets_expr, simulate_sets_seeded, SetSimResult,
+    SimResult,
+};
+pub use stats::Stats;
+pub use validate::{validate, Diagnostic, Severity};
+
+use std::collections::HashMap;
 
 /// Parse and roll a dice expression in one step.
 ///
@@ -71,6 +109,45 @@ pub fn roll_with_rng(expr: &str, rng: &mut impl Rng) -> Result<RollResult> {
     roller::evaluate_with_rng(&parsed, rng)
 }
 
+/// Parse and roll with a custom RNG and evaluation configuration.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{roll_with_config, EvalConfig, FastRng, LimitPolicy};
+///
+/// // Stop exploding after 10 explosions instead of failing the roll
+/// let config = EvalConfig {
+///     max_explosions: 10,
+///     on_limit: LimitPolicy::Cap,
+///     ..EvalConfig::default()
+/// };
+/// let result = roll_with_config("1d1!", &mut FastRng::new(), &config).unwrap();
+/// assert_eq!(result.total, 11);
+/// ```
+pub fn roll_with_config(expr: &str, rng: &mut impl Rng, config: &EvalConfig) -> Result<RollResult> {
+    let parsed = parser::parse(expr)?;
+    roller::evaluate_with_config(&parsed, rng, config)
+}
+
+/// Parse and roll an expression, replacing each `@name` with its value in `vars`.
+///
+/// Rolling a variable missing from `vars` fails with [`Error::UnknownVariable`].
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// let vars = HashMap::from([("level".to_string(), 3)]);
+/// let result = diceman::roll_with_vars("@level d8 + @level", &vars).unwrap();
+/// assert!((6..=27).contains(&result.total));
+/// ```
+pub fn roll_with_vars(expr: &str, vars: &HashMap<String, i64>) -> Result<RollResult> {
+    let parsed = parser::parse(expr)?;
+    roller::evaluate(&parsed.substitute(vars))
+}
+
 /// Parse a dice expression without rolling.
 ///
 /// Returns the AST representation of the expression.
@@ -93,6 +170,36 @@ pub fn parse(input: &str) -> Result<Expr> {
     parser::parse(input)
 }
 
+/// Parse a dice expression and compile it for repeated rolling.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::FastRng;
+///
+/// let mut plan = diceman::compile("2d6 + 3").unwrap();
+/// let mut rng = FastRng::new();
+/// let total = plan.roll(&mut rng).unwrap();
+/// assert!((5..=15).contains(&total));
+/// ```
+pub fn compile(input: &str) -> Result<RollPlan> {
+    Ok(RollPlan::new(&parser::parse(input)?))
+}
+
+/// Parse a dice expression and print it in canonical notation.
+///
+/// Shorthand is expanded and spacing is made consistent; the output parses
+/// back to the same AST.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(diceman::normalize("d20+4d6k3").unwrap(), "1d20 + 4d6kh3");
+/// ```
+pub fn normalize(input: &str) -> Result<String> {
+    Ok(parser::parse(input)?.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,6 +233,17 @@ mod tests {
         assert_eq!(result1.total, result2.total);
     }
 
+    #[test]
+    fn test_roll_with_vars() {
+        let vars = HashMap::from([("level".to_string(), 2), ("bonus".to_string(), -1)]);
+        let result = roll_with_vars("@level d1 + @bonus", &vars).unwrap();
+        assert_eq!(result.total, 1);
+        assert!(matches!(
+            roll_with_vars("@missing", &vars),
+            Err(Error::UnknownVariable(name)) if name == "missing"
+        ));
+    }
+
     #[test]
     fn test_parse() {
         let expr = parse("4d6kh3").unwrap();
@@ -139,6 +257,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("d20").unwrap(), "1d20");
+        assert_eq!(normalize("4d6k3").unwrap(), "4d6kh3");
+        assert_eq!(normalize(" 2d6+( 1d4 )*2 ").unwrap(), "2d6 + (1d4) * 2");
+        assert!(normalize("2d").is_err());
+    }
+
     #[test]
     fn test_simulate_integration() {
         let result = simulate("2d6", 1000).unwrap();
@@ -0,0 +1,342 @@
+// ABOUTME: Simplification pass that folds constants and merges plain rolls.
+// ABOUTME: Rewrites expressions without changing what they roll or in which order.
+
+use crate::ast::{DynamicRoll, DynamicSides, Expr, Op, Roll, Sides};
+use crate::plan::roll_total;
+use crate::roller::{apply_op, EvalLimits, FastRng, Fraction};
+
+/// Simplify an expression without changing its distribution.
+///
+/// - Constant arithmetic and function calls are folded (`2 + 3` → `5`).
+/// - Identities are removed (`x + 0`, `x * 1`, `x / 1`).
+/// - Constants move to the right and merge (`2 + 1d6 + 3` → `1d6 + 5`).
+/// - Plain rolls of the same die merge (`1d6 + 2d6` → `3d6`).
+/// - Dynamic rolls with a constant count and sides become plain rolls
+///   (`(1 + 2)d6` → `3d6`).
+/// - Parentheses are dropped; printing adds back the ones that are needed.
+///
+/// The result rolls the same dice in the same order as the original, so it
+/// gives the same total for the same seed. Rewrites that could change an
+/// error are skipped: `1 / 0` is left alone, constants of opposite sign
+/// are not merged since that could hide an overflow, and rolls only merge
+/// while the result stays within the default per-roll dice limit. Use
+/// [`optimize_with_limits`] when rolling with other limits.
+///
+/// # Examples
+///
+/// ```
+/// let expr = diceman::parse("(1d6 + 0) * 1 + 2 + 3").unwrap();
+/// assert_eq!(diceman::optimize(&expr).to_string(), "1d6 + 5");
+/// ```
+pub fn optimize(expr: &Expr) -> Expr {
+    optimize_with_limits(expr, &EvalLimits::default())
+}
+
+/// Simplify an expression that will be rolled under `limits`.
+///
+/// Plain rolls only merge while the merged roll stays within
+/// `limits.max_dice_per_roll`, so `8d6 + 8d6` isn't turned into a `16d6`
+/// that the caller's limits would reject.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::EvalLimits;
+///
+/// let expr = diceman::parse("8d6 + 8d6").unwrap();
+/// let limits = EvalLimits { max_dice_per_roll: 10, ..EvalLimits::default() };
+/// assert_eq!(diceman::optimize_with_limits(&expr, &limits).to_string(), "8d6 + 8d6");
+/// ```
+pub fn optimize_with_limits(expr: &Expr, limits: &EvalLimits) -> Expr {
+    simplify(expr, false, limits)
+}
+
+/// `exact` is true inside the argument of a rounding function, where
+/// division is not truncated and intermediate values may be fractions.
+fn simplify(expr: &Expr, exact: bool, limits: &EvalLimits) -> Expr {
+    match expr {
+        Expr::Number(_) | Expr::Roll(_) | Expr::Var(_) => expr.clone(),
+        Expr::DynamicRoll(roll) => simplify_dynamic_roll(roll, limits),
+        Expr::Bands { expr, bands } => Expr::Bands {
+            expr: Box::new(simplify(expr, exact, limits)),
+            bands: bands.clone(),
+        },
+        Expr::Group(inner) => simplify(inner, exact, limits),
+        Expr::BinOp { op, left, right } => simplify_binop(
+            *op,
+            simplify(left, exact, limits),
+            simplify(right, exact, limits),
+            exact,
+            limits,
+        ),
+        Expr::Call { func, args } => {
+            let call = Expr::call(*func, args.iter().map(|arg| simplify(arg, func.is_rounding(), limits)));
+            if call.is_constant() {
+                // Nothing is rolled, so any seed gives the same answer
+                if let Ok(n) = roll_total(&call, &mut FastRng::with_seed(0)) {
+                    return Expr::Number(n);
+                }
+            }
+            call
+        }
+    }
+}
+
+fn simplify_dynamic_roll(roll: &DynamicRoll, limits: &EvalLimits) -> Expr {
+    // The count and sides are always evaluated with truncating division
+    let count = simplify(&roll.count, false, limits);
+    let sides = match &roll.sides {
+        DynamicSides::Fixed(sides) => DynamicSides::Fixed(*sides),
+        DynamicSides::Expr(sides) => DynamicSides::Expr(Box::new(simplify(sides, false, limits))),
+    };
+    let fixed = match (&count, &sides) {
+        (Expr::Number(count), DynamicSides::Fixed(sides)) => u32::try_from(*count).ok().map(|n| (n, *sides)),
+        (Expr::Number(count), DynamicSides::Expr(sides)) => match **sides {
+            Expr::Number(s) if s > 0 => u32::try_from(*count).ok().zip(u32::try_from(s).ok().map(Sides::Number)),
+            _ => None,
+        },
+        _ => None,
+    };
+    match fixed {
+        Some((count, sides)) => Expr::Roll(Roll {
+            count,
+            sides,
+            modifiers: roll.modifiers.clone(),
+        }),
+        None => Expr::DynamicRoll(DynamicRoll {
+            count: Box::new(count),
+            sides,
+            modifiers: roll.modifiers.clone(),
+        }),
+    }
+}
+
+fn simplify_binop(op: Op, left: Expr, right: Expr, exact: bool, limits: &EvalLimits) -> Expr {
+    match (op, &left, &right) {
+        (_, Expr::Number(a), Expr::Number(b)) => {
+            let folded = if exact {
+                Fraction::integer(*a)
+                    .apply(op, Fraction::integer(*b))
+                    .ok()
+                    .and_then(Fraction::to_integer)
+            } else {
+                apply_op(op, *a, *b).ok()
+            };
+            match folded {
+                Some(n) => Expr::Number(n),
+                None => Expr::binop(op, left, right),
+            }
+        }
+        (Op::Add | Op::Sub, _, Expr::Number(0)) | (Op::Mul | Op::Div, _, Expr::Number(1)) => left,
+        // Inside a rounding function the left side may be a fraction that `//` floors
+        (Op::FloorDiv, _, Expr::Number(1)) if !exact => left,
+        (Op::Add, Expr::Number(0), _) | (Op::Mul, Expr::Number(1), _) => right,
+        (Op::Add | Op::Mul, Expr::Number(_), _) => simplify_binop(op, right, left, exact, limits),
+        (Op::Add, _, Expr::Number(n)) if !exact => add_constant(left, *n),
+        (Op::Sub, _, Expr::Number(n)) if !exact => match n.checked_neg() {
+            Some(n) => add_constant(left, n),
+            None => Expr::binop(op, left, right),
+        },
+        (Op::Add | Op::Sub, _, Expr::Roll(roll)) => match merge_into(op, &left, roll, limits) {
+            Some(merged) => merged,
+            None => Expr::binop(op, left, right),
+        },
+        _ => Expr::binop(op, left, right),
+    }
+}
+
+/// Build `x + n`, merging `n` into a constant already added to `x`.
+///
+/// Constants only merge when they have the same sign: `(x + a) + b` then
+/// overflows exactly when `x + (a + b)` does.
+fn add_constant(x: Expr, n: i64) -> Expr {
+    if let Some((inner, m)) = trailing_constant(&x) {
+        if (m >= 0) == (n >= 0) {
+            if let Some(sum) = m.checked_add(n) {
+                return offset(inner.clone(), sum);
+            }
+        }
+    }
+    offset(x, n)
+}
+
+/// Split `x + n` or `x - n` into `x` and the signed constant.
+fn trailing_constant(expr: &Expr) -> Option<(&Expr, i64)> {
+    match expr {
+        Expr::BinOp { op: Op::Add, left, right } => match **right {
+            Expr::Number(n) => Some((left, n)),
+            _ => None,
+        },
+        Expr::BinOp { op: Op::Sub, left, right } => match **right {
+            Expr::Number(n) => Some((left, n.checked_neg()?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `x + n`, written as a subtraction when `n` is negative.
+fn offset(x: Expr, n: i64) -> Expr {
+    if n == 0 {
+        return x;
+    }
+    match n.checked_neg() {
+        Some(neg) if n < 0 => Expr::binop(Op::Sub, x, neg),
+        _ => Expr::binop(Op::Add, x, n),
+    }
+}
+
+/// Merge `roll` into `left` for `left + roll` or `left - roll`.
+///
+/// `1d6 + 2d6` becomes `3d6`, and `x + 1d6 + 2d6` becomes `x + 3d6`. The
+/// merged roll rolls the same dice in the same order, so only the formatting
+/// changes.
+fn merge_into(op: Op, left: &Expr, roll: &Roll, limits: &EvalLimits) -> Option<Expr> {
+    match left {
+        Expr::Roll(first) if op == Op::Add => merge(first, roll, limits).map(Expr::Roll),
+        // Regrouping is only safe when every die has the same sign, which
+        // fudge dice don't
+        Expr::BinOp {
+            op: left_op,
+            left: inner,
+            right,
+        } if *left_op == op && roll.sides != Sides::Fudge => match &**right {
+            Expr::Roll(first) => Some(Expr::binop(op, (**inner).clone(), merge(first, roll, limits)?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Combine two plain rolls of the same die, if the result is within the
+/// per-roll dice limit.
+fn merge(first: &Roll, second: &Roll, limits: &EvalLimits) -> Option<Roll> {
+    let plain = first.modifiers.is_empty() && second.modifiers.is_empty();
+    let count = first.count.checked_add(second.count)?;
+    (plain && first.sides == second.sides && count <= limits.max_dice_per_roll)
+        .then(|| Roll::with_sides(count, first.sides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::roller::evaluate_with_rng;
+    use crate::sim::simulate_seeded;
+    use crate::testing::expr;
+    use proptest::prelude::*;
+
+    fn optimized(input: &str) -> String {
+        optimize(&parse(input).unwrap()).to_string()
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        assert_eq!(optimized("(2 + 3) * 4"), "20");
+        assert_eq!(optimized("7 / 2 + 7 // 2"), "6");
+        assert_eq!(optimized("max(1, 2) + abs(-3)"), "5");
+        assert_eq!(optimized("1d6 + 2 * 3"), "1d6 + 6");
+    }
+
+    #[test]
+    fn test_remove_identities() {
+        assert_eq!(optimized("(1d6 + 0) * 1 + 2 + 3"), "1d6 + 5");
+        assert_eq!(optimized("0 + 1d8 / 1"), "1d8");
+        assert_eq!(optimized("1 * (4d6kh3)"), "4d6kh3");
+        // Subtracting from zero is negation, not an identity
+        assert_eq!(optimized("0 - 1d4"), "-1d4");
+    }
+
+    #[test]
+    fn test_merge_constants() {
+        assert_eq!(optimized("2 + 1d20 + 3"), "1d20 + 5");
+        assert_eq!(optimized("1d20 - 2 - 3"), "1d20 - 5");
+        // Opposite signs could hide an overflow, so they stay separate
+        assert_eq!(optimized("1d20 + 5 - 3"), "1d20 + 5 - 3");
+    }
+
+    #[test]
+    fn test_merge_rolls() {
+        assert_eq!(optimized("1d6 + 2d6"), "3d6");
+        assert_eq!(optimized("1d6 + 1d6 + 1d6 + 4"), "3d6 + 4");
+        assert_eq!(optimized("(1d6 + 0) * 1 + 2d6 + 2 + 3"), "3d6 + 5");
+        assert_eq!(optimized("1d20 - 1d4 - 1d4"), "1d20 - 2d4");
+        assert_eq!(optimized("2dF + 2dF"), "4dF");
+        // Different dice, modifiers, or subtraction don't merge
+        assert_eq!(optimized("1d6 + 1d8"), "1d6 + 1d8");
+        assert_eq!(optimized("4d6kh3 + 1d6"), "4d6kh3 + 1d6");
+        assert_eq!(optimized("1d6 - 1d6"), "1d6 - 1d6");
+    }
+
+    #[test]
+    fn test_fold_dynamic_rolls() {
+        assert_eq!(optimized("(1 + 2)d6kh1"), "3d6kh1");
+        assert_eq!(optimized("2d(3 * 4)"), "2d12");
+        assert_eq!(optimized("(1d4)d(2 + 4)"), "(1d4)d6");
+        // A count that fails the roll is left for the roll to reject
+        assert_eq!(optimized("(0 - 1)d6"), "(-1)d6");
+    }
+
+    #[test]
+    fn test_rounding_stays_exact() {
+        assert_eq!(optimized("floor(7 / 2)"), "3");
+        assert_eq!(optimized("ceil(1 / 2 + 1 / 2)"), "1");
+        // 1/2 isn't an integer, so it can't be folded on its own
+        assert_eq!(optimized("floor(1d6 / 2 + 1 / 2)"), "floor(1d6 / 2 + 1 / 2)");
+        assert_eq!(optimized("floor(1d6 / 2 // 1)"), "floor(1d6 / 2 // 1)");
+    }
+
+    #[test]
+    fn test_errors_are_preserved() {
+        assert_eq!(optimized("1d6 / (1 - 1)"), "1d6 / 0");
+        assert_eq!(optimized("4294967295 * 4294967295"), "4294967295 * 4294967295");
+    }
+
+    #[test]
+    fn test_folded_constants_reparse() {
+        let folded = optimized("65536 * 65536 + 1d6");
+        assert_eq!(folded, "1d6 + 4294967296");
+        assert_eq!(parse(&folded).unwrap().to_string(), folded);
+    }
+
+    #[test]
+    fn test_merge_respects_limits() {
+        let expr = parse("8d6 + 8d6").unwrap();
+        assert_eq!(optimize(&expr).to_string(), "16d6");
+        let limits = EvalLimits {
+            max_dice_per_roll: 10,
+            ..EvalLimits::default()
+        };
+        assert_eq!(optimize_with_limits(&expr, &limits).to_string(), "8d6 + 8d6");
+    }
+
+    #[test]
+    fn test_simulated_distribution_unchanged() {
+        for input in ["(1d6 + 0) * 1 + 2 + 3", "1d6 + 2d6 + 1d6", "floor((2d8 + 1) / 2) + max(1, 2)"] {
+            let before = simulate_seeded(input, 5000, 11).unwrap();
+            let after = simulate_seeded(&optimized(input), 5000, 11).unwrap();
+            assert_eq!(before.distribution, after.distribution, "{}", input);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_optimize_preserves_rolls(expr in expr(), seed in any::<u64>()) {
+            let optimized = optimize(&expr);
+            let before = evaluate_with_rng(&expr, &mut FastRng::with_seed(seed));
+            let after = evaluate_with_rng(&optimized, &mut FastRng::with_seed(seed));
+            match (before, after) {
+                (Ok(before), Ok(after)) => prop_assert_eq!(before.total, after.total),
+                (Err(_), Err(_)) => {}
+                (before, after) => prop_assert!(false, "{}: {:?} vs {:?}", optimized, before, after),
+            }
+        }
+
+        #[test]
+        fn prop_optimize_output_parses(expr in expr()) {
+            let printed = optimize(&expr).to_string();
+            let reparsed = parse(&printed);
+            prop_assert!(reparsed.is_ok(), "{} did not parse", printed);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 // ABOUTME: Recursive descent parser for dice notation expressions.
 // ABOUTME: Converts token streams into an AST.
 
-use crate::ast::{Compare, Condition, Expr, Modifier, Op, Roll, Sides};
+use crate::ast::{Band, Compare, Condition, DynamicRoll, DynamicSides, Expr, Func, Modifier, Op, Roll, Sides};
 use crate::error::{Error, Result};
 use crate::lexer::{Lexer, Token};
 
@@ -21,7 +21,7 @@ impl<'a> Parser<'a> {
 
     /// Parse the input into an expression.
     pub fn parse(&mut self) -> Result<Expr> {
-        let expr = self.expression()?;
+        let expr = self.expression_or_bands()?;
         if self.current != Token::Eof {
             return Err(Error::Expected {
                 expected: "end of input".to_string(),
@@ -70,7 +70,16 @@ impl<'a> Parser<'a> {
         Ok(left)
     }
 
-    /// Parse a term (handles * and /).
+    /// Parse an expression, followed by an outcome table if one is given.
+    fn expression_or_bands(&mut self) -> Result<Expr> {
+        let expr = self.expression()?;
+        if self.current == Token::LBrace {
+            return self.bands(expr);
+        }
+        Ok(expr)
+    }
+
+    /// Parse a term (handles *, / and //).
     fn term(&mut self) -> Result<Expr> {
         let mut left = self.factor()?;
 
@@ -78,6 +87,7 @@ impl<'a> Parser<'a> {
             let op = match self.current {
                 Token::Star => Op::Mul,
                 Token::Slash => Op::Div,
+                Token::DoubleSlash => Op::FloorDiv,
                 _ => break,
             };
             self.advance()?;
@@ -92,16 +102,19 @@ impl<'a> Parser<'a> {
         Ok(left)
     }
 
-    /// Parse a factor (number, roll, or parenthesized expression).
+    /// Parse a factor (number, roll, function call, or parenthesized expression).
     fn factor(&mut self) -> Result<Expr> {
         match &self.current {
             Token::Number(_) => self.roll_or_number(),
             Token::D => self.roll_or_number(),
+            Token::Ident(_) => self.call(),
+            Token::Var(_) => {
+                let var = self.variable()?;
+                self.dynamic_roll_or(var)
+            }
             Token::LParen => {
-                self.advance()?;
-                let expr = self.expression()?;
-                self.expect(Token::RParen)?;
-                Ok(Expr::Group(Box::new(expr)))
+                let group = self.group()?;
+                self.dynamic_roll_or(group)
             }
             Token::Minus => {
                 self.advance()?;
@@ -119,6 +132,166 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parse an outcome table after an expression: "{<=6: miss, 7..9: weak}".
+    fn bands(&mut self, expr: Expr) -> Result<Expr> {
+        self.expect(Token::LBrace)?;
+        let mut bands = vec![self.band()?];
+        while self.current == Token::Comma {
+            self.advance()?;
+            bands.push(self.band()?);
+        }
+        self.expect(Token::RBrace)?;
+        Ok(Expr::Bands {
+            expr: Box::new(expr),
+            bands,
+        })
+    }
+
+    /// Parse one band: a range ("<=6", ">=10", "7..9", "12"), ':', and a label.
+    fn band(&mut self) -> Result<Band> {
+        let range = match self.current {
+            Token::Lt => {
+                self.advance()?;
+                if self.current == Token::Eq {
+                    self.advance()?;
+                    i64::MIN..=self.band_value()?
+                } else {
+                    i64::MIN..=self.band_value()? - 1
+                }
+            }
+            Token::Gt => {
+                self.advance()?;
+                if self.current == Token::Eq {
+                    self.advance()?;
+                    self.band_value()?..=i64::MAX
+                } else {
+                    self.band_value()? + 1..=i64::MAX
+                }
+            }
+            Token::Eq => {
+                self.advance()?;
+                let value = self.band_value()?;
+                value..=value
+            }
+            _ => {
+                let start = self.band_value()?;
+                if self.current == Token::DotDot {
+                    self.advance()?;
+                    start..=self.band_value()?
+                } else {
+                    start..=start
+                }
+            }
+        };
+        if range.is_empty() {
+            return Err(Error::Expected {
+                expected: "band range with its low end first".to_string(),
+                found: format!("{}..{}", range.start(), range.end()),
+            });
+        }
+        if self.current != Token::Colon {
+            return Err(Error::Expected {
+                expected: "':' after band range".to_string(),
+                found: format!("{:?}", self.current),
+            });
+        }
+        // The label is free text, so it is read straight from the input
+        let label = self.lexer.label()?;
+        self.current = self.lexer.next_token()?;
+        Ok(Band { range, label })
+    }
+
+    /// Parse a band's bound: a whole number, which may be negative.
+    fn band_value(&mut self) -> Result<i64> {
+        let negative = self.current == Token::Minus;
+        if negative {
+            self.advance()?;
+        }
+        match self.advance()? {
+            Token::Number(n) if negative => Ok(-n),
+            Token::Number(n) => Ok(n),
+            found => Err(Error::Expected {
+                expected: "number in band range".to_string(),
+                found: format!("{:?}", found),
+            }),
+        }
+    }
+
+    /// Parse a parenthesized expression.
+    fn group(&mut self) -> Result<Expr> {
+        self.expect(Token::LParen)?;
+        let expr = self.expression_or_bands()?;
+        self.expect(Token::RParen)?;
+        Ok(Expr::Group(Box::new(expr)))
+    }
+
+    /// Parse a variable reference (@level).
+    fn variable(&mut self) -> Result<Expr> {
+        let Token::Var(name) = self.advance()? else {
+            unreachable!("variable() is only entered on a variable");
+        };
+        Ok(Expr::Var(name))
+    }
+
+    /// Parse a dice roll counting `count` dice if a 'd' follows, as in
+    /// "(1d4)d6" or "@level d8", or else return `count` itself.
+    fn dynamic_roll_or(&mut self, count: Expr) -> Result<Expr> {
+        if self.current != Token::D {
+            return Ok(count);
+        }
+        self.advance()?;
+        self.dynamic_roll(count)
+    }
+
+    /// Parse the rest of a dice roll with a computed count or sides, after the 'd'.
+    fn dynamic_roll(&mut self, count: Expr) -> Result<Expr> {
+        let sides = match self.current {
+            Token::LParen => DynamicSides::Expr(Box::new(self.group()?)),
+            Token::Var(_) => DynamicSides::Expr(Box::new(self.variable()?)),
+            _ => DynamicSides::Fixed(self.sides()?),
+        };
+        let modifiers = self.modifiers()?;
+        Ok(Expr::DynamicRoll(DynamicRoll {
+            count: Box::new(count),
+            sides,
+            modifiers,
+        }))
+    }
+
+    /// Parse a function call (floor(...), max(a, b), clamp(x, lo, hi), ...).
+    fn call(&mut self) -> Result<Expr> {
+        let Token::Ident(name) = self.advance()? else {
+            unreachable!("call() is only entered on an identifier");
+        };
+        let func = Func::from_name(&name).ok_or_else(|| Error::Expected {
+            expected: "function name".to_string(),
+            found: name.clone(),
+        })?;
+
+        self.expect(Token::LParen)?;
+        let mut args = vec![self.expression()?];
+        while self.current == Token::Comma {
+            self.advance()?;
+            args.push(self.expression()?);
+        }
+        self.expect(Token::RParen)?;
+
+        let arity = func.arity();
+        if !arity.contains(&args.len()) {
+            let expected = if arity.start() == arity.end() {
+                format!("{} argument(s) to {}", arity.start(), func)
+            } else {
+                format!("at least {} arguments to {}", arity.start(), func)
+            };
+            return Err(Error::Expected {
+                expected,
+                found: args.len().to_string(),
+            });
+        }
+
+        Ok(Expr::Call { func, args })
+    }
+
     /// Parse a dice roll or plain number.
     fn roll_or_number(&mut self) -> Result<Expr> {
         // Get the optional count
@@ -131,12 +304,19 @@ impl<'a> Parser<'a> {
 
         // Check if this is a dice roll or just a number
         if self.current != Token::D {
-            return Ok(Expr::Number(count as i64));
+            return Ok(Expr::Number(count));
         }
 
         // It's a dice roll - consume the 'd'
         self.advance()?;
 
+        // Sides from an expression make this a dynamic roll ("2d(1d4)"), as
+        // does a count too large for a plain roll, which the roll rejects
+        let count = match u32::try_from(count) {
+            Ok(count) if !matches!(self.current, Token::LParen | Token::Var(_)) => count,
+            _ => return self.dynamic_roll(Expr::Number(count)),
+        };
+
         // Parse the sides
         let sides = self.sides()?;
 
@@ -156,7 +336,7 @@ impl<'a> Parser<'a> {
             Token::Number(n) => {
                 let n = *n;
                 self.advance()?;
-                Ok(Sides::Number(n))
+                Ok(Sides::Number(u32::try_from(n).map_err(|_| Error::InvalidDiceSides(n))?))
             }
             Token::Percent => {
                 self.advance()?;
@@ -167,17 +347,20 @@ impl<'a> Parser<'a> {
                 Ok(Sides::Fudge)
             }
             _ => Err(Error::Expected {
-                expected: "dice sides (number, %, or F)".to_string(),
+                expected: "dice sides (number, %, F, or '(')".to_string(),
                 found: format!("{:?}", self.current),
             }),
         }
     }
 
-    /// Parse modifiers (keep, drop, explode, reroll).
+    /// Parse modifiers (keep, drop, explode, reroll, wild die, min/max).
     fn modifiers(&mut self) -> Result<Vec<Modifier>> {
         let mut modifiers = Vec::new();
+        // Whether the last modifier was a plain "!", which marks a wild die's ace
+        let mut ace = false;
 
         loop {
+            let after_ace = std::mem::take(&mut ace);
             match self.current {
                 Token::K => {
                     self.advance()?;
@@ -185,16 +368,60 @@ impl<'a> Parser<'a> {
                 }
                 Token::Explode => {
                     self.advance()?;
+                    let plain = !matches!(self.current, Token::Explode | Token::P | Token::Eq | Token::Lt | Token::Gt);
                     modifiers.push(self.explode_modifier()?);
+                    ace = plain;
                 }
                 Token::R => {
                     self.advance()?;
                     modifiers.push(self.reroll_modifier()?);
                 }
+                Token::S => {
+                    self.advance()?;
+                    modifiers.push(self.sort_modifier()?);
+                }
+                Token::M => {
+                    self.advance()?;
+                    modifiers.push(self.matches_modifier()?);
+                }
+                Token::W => {
+                    self.advance()?;
+                    // The wild die aces the dice anyway, so a plain '!' before
+                    // it ("d8!w6") just marks the ace
+                    if after_ace {
+                        modifiers.pop();
+                    }
+                    modifiers.push(Modifier::WildDie(self.optional_number(6)?));
+                }
+                // "min3" after dice is a per-die minimum, not the function.
+                // Fudge dice can go negative, so "4dFmax-1" is allowed too.
+                Token::Ident(ref name)
+                    if matches!(name.as_str(), "min" | "max")
+                        && matches!(self.lexer.peek()?, Token::Number(_) | Token::Minus) =>
+                {
+                    let minimum = name == "min";
+                    self.advance()?;
+                    let negative = self.current == Token::Minus;
+                    if negative {
+                        self.advance()?;
+                    }
+                    let n = match self.advance()? {
+                        Token::Number(n) if negative => -n,
+                        Token::Number(n) => n,
+                        found => {
+                            return Err(Error::Expected {
+                                expected: format!("number after {}", if minimum { "min" } else { "max" }),
+                                found: format!("{:?}", found),
+                            })
+                        }
+                    };
+                    modifiers.push(if minimum { Modifier::Min(n) } else { Modifier::Max(n) });
+                }
                 Token::D => {
-                    // In modifier context, 'd' followed by 'h' or 'l' is a drop modifier
+                    // In modifier context, 'd' followed by 'h', 'l' or a
+                    // comparison is a drop modifier
                     let next = self.lexer.peek()?;
-                    if matches!(next, Token::H | Token::L) {
+                    if matches!(next, Token::H | Token::L | Token::Gt | Token::Lt | Token::Eq) {
                         self.advance()?;
                         modifiers.push(self.drop_modifier()?);
                     } else {
@@ -205,6 +432,7 @@ impl<'a> Parser<'a> {
                 Token::Gt | Token::Lt | Token::Eq => {
                     let condition = self.required_condition()?;
                     modifiers.push(Modifier::CountSuccesses(condition));
+                    self.success_modifiers(&mut modifiers)?;
                 }
                 _ => break,
             }
@@ -213,8 +441,66 @@ impl<'a> Parser<'a> {
         Ok(modifiers)
     }
 
-    /// Parse a keep modifier (kh3, kl1, k3).
+    /// Parse failure (f1, f<3) and double-success (dd10, dd>=9) conditions,
+    /// which may only follow a success condition.
+    fn success_modifiers(&mut self, modifiers: &mut Vec<Modifier>) -> Result<()> {
+        loop {
+            match self.current {
+                Token::Fudge => {
+                    self.advance()?;
+                    modifiers.push(Modifier::CountFailures(self.shorthand_condition()?));
+                }
+                // A single 'd' is still a drop modifier
+                Token::D if self.lexer.peek()? == Token::D => {
+                    self.advance()?;
+                    self.advance()?;
+                    modifiers.push(Modifier::DoubleSuccesses(self.shorthand_condition()?));
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Parse a condition where a bare number means `=` (f1 is f=1).
+    fn shorthand_condition(&mut self) -> Result<Condition> {
+        if let Token::Number(_) = self.current {
+            return self.finish_condition(Compare::Equal);
+        }
+        self.required_condition()
+    }
+
+    /// Parse a sort modifier (s, sa, sd).
+    fn sort_modifier(&mut self) -> Result<Modifier> {
+        let descending = match self.current {
+            Token::A => {
+                self.advance()?;
+                false
+            }
+            // "sdh1" is an ascending sort followed by a drop
+            Token::D if !matches!(self.lexer.peek()?, Token::H | Token::L) => {
+                self.advance()?;
+                true
+            }
+            _ => false,
+        };
+        Ok(Modifier::Sort { descending })
+    }
+
+    /// Parse a match modifier (m, m3, mt, mt3).
+    fn matches_modifier(&mut self) -> Result<Modifier> {
+        let count_sets = self.current == Token::T;
+        if count_sets {
+            self.advance()?;
+        }
+        let width = self.optional_number(2)?;
+        Ok(Modifier::Matches { width, count_sets })
+    }
+
+    /// Parse a keep modifier (kh3, kl1, k3, k>=4).
     fn keep_modifier(&mut self) -> Result<Modifier> {
+        if let Some(condition) = self.optional_condition()? {
+            return Ok(Modifier::KeepWhere(condition));
+        }
         let high = match self.current {
             Token::H => {
                 self.advance()?;
@@ -236,8 +522,11 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Parse a drop modifier (dh3, dl1).
+    /// Parse a drop modifier (dh3, dl1, d<3).
     fn drop_modifier(&mut self) -> Result<Modifier> {
+        if let Some(condition) = self.optional_condition()? {
+            return Ok(Modifier::DropWhere(condition));
+        }
         let high = match self.current {
             Token::H => {
                 self.advance()?;
@@ -249,7 +538,7 @@ impl<'a> Parser<'a> {
             }
             _ => {
                 return Err(Error::Expected {
-                    expected: "'h' or 'l' after 'd'".to_string(),
+                    expected: "'h', 'l' or a comparison after 'd'".to_string(),
                     found: format!("{:?}", self.current),
                 });
             }
@@ -264,7 +553,11 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Parse an explode modifier (!, !!, !p, !!p, !>5, !!p>5).
+    /// Parse an explode modifier (!, !!, !p, !!p, !>5, !!p>5, !=).
+    ///
+    /// A bare '=' spells out the default, the die's maximum, where the next
+    /// modifier would otherwise run on: "1d(1d4)!=!>3" explodes on the
+    /// maximum, then on rolls over 3.
     fn explode_modifier(&mut self) -> Result<Modifier> {
         let compounding = if self.current == Token::Explode {
             self.advance()?;
@@ -280,13 +573,25 @@ impl<'a> Parser<'a> {
             false
         };
 
-        let condition = self.optional_condition()?;
+        let condition = if self.current == Token::Eq && !matches!(self.lexer.peek()?, Token::Number(_)) {
+            self.advance()?;
+            None
+        } else {
+            self.optional_condition()?
+        };
 
         Ok(Modifier::Explode { compounding, penetrating, condition })
     }
 
-    /// Parse a reroll modifier (r, ro, r<3).
+    /// Parse a reroll modifier (r, ro, r<3, rb<3, rw).
     fn reroll_modifier(&mut self) -> Result<Modifier> {
+        if let Token::B | Token::W = self.current {
+            let best = self.current == Token::B;
+            self.advance()?;
+            let condition = self.optional_condition()?;
+            return Ok(Modifier::RerollKeep { best, condition });
+        }
+
         let once = if self.current == Token::O {
             self.advance()?;
             true
@@ -303,7 +608,10 @@ impl<'a> Parser<'a> {
     fn optional_number(&mut self, default: u32) -> Result<u32> {
         if let Token::Number(n) = self.current {
             self.advance()?;
-            Ok(n)
+            u32::try_from(n).map_err(|_| Error::Expected {
+                expected: format!("number up to {}", u32::MAX),
+                found: n.to_string(),
+            })
         } else {
             Ok(default)
         }
@@ -355,10 +663,7 @@ impl<'a> Parser<'a> {
     fn finish_condition(&mut self, compare: Compare) -> Result<Condition> {
         if let Token::Number(n) = self.current {
             self.advance()?;
-            Ok(Condition {
-                compare,
-                value: n as i64,
-            })
+            Ok(Condition { compare, value: n })
         } else {
             Err(Error::Expected {
                 expected: "number after comparison".to_string(),
@@ -681,4 +986,360 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_parse_failures_and_doubles() {
+        let expr = parse("10d10>=8f1dd10").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Roll(Roll {
+                count: 10,
+                sides: Sides::Number(10),
+                modifiers: vec![
+                    Modifier::CountSuccesses(Condition {
+                        compare: Compare::GreaterOrEqual,
+                        value: 8,
+                    }),
+                    Modifier::CountFailures(Condition {
+                        compare: Compare::Equal,
+                        value: 1,
+                    }),
+                    Modifier::DoubleSuccesses(Condition {
+                        compare: Compare::Equal,
+                        value: 10,
+                    }),
+                ],
+            })
+        );
+
+        let expr = parse("6d10>7f<3dl1").unwrap();
+        match expr {
+            Expr::Roll(roll) => {
+                assert_eq!(
+                    roll.modifiers[1],
+                    Modifier::CountFailures(Condition {
+                        compare: Compare::LessThan,
+                        value: 3,
+                    })
+                );
+                assert_eq!(roll.modifiers[2], Modifier::DropLowest(1));
+            }
+            _ => panic!("Expected a roll"),
+        }
+    }
+
+    #[test]
+    fn test_parse_failures_need_successes() {
+        assert!(parse("10d10f1").is_err());
+        assert!(parse("10d10>=8f").is_err());
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        let sorts = |input: &str| match parse(input).unwrap() {
+            Expr::Roll(roll) => roll.modifiers,
+            _ => panic!("Expected a roll"),
+        };
+        assert_eq!(sorts("8d6s"), vec![Modifier::Sort { descending: false }]);
+        assert_eq!(sorts("8d6sa"), vec![Modifier::Sort { descending: false }]);
+        assert_eq!(sorts("8d6sd"), vec![Modifier::Sort { descending: true }]);
+        assert_eq!(
+            sorts("4d6sdl1"),
+            vec![Modifier::Sort { descending: false }, Modifier::DropLowest(1)]
+        );
+        assert_eq!(
+            sorts("4d6sddl1"),
+            vec![Modifier::Sort { descending: true }, Modifier::DropLowest(1)]
+        );
+        assert_eq!(
+            sorts("5d10>=8sd"),
+            vec![
+                Modifier::CountSuccesses(Condition {
+                    compare: Compare::GreaterOrEqual,
+                    value: 8,
+                }),
+                Modifier::Sort { descending: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_reroll_keep() {
+        let modifiers = |input: &str| match parse(input).unwrap() {
+            Expr::Roll(roll) => roll.modifiers,
+            _ => panic!("Expected a roll"),
+        };
+        assert_eq!(
+            modifiers("1d20rb"),
+            vec![Modifier::RerollKeep {
+                best: true,
+                condition: None,
+            }]
+        );
+        assert_eq!(
+            modifiers("2d6rw<3kh1"),
+            vec![
+                Modifier::RerollKeep {
+                    best: false,
+                    condition: Some(Condition {
+                        compare: Compare::LessThan,
+                        value: 3,
+                    }),
+                },
+                Modifier::KeepHighest(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_min_max() {
+        let modifiers = |input: &str| match parse(input).unwrap() {
+            Expr::Roll(roll) => roll.modifiers,
+            _ => panic!("Expected a roll"),
+        };
+        assert_eq!(modifiers("2d6min3"), vec![Modifier::Min(3)]);
+        assert_eq!(
+            modifiers("4d6MAX5kh3"),
+            vec![Modifier::Max(5), Modifier::KeepHighest(3)]
+        );
+        assert_eq!(modifiers("2d6rmin2"), modifiers("2d6r min2"));
+        assert_eq!(modifiers("4dFmin-1max1"), vec![Modifier::Min(-1), Modifier::Max(1)]);
+        assert!(parse("4dFmax-").is_err());
+        // Without a number it's still the function, which can't follow dice
+        assert!(parse("2d6min").is_err());
+        assert!(parse("2d6 + min(1, 2)").is_ok());
+    }
+
+    #[test]
+    fn test_parse_wild_die() {
+        let modifiers = |input: &str| match parse(input).unwrap() {
+            Expr::Roll(roll) => roll.modifiers,
+            _ => panic!("Expected a roll"),
+        };
+        assert_eq!(modifiers("d8w"), vec![Modifier::WildDie(6)]);
+        assert_eq!(modifiers("d8!w6"), vec![Modifier::WildDie(6)]);
+        assert_eq!(modifiers("d12w8"), vec![Modifier::WildDie(8)]);
+        // Only a plain '!' is folded into the wild die
+        assert_eq!(modifiers("d8!!w").len(), 2);
+    }
+
+    #[test]
+    fn test_parse_matches() {
+        let matches = |input: &str| match parse(input).unwrap() {
+            Expr::Roll(roll) => roll.modifiers,
+            _ => panic!("Expected a roll"),
+        };
+        assert_eq!(
+            matches("6d10m"),
+            vec![Modifier::Matches {
+                width: 2,
+                count_sets: false,
+            }]
+        );
+        assert_eq!(
+            matches("6d10mt3"),
+            vec![Modifier::Matches {
+                width: 3,
+                count_sets: true,
+            }]
+        );
+        assert_eq!(
+            matches("6d10kh5m"),
+            vec![
+                Modifier::KeepHighest(5),
+                Modifier::Matches {
+                    width: 2,
+                    count_sets: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_keep_and_drop_conditions() {
+        let modifiers = |input: &str| match parse(input).unwrap() {
+            Expr::Roll(roll) => roll.modifiers,
+            _ => panic!("Expected a roll"),
+        };
+        assert_eq!(
+            modifiers("6d6k>=4"),
+            vec![Modifier::KeepWhere(Condition {
+                compare: Compare::GreaterOrEqual,
+                value: 4,
+            })]
+        );
+        assert_eq!(
+            modifiers("6d6d<3kh2"),
+            vec![
+                Modifier::DropWhere(Condition {
+                    compare: Compare::LessThan,
+                    value: 3,
+                }),
+                Modifier::KeepHighest(2),
+            ]
+        );
+        // After a success condition, 'dd' is a double and 'd' is still a drop
+        assert_eq!(
+            modifiers("6d6>=4dd<3")[1],
+            Modifier::DoubleSuccesses(Condition {
+                compare: Compare::LessThan,
+                value: 3,
+            })
+        );
+        assert_eq!(
+            modifiers("6d6>=4d<3")[1],
+            Modifier::DropWhere(Condition {
+                compare: Compare::LessThan,
+                value: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_floor_division() {
+        let expr = parse("7 // 2").unwrap();
+        assert_eq!(
+            expr,
+            Expr::BinOp {
+                op: Op::FloorDiv,
+                left: Box::new(Expr::Number(7)),
+                right: Box::new(Expr::Number(2)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rounding_call() {
+        let expr = parse("floor(8d6 / 2)").unwrap();
+        match expr {
+            Expr::Call { func, args } => {
+                assert_eq!(func, Func::Floor);
+                assert_eq!(args.len(), 1);
+                assert!(matches!(args[0], Expr::BinOp { op: Op::Div, .. }));
+            }
+            _ => panic!("Expected Call"),
+        }
+    }
+
+    #[test]
+    fn test_parse_call_requires_parens() {
+        assert!(parse("floor 3").is_err());
+        assert!(parse("ceil(3").is_err());
+    }
+
+    #[test]
+    fn test_parse_multi_argument_call() {
+        let expr = parse("max(1d6 - 2, 1)").unwrap();
+        match expr {
+            Expr::Call { func, args } => {
+                assert_eq!(func, Func::Max);
+                assert_eq!(args.len(), 2);
+                assert_eq!(args[1], Expr::Number(1));
+            }
+            _ => panic!("Expected Call"),
+        }
+
+        let expr = parse("clamp(2d6, 3, 10)").unwrap();
+        assert!(matches!(expr, Expr::Call { func: Func::Clamp, ref args } if args.len() == 3));
+    }
+
+    #[test]
+    fn test_parse_call_arity() {
+        assert!(parse("max(1)").is_err());
+        assert!(parse("abs(1, 2)").is_err());
+        assert!(parse("clamp(1, 2)").is_err());
+        assert!(parse("min(1, 2, 3)").is_ok());
+    }
+
+    #[test]
+    fn test_parse_dynamic_roll() {
+        let dynamic = |input: &str| match parse(input).unwrap() {
+            Expr::DynamicRoll(roll) => roll,
+            other => panic!("Expected a dynamic roll, found {:?}", other),
+        };
+
+        let roll = dynamic("(1d4)d6kh2");
+        assert!(matches!(*roll.count, Expr::Group(_)));
+        assert_eq!(roll.sides, DynamicSides::Fixed(Sides::Number(6)));
+        assert_eq!(roll.modifiers, vec![Modifier::KeepHighest(2)]);
+
+        let roll = dynamic("1d(2d4)");
+        assert_eq!(*roll.count, Expr::Number(1));
+        assert!(matches!(roll.sides, DynamicSides::Expr(ref sides) if matches!(**sides, Expr::Group(_))));
+
+        let roll = dynamic("@level d8");
+        assert_eq!(*roll.count, Expr::Var("level".to_string()));
+        assert_eq!(dynamic("d@size").sides, DynamicSides::Expr(Box::new(Expr::Var("size".to_string()))));
+
+        // Without a 'd' these are a plain group and variable
+        assert!(matches!(parse("(1d4) + 1").unwrap(), Expr::BinOp { .. }));
+        assert_eq!(parse("@bonus").unwrap(), Expr::Var("bonus".to_string()));
+        assert!(parse("(1d4)d").is_err());
+    }
+
+    #[test]
+    fn test_parse_spelled_out_default_explode() {
+        let explode = |compounding| Modifier::Explode {
+            compounding,
+            penetrating: false,
+            condition: None,
+        };
+        let modifiers = |input: &str| match parse(input).unwrap() {
+            Expr::DynamicRoll(roll) => roll.modifiers,
+            other => panic!("Expected a dynamic roll, found {:?}", other),
+        };
+        assert_eq!(modifiers("1d(1d4)!="), vec![explode(false)]);
+        assert_eq!(
+            modifiers("1d(1d4)!=!>3"),
+            vec![explode(false), Modifier::Explode {
+                compounding: false,
+                penetrating: false,
+                condition: Some(Condition::gt(3)),
+            }]
+        );
+        assert_eq!(modifiers("1d(1d4)!!==3"), vec![explode(true), Modifier::CountSuccesses(Condition::eq(3))]);
+        // Spelled out, the explode isn't just the wild die's ace
+        assert_eq!(modifiers("1d(1d4)!=w6"), vec![explode(false), Modifier::WildDie(6)]);
+        assert_eq!(modifiers("1d(1d4)!w6"), vec![Modifier::WildDie(6)]);
+    }
+
+    #[test]
+    fn test_parse_bands() {
+        let Expr::Bands { expr, bands } = parse("2d6+1 {<=6: miss, 7..9: weak hit, >=10: strong}").unwrap() else {
+            panic!("Expected bands");
+        };
+        assert!(matches!(*expr, Expr::BinOp { op: Op::Add, .. }));
+        assert_eq!(
+            bands,
+            vec![
+                Band::at_most(6, "miss"),
+                Band::new(7..=9, "weak hit"),
+                Band::at_least(10, "strong"),
+            ]
+        );
+
+        // Strict comparisons, single values and negative totals
+        let Expr::Bands { bands, .. } = parse("4dF {<-1: bad, =0: even, 1: one, -1..-1: minus, >1: good}").unwrap() else {
+            panic!("Expected bands");
+        };
+        let ranges: Vec<_> = bands.into_iter().map(|band| band.range).collect();
+        assert_eq!(ranges, vec![i64::MIN..=-2, 0..=0, 1..=1, -1..=-1, 2..=i64::MAX]);
+
+        assert!(parse("2d6 {9..7: backwards}").is_err());
+        assert!(parse("2d6 {<=6 miss}").is_err());
+        assert!(parse("2d6 {<=6: }").is_err());
+        assert!(parse("2d6 {<=6: miss").is_err());
+        // Inside a larger expression a table needs parentheses
+        assert!(parse("2d6 {<=6: miss} + 1").is_err());
+        let Expr::BinOp { left, .. } = parse("(2d6 {<=6: miss}) + 1").unwrap() else {
+            panic!("Expected an addition");
+        };
+        assert!(matches!(*left, Expr::Group(ref inner) if matches!(**inner, Expr::Bands { .. })));
+
+        // Quoted labels can hold the characters that end a plain one
+        let Expr::Bands { bands, .. } = parse(r#"1d6 {<=3: "low, \"bad\"", >3: "}"}"#).unwrap() else {
+            panic!("Expected bands");
+        };
+        assert_eq!(bands, vec![Band::at_most(3, r#"low, "bad""#), Band::at_least(4, "}")]);
+    }
 }
@@ -0,0 +1,819 @@
+// ABOUTME: Totals-only rolling: reusable compiled RollPlans and one-off roll_total.
+// ABOUTME: Skips formatting and per-die history; used by simulation's hot loop.
+
+use crate::ast::{Condition, DynamicRoll, DynamicSides, Expr, Func, Modifier, Op, Roll, Sides};
+use crate::error::{Error, Result};
+use crate::roller::{
+    apply_op, check_roll_limits, checked, counted_sets, dynamic_count, dynamic_sides, find_sets, roll_die,
+    EvalConfig, Fraction, LimitPolicy, ModifierOrder, Rng, Scoring,
+};
+
+/// A dice expression compiled for fast repeated rolling.
+///
+/// Compiling flattens the expression tree into a postfix instruction list and
+/// resolves each modifier's default condition once, up front. Rolling a plan
+/// produces only the total, reusing internal buffers between rolls, so it is
+/// the right tool for simulations and other hot loops. Use
+/// [`evaluate`](crate::roller::evaluate) when the individual dice or formatted
+/// output are needed.
+///
+/// Rolling a plan consumes random numbers in exactly the same order as
+/// evaluating the expression, so both give the same total for the same seed.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{FastRng, RollPlan};
+///
+/// let expr = diceman::parse("4d6kh3").unwrap();
+/// let mut plan = RollPlan::new(&expr);
+/// let mut rng = FastRng::with_seed(7);
+/// for _ in 0..1000 {
+///     let total = plan.roll(&mut rng).unwrap();
+///     assert!((3..=18).contains(&total));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RollPlan {
+    instructions: Vec<Instr>,
+    rolls: Vec<CompiledRoll>,
+    dynamic_rolls: Vec<CompiledDynamicRoll>,
+    /// Names of unbound variables, which fail the roll.
+    variables: Vec<String>,
+    /// Value stack, reused between rolls.
+    stack: Vec<Fraction>,
+    dice: Dice,
+}
+
+/// A single postfix instruction.
+#[derive(Debug, Clone, Copy)]
+enum Instr {
+    /// Push a literal.
+    Number(i64),
+    /// Roll `rolls[index]` and push its total.
+    Roll(usize),
+    /// Pop the count (and sides, if they are computed), roll
+    /// `dynamic_rolls[index]` and push its total.
+    DynamicRoll(usize),
+    /// Fail with the unbound variable `variables[index]`.
+    Var(usize),
+    /// Pop two integers and push the result of an integer operation.
+    Op(Op),
+    /// Pop two exact values and push the exact result (inside rounding functions).
+    ExactOp(Op),
+    /// Pop `argc` arguments and push the function's result.
+    Call(Func, usize),
+}
+
+/// A roll with its modifiers' default conditions resolved.
+#[derive(Debug, Clone)]
+struct CompiledRoll {
+    count: u32,
+    sides: Sides,
+    steps: Vec<Step>,
+    /// How dice are scored if the roll counts successes instead of summing.
+    scoring: Option<Scoring>,
+    /// The set width, if the roll counts sets of matching dice instead.
+    sets: Option<u32>,
+}
+
+/// A roll whose count or sides are only known when it is rolled, so its
+/// default conditions are resolved then.
+#[derive(Debug, Clone)]
+struct CompiledDynamicRoll {
+    /// The die type, or `None` if the sides are computed.
+    sides: Option<Sides>,
+    /// Modifiers in the order they apply.
+    modifiers: Vec<Modifier>,
+    scoring: Option<Scoring>,
+    sets: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Reroll { once: bool, condition: Condition },
+    RerollKeep { best: bool, condition: Condition },
+    WildDie { ace: Condition, wild: u32 },
+    Min(i64),
+    Max(i64),
+    Explode { compounding: bool, penetrating: bool, condition: Condition },
+    KeepHighest(usize),
+    KeepLowest(usize),
+    DropHighest(usize),
+    DropLowest(usize),
+    KeepWhere(Condition),
+    DropWhere(Condition),
+}
+
+/// The totals-only dice engine shared by [`RollPlan`] and [`roll_total`].
+#[derive(Debug, Clone)]
+struct Dice {
+    config: EvalConfig,
+    /// Dice rolled so far in this evaluation, checked against `max_total_dice`.
+    rolled: u32,
+    /// Dice of the roll being evaluated, reused between rolls.
+    dice: Vec<Die>,
+    /// Index scratch space for keep/drop, reused between rolls.
+    order: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Die {
+    value: i64,
+    dropped: bool,
+    /// Explosions so far in this die's chain, checked against `max_explosions`.
+    chain: u32,
+}
+
+impl RollPlan {
+    /// Compile an expression with the default configuration.
+    pub fn new(expr: &Expr) -> Self {
+        Self::with_config(expr, &EvalConfig::default())
+    }
+
+    /// Compile an expression with a custom evaluation configuration.
+    pub fn with_config(expr: &Expr, config: &EvalConfig) -> Self {
+        let mut plan = RollPlan {
+            instructions: Vec::new(),
+            rolls: Vec::new(),
+            dynamic_rolls: Vec::new(),
+            variables: Vec::new(),
+            stack: Vec::new(),
+            dice: Dice::new(config),
+        };
+        plan.compile(expr, false);
+        plan
+    }
+
+    /// Roll the plan once and return the total.
+    pub fn roll(&mut self, rng: &mut impl Rng) -> Result<i64> {
+        self.stack.clear();
+        self.dice.rolled = 0;
+
+        for i in 0..self.instructions.len() {
+            let value = match self.instructions[i] {
+                Instr::Number(n) => Fraction::integer(n),
+                Instr::Roll(index) => {
+                    let roll = &self.rolls[index];
+                    let total = self.dice.roll(
+                        roll.count,
+                        &roll.sides,
+                        roll.steps.iter().copied(),
+                        roll.scoring,
+                        roll.sets,
+                        rng,
+                    )?;
+                    Fraction::integer(total)
+                }
+                Instr::DynamicRoll(index) => {
+                    let roll = &self.dynamic_rolls[index];
+                    let limits = &self.dice.config.limits;
+                    let sides = match roll.sides {
+                        Some(sides) => sides,
+                        None => dynamic_sides(self.stack.pop().expect("compiled plans are balanced").trunc(), limits)?,
+                    };
+                    let count = self.stack.pop().expect("compiled plans are balanced").trunc();
+                    let total = self.dice.roll(
+                        dynamic_count(count, limits)?,
+                        &sides,
+                        roll.modifiers.iter().filter_map(|m| Step::new(m, &sides)),
+                        roll.scoring,
+                        roll.sets,
+                        rng,
+                    )?;
+                    Fraction::integer(total)
+                }
+                Instr::Var(index) => return Err(Error::UnknownVariable(self.variables[index].clone())),
+                Instr::Op(op) => {
+                    let (left, right) = self.pop_pair();
+                    Fraction::integer(apply_op(op, left.trunc(), right.trunc())?)
+                }
+                Instr::ExactOp(op) => {
+                    let (left, right) = self.pop_pair();
+                    left.apply(op, right)?
+                }
+                Instr::Call(func, argc) => {
+                    let args = self.stack.len() - argc;
+                    let total = call(func, &self.stack[args..])?;
+                    self.stack.truncate(args);
+                    Fraction::integer(total)
+                }
+            };
+            self.stack.push(value);
+        }
+
+        Ok(self.stack.pop().map(|v| v.trunc()).unwrap_or_default())
+    }
+
+    fn pop_pair(&mut self) -> (Fraction, Fraction) {
+        let right = self.stack.pop().expect("compiled plans are balanced");
+        let left = self.stack.pop().expect("compiled plans are balanced");
+        (left, right)
+    }
+
+    /// Emit postfix instructions for `expr`. `exact` is true inside the
+    /// argument of a rounding function, where division is not truncated.
+    fn compile(&mut self, expr: &Expr, exact: bool) {
+        match expr {
+            Expr::Number(n) => self.instructions.push(Instr::Number(*n)),
+            Expr::Roll(roll) => {
+                self.rolls.push(CompiledRoll::new(roll, self.dice.config.order));
+                self.instructions.push(Instr::Roll(self.rolls.len() - 1));
+            }
+            Expr::BinOp { op, left, right } => {
+                self.compile(left, exact);
+                self.compile(right, exact);
+                if exact {
+                    self.instructions.push(Instr::ExactOp(*op));
+                } else {
+                    self.instructions.push(Instr::Op(*op));
+                }
+            }
+            Expr::Group(inner) => self.compile(inner, exact),
+            // A table reads off a whole total, so its expression truncates
+            // even inside a rounding function, as in the evaluator
+            Expr::Bands { expr: inner, .. } => self.compile(inner, false),
+            Expr::Call { func, args } => {
+                for arg in args {
+                    self.compile(arg, func.is_rounding());
+                }
+                self.instructions.push(Instr::Call(*func, args.len()));
+            }
+            Expr::Var(name) => {
+                self.variables.push(name.clone());
+                self.instructions.push(Instr::Var(self.variables.len() - 1));
+            }
+            Expr::DynamicRoll(roll) => {
+                self.compile(&roll.count, false);
+                let sides = match &roll.sides {
+                    DynamicSides::Fixed(sides) => Some(*sides),
+                    DynamicSides::Expr(sides) => {
+                        self.compile(sides, false);
+                        None
+                    }
+                };
+                self.dynamic_rolls.push(CompiledDynamicRoll {
+                    sides,
+                    modifiers: self.dice.config.order.apply(&roll.modifiers).into_owned(),
+                    scoring: Scoring::from_modifiers(&roll.modifiers),
+                    sets: counted_sets(&roll.modifiers),
+                });
+                self.instructions.push(Instr::DynamicRoll(self.dynamic_rolls.len() - 1));
+            }
+        }
+    }
+}
+
+/// Roll an expression once and return only its total.
+///
+/// Unlike [`evaluate`](crate::roller::evaluate), this builds no formatted
+/// output and keeps no per-die history: rolls without modifiers are summed
+/// as they are rolled, and rolls with modifiers track only each die's value.
+/// To roll the same expression many times, compile it into a [`RollPlan`].
+///
+/// Like a plan, this consumes random numbers in the same order as evaluating
+/// the expression, so both give the same total for the same seed.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{roll_total, FastRng};
+///
+/// let expr = diceman::parse("10d10!>=8").unwrap();
+/// let total = roll_total(&expr, &mut FastRng::with_seed(1)).unwrap();
+/// assert!(total >= 10);
+/// ```
+pub fn roll_total(expr: &Expr, rng: &mut impl Rng) -> Result<i64> {
+    roll_total_with_config(expr, rng, &EvalConfig::default())
+}
+
+/// Roll an expression once with a custom configuration and return only its total.
+pub fn roll_total_with_config(expr: &Expr, rng: &mut impl Rng, config: &EvalConfig) -> Result<i64> {
+    Totals {
+        rng,
+        dice: Dice::new(config),
+    }
+    .total(expr)
+}
+
+/// Tree-walking counterpart of [`RollPlan::roll`] for one-off rolls.
+struct Totals<'a, R: Rng> {
+    rng: &'a mut R,
+    dice: Dice,
+}
+
+impl<R: Rng> Totals<'_, R> {
+    fn total(&mut self, expr: &Expr) -> Result<i64> {
+        match expr {
+            Expr::Number(n) => Ok(*n),
+            Expr::Roll(roll) => {
+                let modifiers = self.dice.config.order.apply(&roll.modifiers);
+                self.dice.roll(
+                    roll.count,
+                    &roll.sides,
+                    modifiers.iter().filter_map(|m| Step::new(m, &roll.sides)),
+                    Scoring::from_modifiers(&roll.modifiers),
+                    counted_sets(&roll.modifiers),
+                    self.rng,
+                )
+            }
+            Expr::DynamicRoll(roll) => self.dynamic_roll(roll),
+            Expr::Var(name) => Err(Error::UnknownVariable(name.clone())),
+            Expr::BinOp { op, left, right } => {
+                let left = self.total(left)?;
+                let right = self.total(right)?;
+                apply_op(*op, left, right)
+            }
+            Expr::Group(inner) | Expr::Bands { expr: inner, .. } => self.total(inner),
+            Expr::Call { func, args } if func.is_rounding() => {
+                let value = self.exact(&args[0])?;
+                call(*func, &[value])
+            }
+            Expr::Call { func: Func::Min, args } => self.fold(args, i64::min),
+            Expr::Call { func: Func::Max, args } => self.fold(args, i64::max),
+            Expr::Call { func, args } => {
+                // The remaining functions take at most three arguments
+                let mut values = [Fraction::integer(0); 3];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = Fraction::integer(self.total(arg)?);
+                }
+                call(*func, &values[..args.len().min(3)])
+            }
+        }
+    }
+
+    /// Evaluate a dynamic roll's count, then its sides, then roll it.
+    fn dynamic_roll(&mut self, roll: &DynamicRoll) -> Result<i64> {
+        let count = self.total(&roll.count)?;
+        let sides = match &roll.sides {
+            DynamicSides::Fixed(sides) => *sides,
+            DynamicSides::Expr(sides) => dynamic_sides(self.total(sides)?, &self.dice.config.limits)?,
+        };
+        let count = dynamic_count(count, &self.dice.config.limits)?;
+        let modifiers = self.dice.config.order.apply(&roll.modifiers);
+        self.dice.roll(
+            count,
+            &sides,
+            modifiers.iter().filter_map(|m| Step::new(m, &sides)),
+            Scoring::from_modifiers(&roll.modifiers),
+            counted_sets(&roll.modifiers),
+            self.rng,
+        )
+    }
+
+    /// Combine the totals of `args` pairwise, without collecting them.
+    fn fold(&mut self, args: &[Expr], combine: fn(i64, i64) -> i64) -> Result<i64> {
+        let mut result = None;
+        for arg in args {
+            let value = self.total(arg)?;
+            result = Some(result.map_or(value, |r| combine(r, value)));
+        }
+        Ok(result.unwrap_or_default())
+    }
+
+    /// Evaluate without truncating division, inside a rounding function.
+    fn exact(&mut self, expr: &Expr) -> Result<Fraction> {
+        match expr {
+            Expr::BinOp { op, left, right } => {
+                let left = self.exact(left)?;
+                let right = self.exact(right)?;
+                left.apply(*op, right)
+            }
+            Expr::Group(inner) => self.exact(inner),
+            _ => Ok(Fraction::integer(self.total(expr)?)),
+        }
+    }
+}
+
+impl CompiledRoll {
+    fn new(roll: &Roll, order: ModifierOrder) -> Self {
+        CompiledRoll {
+            count: roll.count,
+            sides: roll.sides,
+            steps: order
+                .apply(&roll.modifiers)
+                .iter()
+                .filter_map(|m| Step::new(m, &roll.sides))
+                .collect(),
+            scoring: Scoring::from_modifiers(&roll.modifiers),
+            sets: counted_sets(&roll.modifiers),
+        }
+    }
+}
+
+impl Step {
+    /// Resolve a modifier's default condition. Success counting, sorting and
+    /// matching are not steps; they don't change which dice are totalled.
+    fn new(modifier: &Modifier, sides: &Sides) -> Option<Self> {
+        Some(match modifier {
+            Modifier::Reroll { once, condition } => Step::Reroll {
+                once: *once,
+                condition: condition.unwrap_or_else(Condition::reroll_default),
+            },
+            Modifier::RerollKeep { best, condition } => Step::RerollKeep {
+                best: *best,
+                condition: condition.unwrap_or_else(Condition::reroll_default),
+            },
+            Modifier::Explode {
+                compounding,
+                penetrating,
+                condition,
+            } => Step::Explode {
+                compounding: *compounding,
+                penetrating: *penetrating,
+                condition: condition.unwrap_or_else(|| Condition::explode_default(sides)),
+            },
+            Modifier::WildDie(wild) => Step::WildDie {
+                ace: Condition::explode_default(sides),
+                wild: *wild,
+            },
+            Modifier::Min(n) => Step::Min(*n),
+            Modifier::Max(n) => Step::Max(*n),
+            Modifier::KeepHighest(n) => Step::KeepHighest(*n as usize),
+            Modifier::KeepLowest(n) => Step::KeepLowest(*n as usize),
+            Modifier::DropHighest(n) => Step::DropHighest(*n as usize),
+            Modifier::DropLowest(n) => Step::DropLowest(*n as usize),
+            Modifier::KeepWhere(condition) => Step::KeepWhere(*condition),
+            Modifier::DropWhere(condition) => Step::DropWhere(*condition),
+            Modifier::CountSuccesses(_)
+            | Modifier::CountFailures(_)
+            | Modifier::DoubleSuccesses(_)
+            | Modifier::Sort { .. }
+            | Modifier::Matches { .. } => return None,
+        })
+    }
+}
+
+impl Dice {
+    fn new(config: &EvalConfig) -> Self {
+        Dice {
+            config: *config,
+            rolled: 0,
+            dice: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// Roll `count` dice, apply `steps` in order, and return the total (or
+    /// the number of sets of `sets` or more matching dice, if that is set,
+    /// or else the net successes, if `scoring` is set).
+    fn roll(
+        &mut self,
+        count: u32,
+        sides: &Sides,
+        steps: impl Iterator<Item = Step>,
+        scoring: Option<Scoring>,
+        sets: Option<u32>,
+        rng: &mut impl Rng,
+    ) -> Result<i64> {
+        let Dice {
+            config,
+            rolled,
+            dice,
+            order,
+        } = self;
+
+        check_roll_limits(count, sides, &config.limits)?;
+        count_dice(rolled, count, config)?;
+
+        let mut steps = steps.peekable();
+        if steps.peek().is_none() && sets.is_none() {
+            // Nothing looks at the dice again, so total them as they are rolled
+            let mut total: i64 = 0;
+            for _ in 0..count {
+                let value = roll_die(rng, sides);
+                total = match scoring {
+                    Some(scoring) => total + scoring.score(value),
+                    None => checked(total.checked_add(value))?,
+                };
+            }
+            return Ok(total);
+        }
+
+        dice.clear();
+        for _ in 0..count {
+            dice.push(Die {
+                value: roll_die(rng, sides),
+                dropped: false,
+                chain: 0,
+            });
+        }
+
+        for step in steps {
+            match step {
+                Step::Reroll { once, condition } => {
+                    for die in dice.iter_mut().filter(|d| !d.dropped) {
+                        let mut reroll_count = 0;
+                        while condition.compare.check(die.value, condition.value) {
+                            if reroll_count >= config.max_rerolls {
+                                if config.on_limit == LimitPolicy::Error {
+                                    return Err(Error::RerollLimit(config.max_rerolls));
+                                }
+                                break;
+                            }
+                            die.value = roll_die(rng, sides);
+                            reroll_count += 1;
+                            if once {
+                                break;
+                            }
+                        }
+                    }
+                }
+                Step::RerollKeep { best, condition } => {
+                    for die in dice.iter_mut().filter(|d| !d.dropped) {
+                        if condition.compare.check(die.value, condition.value) {
+                            let new_value = roll_die(rng, sides);
+                            die.value = if best { die.value.max(new_value) } else { die.value.min(new_value) };
+                        }
+                    }
+                }
+                Step::WildDie { ace, wild } => {
+                    let kept = dice.iter().filter(|d| !d.dropped).count();
+                    for die in dice.iter_mut().filter(|d| !d.dropped) {
+                        die.value = compound(die.value, ace, sides, config, rng)?;
+                    }
+                    let wild_sides = Sides::Number(wild);
+                    check_roll_limits(1, &wild_sides, &config.limits)?;
+                    if dice.len() >= config.limits.max_dice_per_roll as usize {
+                        return Err(Error::DiceLimit(config.limits.max_dice_per_roll));
+                    }
+                    count_dice(rolled, 1, config)?;
+                    let value = roll_die(rng, &wild_sides);
+                    dice.push(Die {
+                        value: compound(value, Condition::explode_default(&wild_sides), &wild_sides, config, rng)?,
+                        dropped: false,
+                        chain: 0,
+                    });
+                    keep(dice, order, kept, false);
+                }
+                Step::Min(n) => {
+                    for die in dice.iter_mut().filter(|d| !d.dropped) {
+                        die.value = die.value.max(n);
+                    }
+                }
+                Step::Max(n) => {
+                    for die in dice.iter_mut().filter(|d| !d.dropped) {
+                        die.value = die.value.min(n);
+                    }
+                }
+                Step::Explode {
+                    compounding,
+                    penetrating,
+                    condition,
+                } => {
+                    for die in dice.iter_mut() {
+                        die.chain = 0;
+                    }
+                    let mut i = 0;
+                    while i < dice.len() {
+                        if dice[i].dropped {
+                            i += 1;
+                            continue;
+                        }
+                        let mut current_value = dice[i].value;
+                        let mut explode_count = dice[i].chain;
+                        while condition.compare.check(current_value, condition.value) {
+                            if explode_count >= config.max_explosions {
+                                if config.on_limit == LimitPolicy::Error {
+                                    return Err(Error::ExplodeLimit(config.max_explosions));
+                                }
+                                break;
+                            }
+                            let new_value = roll_die(rng, sides);
+                            explode_count += 1;
+                            let added_value = if penetrating { new_value - 1 } else { new_value };
+
+                            if compounding {
+                                dice[i].value = checked(dice[i].value.checked_add(added_value))?;
+                            } else {
+                                if dice.len() >= config.limits.max_dice_per_roll as usize {
+                                    return Err(Error::DiceLimit(config.limits.max_dice_per_roll));
+                                }
+                                count_dice(rolled, 1, config)?;
+                                dice.push(Die {
+                                    value: added_value,
+                                    dropped: false,
+                                    chain: explode_count,
+                                });
+                            }
+
+                            current_value = new_value;
+                            if !compounding {
+                                break;
+                            }
+                        }
+                        i += 1;
+                    }
+                }
+                Step::KeepHighest(n) => keep(dice, order, n, false),
+                Step::KeepLowest(n) => keep(dice, order, n, true),
+                Step::DropHighest(n) => drop(dice, order, n, true),
+                Step::DropLowest(n) => drop(dice, order, n, false),
+                Step::KeepWhere(condition) => filter(dice, condition, true),
+                Step::DropWhere(condition) => filter(dice, condition, false),
+            }
+        }
+
+        let mut active = dice.iter().filter(|d| !d.dropped);
+        if let Some(width) = sets {
+            Ok(find_sets(active.map(|d| d.value), width).len() as i64)
+        } else if let Some(scoring) = scoring {
+            Ok(active.map(|d| scoring.score(d.value)).sum())
+        } else {
+            active
+                .try_fold(0i64, |sum, d| sum.checked_add(d.value))
+                .ok_or(Error::Overflow)
+        }
+    }
+}
+
+/// Compound-explode a die showing `value` while its rolls match `condition`.
+fn compound(mut value: i64, condition: Condition, sides: &Sides, config: &EvalConfig, rng: &mut impl Rng) -> Result<i64> {
+    let mut current_value = value;
+    let mut explode_count = 0;
+    while condition.compare.check(current_value, condition.value) {
+        if explode_count >= config.max_explosions {
+            if config.on_limit == LimitPolicy::Error {
+                return Err(Error::ExplodeLimit(config.max_explosions));
+            }
+            break;
+        }
+        current_value = roll_die(rng, sides);
+        explode_count += 1;
+        value = checked(value.checked_add(current_value))?;
+    }
+    Ok(value)
+}
+
+/// Record newly rolled dice against the expression-wide limit.
+fn count_dice(rolled: &mut u32, n: u32, config: &EvalConfig) -> Result<()> {
+    *rolled = rolled.saturating_add(n);
+    if *rolled > config.limits.max_total_dice {
+        return Err(Error::TotalDiceLimit(config.limits.max_total_dice));
+    }
+    Ok(())
+}
+
+/// Fill `order` with the indices of active dice, sorted by value.
+fn sort_active(dice: &[Die], order: &mut Vec<usize>, descending: bool) {
+    order.clear();
+    order.extend((0..dice.len()).filter(|&i| !dice[i].dropped));
+    if descending {
+        order.sort_by_key(|&i| std::cmp::Reverse(dice[i].value));
+    } else {
+        order.sort_by_key(|&i| dice[i].value);
+    }
+}
+
+/// Keep `n` active dice, dropping the rest from the other end.
+fn keep(dice: &mut [Die], order: &mut Vec<usize>, n: usize, lowest: bool) {
+    sort_active(dice, order, lowest);
+    let to_drop = order.len().saturating_sub(n);
+    for &i in order.iter().take(to_drop) {
+        dice[i].dropped = true;
+    }
+}
+
+/// Drop `n` active dice from the high or low end.
+fn drop(dice: &mut [Die], order: &mut Vec<usize>, n: usize, highest: bool) {
+    sort_active(dice, order, highest);
+    for &i in order.iter().take(n) {
+        dice[i].dropped = true;
+    }
+}
+
+/// Keep only the active dice matching `condition` (with `keep`), or drop the
+/// ones that do.
+fn filter(dice: &mut [Die], condition: Condition, keep: bool) {
+    for die in dice.iter_mut().filter(|d| !d.dropped) {
+        if condition.compare.check(die.value, condition.value) != keep {
+            die.dropped = true;
+        }
+    }
+}
+
+/// Apply a built-in function to already evaluated arguments.
+fn call(func: Func, args: &[Fraction]) -> Result<i64> {
+    match func {
+        Func::Floor => args[0].floor(),
+        Func::Ceil => args[0].ceil(),
+        Func::Round => args[0].round(),
+        Func::Min => Ok(args.iter().map(Fraction::trunc).min().unwrap_or_default()),
+        Func::Max => Ok(args.iter().map(Fraction::trunc).max().unwrap_or_default()),
+        Func::Abs => checked(args[0].trunc().checked_abs()),
+        Func::Clamp => Ok(args[0].trunc().max(args[1].trunc()).min(args[2].trunc())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::roller::{evaluate_with_config, FastRng};
+    use crate::testing::expr;
+    use proptest::prelude::*;
+
+    fn plan_total(input: &str, seed: u64) -> Result<i64> {
+        RollPlan::new(&parse(input).unwrap()).roll(&mut FastRng::with_seed(seed))
+    }
+
+    #[test]
+    fn test_plan_constant() {
+        assert_eq!(plan_total("(2 + 3) * 4 - 6 / 4", 0).unwrap(), 19);
+        assert_eq!(plan_total("-7 // 2", 0).unwrap(), -4);
+        assert_eq!(plan_total("ceil(1/2 + 1/2)", 0).unwrap(), 1);
+        assert_eq!(plan_total("clamp(abs(0 - 20), 1, 10)", 0).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_plan_errors() {
+        assert!(matches!(plan_total("1 / 0", 0), Err(Error::DivisionByZero)));
+        assert!(matches!(plan_total("1d0", 0), Err(Error::InvalidDiceSides(0))));
+        assert!(matches!(plan_total("1d1!", 0), Err(Error::ExplodeLimit(100))));
+        assert!(matches!(plan_total("1d6r<7", 0), Err(Error::RerollLimit(100))));
+    }
+
+    #[test]
+    fn test_plan_is_reusable() {
+        let mut plan = RollPlan::new(&parse("4d6kh3 + 1d4!").unwrap());
+        let mut rng = FastRng::with_seed(3);
+        for _ in 0..1000 {
+            let total = plan.roll(&mut rng).unwrap();
+            assert!(total >= 4);
+        }
+    }
+
+    #[test]
+    fn test_plan_limit_policy() {
+        let config = EvalConfig {
+            max_explosions: 4,
+            on_limit: LimitPolicy::Stop,
+            ..EvalConfig::default()
+        };
+        let mut plan = RollPlan::with_config(&parse("1d1!").unwrap(), &config);
+        assert_eq!(plan.roll(&mut FastRng::with_seed(0)).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_roll_total() {
+        let total = |input: &str, seed| roll_total(&parse(input).unwrap(), &mut FastRng::with_seed(seed));
+        assert_eq!(total("(2 + 3) * 4 - 6 / 4", 0).unwrap(), 19);
+        assert_eq!(total("floor(7 / 2) + round(5 / 2)", 0).unwrap(), 6);
+        assert_eq!(total("min(3, 1, 2) + max(3, 1, 2)", 0).unwrap(), 4);
+        assert_eq!(total("clamp(1d6 * 10, 5, 5)", 0).unwrap(), 5);
+        assert!(matches!(total("1d1!", 0), Err(Error::ExplodeLimit(100))));
+
+        for seed in 0..100 {
+            let sum = total("100d6", seed).unwrap();
+            assert!((100..=600).contains(&sum));
+            let exploded = total("10d10!>=8", seed).unwrap();
+            assert!(exploded >= 10);
+        }
+    }
+
+    /// Roll `expr` with the evaluator, a plan and `roll_total` from the same
+    /// seed and check they agree, on the band read off the total too, as
+    /// `simulate` reads it.
+    fn check_totals_match(expr: &Expr, seed: u64, canonical: bool) -> std::result::Result<(), TestCaseError> {
+        let config = EvalConfig {
+            max_explosions: 20,
+            max_rerolls: 20,
+            order: if canonical { ModifierOrder::Canonical } else { ModifierOrder::Written },
+            ..EvalConfig::default()
+        };
+        let expected = evaluate_with_config(expr, &mut FastRng::with_seed(seed), &config);
+        let planned = RollPlan::with_config(expr, &config).roll(&mut FastRng::with_seed(seed));
+        let walked = roll_total_with_config(expr, &mut FastRng::with_seed(seed), &config);
+        for actual in [planned, walked] {
+            match (&expected, actual) {
+                (Ok(expected), Ok(actual)) => {
+                    prop_assert_eq!(expected.total, actual, "{}", expr);
+                    prop_assert_eq!(expected.band.as_deref(), expr.band(actual), "{}", expr);
+                }
+                (Err(expected), Err(actual)) => prop_assert_eq!(expected.to_string(), actual.to_string()),
+                (expected, actual) => prop_assert!(false, "{}: {:?} vs {:?}", expr, expected, actual),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_totals_match_evaluator_regressions() {
+        // A table inside a rounding function still truncates its expression,
+        // a table inside parentheses still gives the band, and an outer table
+        // leaving the total uncovered clears an inner table's band
+        for input in ["floor((7/2 {<=3: a}) * 2)", "(2d6 {<=6: miss, >=7: hit})", "(0 {0: a}) {<0: b}"] {
+            let expr = parse(input).unwrap();
+            for seed in 0..4 {
+                check_totals_match(&expr, seed, true).unwrap();
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_totals_match_evaluator(expr in expr(), seed in any::<u64>(), canonical in any::<bool>()) {
+            check_totals_match(&expr, seed, canonical)?;
+        }
+    }
+}
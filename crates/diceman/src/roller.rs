@@ -1,14 +1,12 @@
 // ABOUTME: Dice rolling and expression evaluation logic.
 // ABOUTME: Evaluates parsed AST nodes to produce roll results.
 
-use crate::ast::{Compare, Condition, Expr, Modifier, Op, Roll, Sides};
+use crate::ast::{Condition, DynamicRoll, DynamicSides, Expr, Func, Modifier, Op, Roll, Sides};
 use crate::error::{Error, Result};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
 
-/// Maximum number of explosions/rerolls allowed to prevent infinite loops.
-const MAX_EXPLOSIONS: u32 = 100;
-const MAX_REROLLS: u32 = 100;
-
 /// Trait for random number generation, allowing for testing with fixed values.
 pub trait Rng {
     /// Generate a random number in the range [1, max].
@@ -49,6 +47,14 @@ pub struct DieResult {
     pub rolls: Vec<i64>,
     /// Whether this die was dropped/discarded.
     pub dropped: bool,
+    /// Whether exploding or rerolling this die stopped at the configured limit.
+    pub capped: bool,
+    /// The roll this die's value replaced: the roll set aside by a keep-best
+    /// or keep-worse reroll (`rb`, `rw`), or the value raised or lowered by
+    /// `min`/`max`.
+    pub discarded: Option<i64>,
+    /// Whether this is a Savage Worlds wild die (`w`).
+    pub wild: bool,
 }
 
 /// Result of evaluating a dice expression.
@@ -60,6 +66,12 @@ pub struct RollResult {
     pub dice: Vec<DieResult>,
     /// Formatted expression showing the roll.
     pub expression: String,
+    /// Sets of matching dice as `(value, width)`, from every roll in the
+    /// expression that looks for matches (`m`, `mt`).
+    pub sets: Vec<(i64, u32)>,
+    /// The label of the outcome band the total fell in, for an expression
+    /// with an outcome table (`2d6 {<=6: miss, 7..9: weak, >=10: strong}`).
+    pub band: Option<String>,
 }
 
 impl fmt::Display for RollResult {
@@ -68,6 +80,141 @@ impl fmt::Display for RollResult {
     }
 }
 
+/// Resource limits enforced while evaluating an expression.
+///
+/// These bound the work a single evaluation can do, so untrusted input such
+/// as `4294967295d6` fails fast instead of exhausting memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalLimits {
+    /// Maximum number of dice in a single roll, including exploded dice.
+    pub max_dice_per_roll: u32,
+    /// Maximum number of dice across the whole expression.
+    pub max_total_dice: u32,
+    /// Maximum number of sides on a die.
+    pub max_sides: u32,
+}
+
+impl EvalLimits {
+    /// Limits that never trigger.
+    pub fn unlimited() -> Self {
+        Self {
+            max_dice_per_roll: u32::MAX,
+            max_total_dice: u32::MAX,
+            max_sides: u32::MAX,
+        }
+    }
+}
+
+impl Default for EvalLimits {
+    fn default() -> Self {
+        Self {
+            max_dice_per_roll: 10_000,
+            max_total_dice: 100_000,
+            max_sides: 1_000_000,
+        }
+    }
+}
+
+/// What to do when a die reaches the explosion or reroll limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitPolicy {
+    /// Abort the whole evaluation with `Error::ExplodeLimit`/`Error::RerollLimit`.
+    #[default]
+    Error,
+    /// Stop exploding/rerolling the die and keep its current value.
+    Stop,
+    /// Like `Stop`, but mark the die as capped in the result and output.
+    Cap,
+}
+
+/// The order in which a roll's modifiers are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModifierOrder {
+    /// In the order they are written: `4d6kh3!` keeps the highest three
+    /// dice, then explodes the kept ones.
+    #[default]
+    Written,
+    /// Rerolls and per-die minimums/maximums first, then explosions, then
+    /// keep/drop, then success counting, whatever order they are written in. Modifiers of the same
+    /// kind keep their written order.
+    Canonical,
+}
+
+impl ModifierOrder {
+    /// A roll's modifiers in the order they apply under this policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::{Expr, ModifierOrder};
+    ///
+    /// let Expr::Roll(roll) = diceman::parse("4d6kh3!r").unwrap() else { unreachable!() };
+    /// let applied = ModifierOrder::Canonical.apply(&roll.modifiers);
+    /// let names: Vec<String> = applied.iter().map(|m| m.to_string()).collect();
+    /// assert_eq!(names, ["r", "!", "kh3"]);
+    /// ```
+    pub fn apply<'a>(&self, modifiers: &'a [Modifier]) -> Cow<'a, [Modifier]> {
+        match self {
+            ModifierOrder::Canonical if !modifiers.is_sorted_by_key(stage) => {
+                let mut sorted = modifiers.to_vec();
+                sorted.sort_by_key(stage);
+                Cow::Owned(sorted)
+            }
+            _ => Cow::Borrowed(modifiers),
+        }
+    }
+}
+
+/// Where a modifier goes in the canonical order. Success counting, sorting
+/// and matching don't change the dice, so they go last.
+pub(crate) fn stage(modifier: &Modifier) -> u8 {
+    match modifier {
+        Modifier::Reroll { .. } | Modifier::RerollKeep { .. } | Modifier::Min(_) | Modifier::Max(_) => 0,
+        Modifier::Explode { .. } | Modifier::WildDie(_) => 1,
+        Modifier::KeepHighest(_)
+        | Modifier::KeepLowest(_)
+        | Modifier::DropHighest(_)
+        | Modifier::DropLowest(_)
+        | Modifier::KeepWhere(_)
+        | Modifier::DropWhere(_) => 2,
+        Modifier::CountSuccesses(_)
+        | Modifier::CountFailures(_)
+        | Modifier::DoubleSuccesses(_)
+        | Modifier::Sort { .. }
+        | Modifier::Matches { .. } => 3,
+    }
+}
+
+/// Configuration for evaluating an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalConfig {
+    /// Maximum number of times a single die may explode.
+    pub max_explosions: u32,
+    /// Maximum number of times a single die may be rerolled.
+    pub max_rerolls: u32,
+    /// What happens when a die reaches `max_explosions` or `max_rerolls`.
+    pub on_limit: LimitPolicy,
+    /// Resource limits on dice counts and sides.
+    pub limits: EvalLimits,
+    /// The order in which each roll's modifiers are applied.
+    pub order: ModifierOrder,
+    /// Print Fudge dice as Fate glyphs, `4dF[+ - 0 +]`, instead of `-1, 0, 1`.
+    pub fate: bool,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self {
+            max_explosions: 100,
+            max_rerolls: 100,
+            on_limit: LimitPolicy::default(),
+            limits: EvalLimits::default(),
+            order: ModifierOrder::default(),
+            fate: false,
+        }
+    }
+}
+
 /// Evaluate a dice expression with the default RNG.
 pub fn evaluate(expr: &Expr) -> Result<RollResult> {
     evaluate_with_rng(expr, &mut FastRng::new())
@@ -75,12 +222,336 @@ pub fn evaluate(expr: &Expr) -> Result<RollResult> {
 
 /// Evaluate a dice expression with a custom RNG.
 pub fn evaluate_with_rng(expr: &Expr, rng: &mut impl Rng) -> Result<RollResult> {
-    let mut evaluator = Evaluator { rng };
+    evaluate_with_limits(expr, rng, &EvalLimits::default())
+}
+
+/// Evaluate a dice expression with a custom RNG and resource limits.
+pub fn evaluate_with_limits(
+    expr: &Expr,
+    rng: &mut impl Rng,
+    limits: &EvalLimits,
+) -> Result<RollResult> {
+    let config = EvalConfig {
+        limits: *limits,
+        ..EvalConfig::default()
+    };
+    evaluate_with_config(expr, rng, &config)
+}
+
+/// Evaluate a dice expression with a custom RNG and configuration.
+pub fn evaluate_with_config(
+    expr: &Expr,
+    rng: &mut impl Rng,
+    config: &EvalConfig,
+) -> Result<RollResult> {
+    let mut evaluator = Evaluator {
+        rng,
+        config: *config,
+        total_dice: 0,
+    };
     evaluator.evaluate(expr)
 }
 
+/// Roll a single die of the given type.
+pub(crate) fn roll_die(rng: &mut impl Rng, sides: &Sides) -> i64 {
+    match sides {
+        Sides::Number(n) => rng.roll(*n) as i64,
+        Sides::Percent => rng.roll(100) as i64,
+        Sides::Fudge => rng.roll(3) as i64 - 2, // -1, 0, 1
+    }
+}
+
+/// A Fudge die's face as a Fate glyph. Totals past one face, from exploding
+/// or compounding, keep their sign.
+fn fate_glyph(value: i64) -> String {
+    match value {
+        1 => "+".to_string(),
+        0 => "0".to_string(),
+        -1 => "-".to_string(),
+        _ => format!("{:+}", value),
+    }
+}
+
+/// A percentile die's value with its tens and units dice, as `47 (40+7)`.
+/// A roll of 100 is the tens and units dice both showing zero.
+fn percentile_parts(value: i64) -> String {
+    format!("{} ({:02}+{})", value, value % 100 / 10 * 10, value % 10)
+}
+
+/// Check a roll's dice count and sides against the evaluation limits.
+pub(crate) fn check_roll_limits(count: u32, sides: &Sides, limits: &EvalLimits) -> Result<()> {
+    if let Sides::Number(n) = sides {
+        if *n == 0 {
+            return Err(Error::InvalidDiceSides(*n as i64));
+        }
+        if *n > limits.max_sides {
+            return Err(Error::SidesLimit(limits.max_sides));
+        }
+    }
+    if count > limits.max_dice_per_roll {
+        return Err(Error::DiceLimit(limits.max_dice_per_roll));
+    }
+    Ok(())
+}
+
+/// Convert a dynamic roll's evaluated count to a dice count, failing if it is negative.
+pub(crate) fn dynamic_count(count: i64, limits: &EvalLimits) -> Result<u32> {
+    if count < 0 {
+        return Err(Error::InvalidDiceCount(count));
+    }
+    u32::try_from(count).map_err(|_| Error::DiceLimit(limits.max_dice_per_roll))
+}
+
+/// Convert a dynamic roll's evaluated sides to a die type, failing if it has
+/// fewer than one side.
+pub(crate) fn dynamic_sides(sides: i64, limits: &EvalLimits) -> Result<Sides> {
+    if sides < 1 {
+        return Err(Error::InvalidDiceSides(sides));
+    }
+    u32::try_from(sides)
+        .map(Sides::Number)
+        .map_err(|_| Error::SidesLimit(limits.max_sides))
+}
+
+/// Apply a binary operator with overflow and division-by-zero checks.
+pub(crate) fn apply_op(op: Op, left: i64, right: i64) -> Result<i64> {
+    if matches!(op, Op::Div | Op::FloorDiv) && right == 0 {
+        return Err(Error::DivisionByZero);
+    }
+    let value = match op {
+        Op::Add => left.checked_add(right),
+        Op::Sub => left.checked_sub(right),
+        Op::Mul => left.checked_mul(right),
+        Op::Div => left.checked_div(right),
+        Op::FloorDiv => floor_div(left, right),
+    };
+    value.ok_or(Error::Overflow)
+}
+
+/// Integer division rounding toward negative infinity. `right` must be non-zero.
+fn floor_div(left: i64, right: i64) -> Option<i64> {
+    let quotient = left.checked_div(right)?;
+    if left % right != 0 && (left < 0) != (right < 0) {
+        quotient.checked_sub(1)
+    } else {
+        Some(quotient)
+    }
+}
+
+/// An exact rational value, kept in lowest terms with a positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Fraction {
+    num: i64,
+    den: i64,
+}
+
+impl Fraction {
+    pub(crate) fn integer(n: i64) -> Self {
+        Self { num: n, den: 1 }
+    }
+
+    /// Build a fraction in lowest terms. `den` must be non-zero.
+    fn reduced(num: i64, den: i64) -> Result<Self> {
+        let divisor = gcd(num, den);
+        let (num, den) = (num / divisor, den / divisor);
+        if den < 0 {
+            Ok(Self {
+                num: num.checked_neg().ok_or(Error::Overflow)?,
+                den: den.checked_neg().ok_or(Error::Overflow)?,
+            })
+        } else {
+            Ok(Self { num, den })
+        }
+    }
+
+    pub(crate) fn add(self, other: Self) -> Result<Self> {
+        let num = checked_cross(self.num, other.den, other.num, self.den, i64::checked_add)?;
+        Self::reduced(num, checked(self.den.checked_mul(other.den))?)
+    }
+
+    pub(crate) fn sub(self, other: Self) -> Result<Self> {
+        let num = checked_cross(self.num, other.den, other.num, self.den, i64::checked_sub)?;
+        Self::reduced(num, checked(self.den.checked_mul(other.den))?)
+    }
+
+    pub(crate) fn mul(self, other: Self) -> Result<Self> {
+        Self::reduced(
+            checked(self.num.checked_mul(other.num))?,
+            checked(self.den.checked_mul(other.den))?,
+        )
+    }
+
+    pub(crate) fn div(self, other: Self) -> Result<Self> {
+        if other.num == 0 {
+            return Err(Error::DivisionByZero);
+        }
+        Self::reduced(
+            checked(self.num.checked_mul(other.den))?,
+            checked(self.den.checked_mul(other.num))?,
+        )
+    }
+
+    /// Apply a binary operator exactly. Floor division still yields an integer.
+    pub(crate) fn apply(self, op: Op, other: Self) -> Result<Self> {
+        match op {
+            Op::Add => self.add(other),
+            Op::Sub => self.sub(other),
+            Op::Mul => self.mul(other),
+            Op::Div => self.div(other),
+            Op::FloorDiv => Ok(Fraction::integer(self.div(other)?.floor()?)),
+        }
+    }
+
+    /// The value as an integer, if it is one.
+    pub(crate) fn to_integer(self) -> Option<i64> {
+        (self.den == 1).then_some(self.num)
+    }
+
+    pub(crate) fn trunc(&self) -> i64 {
+        self.num / self.den
+    }
+
+    pub(crate) fn floor(&self) -> Result<i64> {
+        Ok(self.num.div_euclid(self.den))
+    }
+
+    pub(crate) fn ceil(&self) -> Result<i64> {
+        let negated = checked(self.num.checked_neg())?;
+        checked(negated.div_euclid(self.den).checked_neg())
+    }
+
+    /// Round to nearest, with halves rounding away from zero.
+    pub(crate) fn round(&self) -> Result<i64> {
+        let twice = checked(self.num.checked_abs().and_then(|n| n.checked_mul(2)))?;
+        let double_den = checked(self.den.checked_mul(2))?;
+        let magnitude = checked(twice.checked_add(self.den))? / double_den;
+        if self.num < 0 {
+            Ok(-magnitude)
+        } else {
+            Ok(magnitude)
+        }
+    }
+}
+
+/// How a success-counting roll scores each die: one for a success, one more
+/// if it is also a double, and minus one for a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Scoring {
+    pub(crate) success: Condition,
+    pub(crate) double: Option<Condition>,
+    pub(crate) failure: Option<Condition>,
+}
+
+impl Scoring {
+    /// The scoring set by a roll's last success condition and the failure
+    /// and double conditions that follow it, or `None` if the roll sums.
+    pub(crate) fn from_modifiers(modifiers: &[Modifier]) -> Option<Self> {
+        let mut scoring: Option<Scoring> = None;
+        for modifier in modifiers {
+            match (modifier, scoring.as_mut()) {
+                (Modifier::CountSuccesses(success), _) => {
+                    scoring = Some(Scoring {
+                        success: *success,
+                        double: None,
+                        failure: None,
+                    });
+                }
+                (Modifier::CountFailures(failure), Some(scoring)) => scoring.failure = Some(*failure),
+                (Modifier::DoubleSuccesses(double), Some(scoring)) => scoring.double = Some(*double),
+                _ => {}
+            }
+        }
+        scoring
+    }
+
+    pub(crate) fn is_success(&self, value: i64) -> bool {
+        self.success.compare.check(value, self.success.value)
+    }
+
+    pub(crate) fn is_double(&self, value: i64) -> bool {
+        self.is_success(value) && self.double.is_some_and(|c| c.compare.check(value, c.value))
+    }
+
+    pub(crate) fn is_failure(&self, value: i64) -> bool {
+        self.failure.is_some_and(|c| c.compare.check(value, c.value))
+    }
+
+    /// This die's contribution to the net successes. A die can be both a
+    /// success and a failure, in which case they cancel out.
+    pub(crate) fn score(&self, value: i64) -> i64 {
+        self.is_success(value) as i64 + self.is_double(value) as i64 - self.is_failure(value) as i64
+    }
+}
+
+/// The last match modifier on a roll, as `(width, count_sets)`.
+pub(crate) fn matching(modifiers: &[Modifier]) -> Option<(u32, bool)> {
+    modifiers.iter().rev().find_map(|m| match m {
+        Modifier::Matches { width, count_sets } => Some((*width, *count_sets)),
+        _ => None,
+    })
+}
+
+/// The set width of a roll that totals its sets instead of its dice.
+pub(crate) fn counted_sets(modifiers: &[Modifier]) -> Option<u32> {
+    matching(modifiers).and_then(|(width, count_sets)| count_sets.then_some(width))
+}
+
+/// Group values into sets of at least `width` equal values, as
+/// `(value, width)` pairs, widest first and then highest first.
+pub(crate) fn find_sets(values: impl IntoIterator<Item = i64>, width: u32) -> Vec<(i64, u32)> {
+    let mut counts: BTreeMap<i64, u32> = BTreeMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    let mut sets: Vec<(i64, u32)> = counts.into_iter().filter(|&(_, n)| n >= width.max(1)).collect();
+    sets.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
+    sets
+}
+
+/// Convert a checked arithmetic result into an overflow error.
+pub(crate) fn checked(value: Option<i64>) -> Result<i64> {
+    value.ok_or(Error::Overflow)
+}
+
+/// Compute `a * b (op) c * d` with overflow checks.
+fn checked_cross(
+    a: i64,
+    b: i64,
+    c: i64,
+    d: i64,
+    op: fn(i64, i64) -> Option<i64>,
+) -> Result<i64> {
+    let left = checked(a.checked_mul(b))?;
+    let right = checked(c.checked_mul(d))?;
+    checked(op(left, right))
+}
+
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            let decimal = format!("{:.2}", self.num as f64 / self.den as f64);
+            write!(f, "{}", decimal.trim_end_matches('0').trim_end_matches('.'))
+        }
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    // The result divides a non-zero denominator, so it only exceeds i64::MAX
+    // when both inputs are i64::MIN; dividing by 1 is then still correct.
+    i64::try_from(a).unwrap_or(1)
+}
+
 struct Evaluator<'a, R: Rng> {
     rng: &'a mut R,
+    config: EvalConfig,
+    /// Number of dice rolled so far, checked against `max_total_dice`.
+    total_dice: u32,
 }
 
 impl<R: Rng> Evaluator<'_, R> {
@@ -90,28 +561,22 @@ impl<R: Rng> Evaluator<'_, R> {
                 total: *n,
                 dice: vec![],
                 expression: n.to_string(),
+                sets: vec![],
+                band: None,
             }),
             Expr::Roll(roll) => self.evaluate_roll(roll),
             Expr::BinOp { op, left, right } => {
                 let left_result = self.evaluate(left)?;
                 let right_result = self.evaluate(right)?;
-                let total = match op {
-                    Op::Add => left_result.total + right_result.total,
-                    Op::Sub => left_result.total - right_result.total,
-                    Op::Mul => left_result.total * right_result.total,
-                    Op::Div => {
-                        if right_result.total == 0 {
-                            return Err(Error::DivisionByZero);
-                        }
-                        left_result.total / right_result.total
-                    }
-                };
+                let total = apply_op(*op, left_result.total, right_result.total)?;
                 let expression =
                     format!("{} {} {} = {}", left_result.expression, op, right_result.expression, total);
                 Ok(RollResult {
                     total,
                     dice: vec![],
                     expression,
+                    sets: [left_result.sets, right_result.sets].concat(),
+                    band: None,
                 })
             }
             Expr::Group(inner) => {
@@ -120,12 +585,167 @@ impl<R: Rng> Evaluator<'_, R> {
                     total: result.total,
                     dice: result.dice,
                     expression: format!("({})", result.expression),
+                    sets: result.sets,
+                    band: result.band,
                 })
             }
+            Expr::Call { func, args } => self.evaluate_call(*func, args),
+            Expr::Var(name) => Err(Error::UnknownVariable(name.clone())),
+            Expr::DynamicRoll(roll) => self.evaluate_dynamic_roll(roll),
+            Expr::Bands { expr: inner, .. } => {
+                let mut result = self.evaluate(inner)?;
+                // The outermost table decides the band, even when it leaves the total uncovered
+                result.band = expr.band(result.total).map(str::to_string);
+                if let Some(label) = &result.band {
+                    result.expression = format!("{}, {}", result.expression, label);
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    /// Evaluate a dynamic roll's count, then its sides, then roll it like a plain roll.
+    fn evaluate_dynamic_roll(&mut self, dynamic: &DynamicRoll) -> Result<RollResult> {
+        let count = self.evaluate(&dynamic.count)?;
+        let (sides, sides_expression, sides_sets) = match &dynamic.sides {
+            DynamicSides::Fixed(sides) => (*sides, sides.to_string(), vec![]),
+            DynamicSides::Expr(expr) => {
+                let result = self.evaluate(expr)?;
+                let sides = dynamic_sides(result.total, &self.config.limits)?;
+                (sides, result.expression, result.sets)
+            }
+        };
+        let roll = Roll {
+            count: dynamic_count(count.total, &self.config.limits)?,
+            sides,
+            modifiers: dynamic.modifiers.clone(),
+        };
+        let result = self.evaluate_roll(&roll)?;
+        // Bound variables leave plain numbers, which the roll already shows
+        let literal = |expr: &Expr| matches!(expr, Expr::Number(_));
+        let expression = match &dynamic.sides {
+            DynamicSides::Expr(sides) if !literal(sides) => {
+                format!("{}d{} -> {}", count.expression, sides_expression, result.expression)
+            }
+            _ if !literal(&dynamic.count) => {
+                format!("{}d{} -> {}", count.expression, sides_expression, result.expression)
+            }
+            _ => result.expression,
+        };
+        Ok(RollResult {
+            total: result.total,
+            dice: result.dice,
+            expression,
+            sets: [count.sets, sides_sets, result.sets].concat(),
+            band: None,
+        })
+    }
+
+    fn evaluate_call(&mut self, func: Func, args: &[Expr]) -> Result<RollResult> {
+        match func {
+            Func::Floor => self.evaluate_rounded(func, &args[0], Fraction::floor),
+            Func::Ceil => self.evaluate_rounded(func, &args[0], Fraction::ceil),
+            Func::Round => self.evaluate_rounded(func, &args[0], Fraction::round),
+            Func::Min => self.evaluate_combined(func, args, |v| Ok(v.iter().copied().min().unwrap_or_default())),
+            Func::Max => self.evaluate_combined(func, args, |v| Ok(v.iter().copied().max().unwrap_or_default())),
+            Func::Abs => self.evaluate_combined(func, args, |v| checked(v[0].checked_abs())),
+            // Written as max-then-min so an inverted range can't panic
+            Func::Clamp => self.evaluate_combined(func, args, |v| Ok(v[0].max(v[1]).min(v[2]))),
+        }
+    }
+
+    /// Evaluate a rounding function, applying `round` to the exact value of `arg`.
+    fn evaluate_rounded(
+        &mut self,
+        func: Func,
+        arg: &Expr,
+        round: fn(&Fraction) -> Result<i64>,
+    ) -> Result<RollResult> {
+        let (result, value) = self.evaluate_exact(arg)?;
+        let total = round(&value)?;
+        Ok(RollResult {
+            total,
+            dice: result.dice,
+            expression: format!("{}({}) = {}", func, result.expression, total),
+            sets: result.sets,
+            band: None,
+        })
+    }
+
+    /// Evaluate a function over the integer totals of all its arguments.
+    fn evaluate_combined(
+        &mut self,
+        func: Func,
+        args: &[Expr],
+        combine: fn(&[i64]) -> Result<i64>,
+    ) -> Result<RollResult> {
+        let results = args
+            .iter()
+            .map(|arg| self.evaluate(arg))
+            .collect::<Result<Vec<_>>>()?;
+        let values: Vec<i64> = results.iter().map(|r| r.total).collect();
+        let total = combine(&values)?;
+        let expression = format!(
+            "{}({}) = {}",
+            func,
+            results.iter().map(|r| r.expression.as_str()).collect::<Vec<_>>().join(", "),
+            total
+        );
+        let sets = results.iter().flat_map(|r| r.sets.iter().copied()).collect();
+        Ok(RollResult {
+            total,
+            dice: results.into_iter().flat_map(|r| r.dice).collect(),
+            expression,
+            sets,
+            band: None,
+        })
+    }
+
+    /// Evaluate an expression without truncating division, for use inside
+    /// rounding functions. Rolls and other leaves are evaluated normally.
+    fn evaluate_exact(&mut self, expr: &Expr) -> Result<(RollResult, Fraction)> {
+        match expr {
+            Expr::BinOp { op, left, right } => {
+                let (left_result, left_value) = self.evaluate_exact(left)?;
+                let (right_result, right_value) = self.evaluate_exact(right)?;
+                let value = left_value.apply(*op, right_value)?;
+                let expression =
+                    format!("{} {} {} = {}", left_result.expression, op, right_result.expression, value);
+                Ok((
+                    RollResult {
+                        total: value.trunc(),
+                        dice: vec![],
+                        expression,
+                        sets: [left_result.sets, right_result.sets].concat(),
+                        band: None,
+                    },
+                    value,
+                ))
+            }
+            Expr::Group(inner) => {
+                let (result, value) = self.evaluate_exact(inner)?;
+                Ok((
+                    RollResult {
+                        total: result.total,
+                        dice: result.dice,
+                        expression: format!("({})", result.expression),
+                        sets: result.sets,
+                        band: result.band,
+                    },
+                    value,
+                ))
+            }
+            _ => {
+                let result = self.evaluate(expr)?;
+                let value = Fraction::integer(result.total);
+                Ok((result, value))
+            }
         }
     }
 
     fn evaluate_roll(&mut self, roll: &Roll) -> Result<RollResult> {
+        self.check_roll(roll)?;
+
         // Roll the dice
         let mut dice: Vec<DieResult> = (0..roll.count)
             .map(|_| {
@@ -134,17 +754,26 @@ impl<R: Rng> Evaluator<'_, R> {
                     value,
                     rolls: vec![value],
                     dropped: false,
+                    capped: false,
+                    discarded: None,
+                    wild: false,
                 }
             })
             .collect();
 
-        // Apply modifiers in order: reroll -> explode -> keep/drop -> count
-        let mut success_condition: Option<&Condition> = None;
-        for modifier in &roll.modifiers {
+        // Apply modifiers in the configured order: as written by default, or
+        // reroll -> explode -> keep/drop -> count under ModifierOrder::Canonical
+        for modifier in self.config.order.apply(&roll.modifiers).iter() {
             match modifier {
                 Modifier::Reroll { once, condition } => {
                     self.apply_reroll(&mut dice, &roll.sides, *once, condition.as_ref())?;
                 }
+                Modifier::RerollKeep { best, condition } => {
+                    self.apply_reroll_keep(&mut dice, &roll.sides, *best, condition.as_ref());
+                }
+                Modifier::WildDie(wild) => self.apply_wild_die(&mut dice, &roll.sides, *wild)?,
+                Modifier::Min(n) => self.apply_clamp(&mut dice, |v| v.max(*n)),
+                Modifier::Max(n) => self.apply_clamp(&mut dice, |v| v.min(*n)),
                 Modifier::Explode { compounding, penetrating, condition } => {
                     self.apply_explode(&mut dice, &roll.sides, *compounding, *penetrating, condition.as_ref())?;
                 }
@@ -152,38 +781,78 @@ impl<R: Rng> Evaluator<'_, R> {
                 Modifier::KeepLowest(n) => self.apply_keep_lowest(&mut dice, *n),
                 Modifier::DropHighest(n) => self.apply_drop_highest(&mut dice, *n),
                 Modifier::DropLowest(n) => self.apply_drop_lowest(&mut dice, *n),
-                Modifier::CountSuccesses(condition) => {
-                    success_condition = Some(condition);
-                }
+                Modifier::KeepWhere(condition) => self.apply_filter(&mut dice, condition, true),
+                Modifier::DropWhere(condition) => self.apply_filter(&mut dice, condition, false),
+                // Success counting changes how the total is computed below
+                Modifier::CountSuccesses(_) | Modifier::CountFailures(_) | Modifier::DoubleSuccesses(_) => {}
+                // Sorting and matching happen once every die is settled
+                Modifier::Sort { .. } | Modifier::Matches { .. } => {}
             }
         }
 
-        // Calculate total: count successes or sum values
-        let total: i64 = if let Some(condition) = success_condition {
+        // Sort last so sorting never changes which dice are kept
+        let sort = roll.modifiers.iter().rev().find_map(|m| match m {
+            Modifier::Sort { descending } => Some(*descending),
+            _ => None,
+        });
+        match sort {
+            Some(false) => dice.sort_by_key(|d| d.value),
+            Some(true) => dice.sort_by_key(|d| std::cmp::Reverse(d.value)),
+            None => {}
+        }
+
+        let matching = matching(&roll.modifiers);
+        let sets = match matching {
+            Some((width, _)) => find_sets(dice.iter().filter(|d| !d.dropped).map(|d| d.value), width),
+            None => vec![],
+        };
+        let count_sets = matches!(matching, Some((_, true)));
+
+        // Calculate total: count sets, count net successes, or sum values
+        let scoring = Scoring::from_modifiers(&roll.modifiers).filter(|_| !count_sets);
+        let total: i64 = if count_sets {
+            sets.len() as i64
+        } else if let Some(scoring) = &scoring {
             dice.iter()
                 .filter(|d| !d.dropped)
-                .filter(|d| condition.compare.check(d.value, condition.value))
-                .count() as i64
+                .map(|d| scoring.score(d.value))
+                .sum()
         } else {
-            dice.iter().filter(|d| !d.dropped).map(|d| d.value).sum()
+            dice.iter()
+                .filter(|d| !d.dropped)
+                .try_fold(0i64, |sum, d| sum.checked_add(d.value))
+                .ok_or(Error::Overflow)?
         };
 
         // Format the expression
-        let expression = self.format_roll(roll, &dice, total, success_condition);
+        let expression = self.format_roll(roll, &dice, total, scoring.as_ref(), &sets, count_sets);
 
         Ok(RollResult {
             total,
             dice,
             expression,
+            sets,
+            band: None,
         })
     }
 
-    fn roll_die(&mut self, sides: &Sides) -> i64 {
-        match sides {
-            Sides::Number(n) => self.rng.roll(*n) as i64,
-            Sides::Percent => self.rng.roll(100) as i64,
-            Sides::Fudge => self.rng.roll(3) as i64 - 2, // -1, 0, 1
+    /// Check a roll against the evaluation limits before any dice are rolled.
+    fn check_roll(&mut self, roll: &Roll) -> Result<()> {
+        check_roll_limits(roll.count, &roll.sides, &self.config.limits)?;
+        self.count_dice(roll.count)
+    }
+
+    /// Record newly rolled dice against the expression-wide limit.
+    fn count_dice(&mut self, n: u32) -> Result<()> {
+        self.total_dice = self.total_dice.saturating_add(n);
+        if self.total_dice > self.config.limits.max_total_dice {
+            return Err(Error::TotalDiceLimit(self.config.limits.max_total_dice));
         }
+        Ok(())
+    }
+
+    fn roll_die(&mut self, sides: &Sides) -> i64 {
+        roll_die(self.rng, sides)
     }
 
     fn apply_reroll(
@@ -193,10 +862,7 @@ impl<R: Rng> Evaluator<'_, R> {
         once: bool,
         condition: Option<&Condition>,
     ) -> Result<()> {
-        let default_condition = Condition {
-            compare: Compare::Equal,
-            value: 1,
-        };
+        let default_condition = Condition::reroll_default();
         let condition = condition.unwrap_or(&default_condition);
 
         for die in dice.iter_mut() {
@@ -206,8 +872,15 @@ impl<R: Rng> Evaluator<'_, R> {
 
             let mut reroll_count = 0;
             while condition.compare.check(die.value, condition.value) {
-                if reroll_count >= MAX_REROLLS {
-                    return Err(Error::RerollLimit(MAX_REROLLS));
+                if reroll_count >= self.config.max_rerolls {
+                    match self.config.on_limit {
+                        LimitPolicy::Error => return Err(Error::RerollLimit(self.config.max_rerolls)),
+                        LimitPolicy::Stop => break,
+                        LimitPolicy::Cap => {
+                            die.capped = true;
+                            break;
+                        }
+                    }
                 }
                 let new_value = self.roll_die(sides);
                 die.rolls.push(new_value);
@@ -223,6 +896,72 @@ impl<R: Rng> Evaluator<'_, R> {
         Ok(())
     }
 
+    /// Reroll matching dice once, keeping the better or worse of the two rolls.
+    fn apply_reroll_keep(
+        &mut self,
+        dice: &mut [DieResult],
+        sides: &Sides,
+        best: bool,
+        condition: Option<&Condition>,
+    ) {
+        let condition = condition.copied().unwrap_or_else(Condition::reroll_default);
+
+        for die in dice.iter_mut().filter(|d| !d.dropped) {
+            if !condition.compare.check(die.value, condition.value) {
+                continue;
+            }
+            let new_value = self.roll_die(sides);
+            die.rolls.push(new_value);
+            let (kept, discarded) = if (new_value > die.value) == best {
+                (new_value, die.value)
+            } else {
+                (die.value, new_value)
+            };
+            die.value = kept;
+            die.discarded = Some(discarded);
+        }
+    }
+
+    /// Raise or lower each active die with `clamp`, keeping the original value
+    /// for the output.
+    fn apply_clamp(&mut self, dice: &mut [DieResult], clamp: impl Fn(i64) -> i64) {
+        for die in dice.iter_mut().filter(|d| !d.dropped) {
+            let clamped = clamp(die.value);
+            if clamped != die.value {
+                die.discarded = Some(die.value);
+                die.value = clamped;
+            }
+        }
+    }
+
+    /// Ace every active die, roll an aced wild die with `wild` sides, and keep
+    /// the highest dice, as many as were active before.
+    fn apply_wild_die(&mut self, dice: &mut Vec<DieResult>, sides: &Sides, wild: u32) -> Result<()> {
+        let kept = dice.iter().filter(|d| !d.dropped).count() as u32;
+        self.apply_explode(dice, sides, true, false, None)?;
+
+        let wild_sides = Sides::Number(wild);
+        check_roll_limits(1, &wild_sides, &self.config.limits)?;
+        if dice.len() >= self.config.limits.max_dice_per_roll as usize {
+            return Err(Error::DiceLimit(self.config.limits.max_dice_per_roll));
+        }
+        self.count_dice(1)?;
+        let value = self.roll_die(&wild_sides);
+        let mut wild_die = vec![DieResult {
+            value,
+            rolls: vec![value],
+            dropped: false,
+            capped: false,
+            discarded: None,
+            wild: true,
+        }];
+        self.apply_explode(&mut wild_die, &wild_sides, true, false, None)?;
+        dice.extend(wild_die);
+
+        self.apply_keep_highest(dice, kept);
+        Ok(())
+    }
+
     fn apply_explode(
         &mut self,
         dice: &mut Vec<DieResult>,
@@ -231,13 +970,13 @@ impl<R: Rng> Evaluator<'_, R> {
         penetrating: bool,
         condition: Option<&Condition>,
     ) -> Result<()> {
-        let max_val = sides.count() as i64;
-        let default_condition = Condition {
-            compare: Compare::Equal,
-            value: max_val,
-        };
+        let default_condition = Condition::explode_default(sides);
         let condition = condition.unwrap_or(&default_condition);
 
+        // Explosions already in each die's chain; a standard explosion's new
+        // die continues the chain of the die that produced it.
+        let mut chain: Vec<u32> = vec![0; dice.len()];
+
         let mut i = 0;
         while i < dice.len() {
             if dice[i].dropped {
@@ -246,33 +985,50 @@ impl<R: Rng> Evaluator<'_, R> {
             }
 
             let mut current_value = dice[i].value;
-            let mut explode_count = 0;
+            let mut explode_count = chain[i];
 
             while condition.compare.check(current_value, condition.value) {
-                if explode_count >= MAX_EXPLOSIONS {
-                    return Err(Error::ExplodeLimit(MAX_EXPLOSIONS));
+                if explode_count >= self.config.max_explosions {
+                    match self.config.on_limit {
+                        LimitPolicy::Error => {
+                            return Err(Error::ExplodeLimit(self.config.max_explosions))
+                        }
+                        LimitPolicy::Stop => break,
+                        LimitPolicy::Cap => {
+                            dice[i].capped = true;
+                            break;
+                        }
+                    }
                 }
 
                 let new_value = self.roll_die(sides);
+                explode_count += 1;
 
                 // Penetrating: subtract 1 from added value (not from check)
                 let added_value = if penetrating { new_value - 1 } else { new_value };
 
                 if compounding {
                     // Compounding: add to same die
-                    dice[i].value += added_value;
+                    dice[i].value = checked(dice[i].value.checked_add(added_value))?;
                     dice[i].rolls.push(new_value);
                 } else {
                     // Standard: create new die
+                    if dice.len() >= self.config.limits.max_dice_per_roll as usize {
+                        return Err(Error::DiceLimit(self.config.limits.max_dice_per_roll));
+                    }
+                    self.count_dice(1)?;
                     dice.push(DieResult {
                         value: added_value,
                         rolls: vec![new_value],
                         dropped: false,
+                        capped: false,
+                        discarded: None,
+                        wild: false,
                     });
+                    chain.push(explode_count);
                 }
 
                 current_value = new_value;
-                explode_count += 1;
 
                 // For non-compounding, break so the new die gets checked in the outer loop
                 if !compounding {
@@ -361,113 +1117,96 @@ impl<R: Rng> Evaluator<'_, R> {
         }
     }
 
+    /// Keep only the active dice matching `condition` (with `keep`), or drop
+    /// the ones that do.
+    fn apply_filter(&mut self, dice: &mut [DieResult], condition: &Condition, keep: bool) {
+        for die in dice.iter_mut().filter(|d| !d.dropped) {
+            if condition.compare.check(die.value, condition.value) != keep {
+                die.dropped = true;
+            }
+        }
+    }
+
     fn format_roll(
         &self,
         roll: &Roll,
         dice: &[DieResult],
         total: i64,
-        success_condition: Option<&Condition>,
+        scoring: Option<&Scoring>,
+        sets: &[(i64, u32)],
+        count_sets: bool,
     ) -> String {
-        let sides_str = match roll.sides {
-            Sides::Number(n) => n.to_string(),
-            Sides::Percent => "%".to_string(),
-            Sides::Fudge => "F".to_string(),
+        let glyphs = self.config.fate && roll.sides == Sides::Fudge;
+        let show = |die: &DieResult, value: i64| match roll.sides {
+            Sides::Fudge if glyphs => fate_glyph(value),
+            // Only a die's single roll is split: an exploded or min/max-adjusted
+            // value isn't what one pair of tens and units dice showed
+            Sides::Percent if die.rolls == [value] && (1..=100).contains(&value) => percentile_parts(value),
+            _ => value.to_string(),
         };
-
-        let modifiers_str: String = roll
-            .modifiers
-            .iter()
-            .map(|m| match m {
-                Modifier::KeepHighest(n) => format!("kh{}", n),
-                Modifier::KeepLowest(n) => format!("kl{}", n),
-                Modifier::DropHighest(n) => format!("dh{}", n),
-                Modifier::DropLowest(n) => format!("dl{}", n),
-                Modifier::Explode { compounding, penetrating, condition } => {
-                    let mut s = "!".to_string();
-                    if *compounding {
-                        s.push('!');
-                    }
-                    if *penetrating {
-                        s.push('p');
-                    }
-                    if let Some(c) = condition {
-                        s.push_str(&format!("{}{}", c.compare, c.value));
-                    }
-                    s
-                }
-                Modifier::Reroll { once, condition } => {
-                    let mut s = "r".to_string();
-                    if *once {
-                        s.push('o');
-                    }
-                    if let Some(c) = condition {
-                        s.push_str(&format!("{}{}", c.compare, c.value));
-                    }
-                    s
-                }
-                Modifier::CountSuccesses(c) => {
-                    format!("{}{}", c.compare, c.value)
-                }
-            })
-            .collect();
-
         // Format dice, marking successes if counting
         let dice_str: String = dice
             .iter()
             .map(|d| {
-                if d.dropped {
-                    format!("({})", d.value)
-                } else if let Some(condition) = success_condition {
-                    if condition.compare.check(d.value, condition.value) {
-                        format!("{}*", d.value) // Mark successes with *
+                // Mark dice that hit the explode/reroll limit with ^
+                let mut value = if d.capped {
+                    format!("{}^", show(d, d.value))
+                } else {
+                    show(d, d.value)
+                };
+                // Mark the wild die with w
+                if d.wild {
+                    value.push('w');
+                }
+                if let (false, Some(scoring)) = (d.dropped, scoring) {
+                    // Mark successes with * (** if doubled) and failures with x
+                    let success = if scoring.is_double(d.value) {
+                        "**"
+                    } else if scoring.is_success(d.value) {
+                        "*"
                     } else {
-                        d.value.to_string()
-                    }
+                        ""
+                    };
+                    let failure = if scoring.is_failure(d.value) { "x" } else { "" };
+                    value = format!("{}{}{}", value, success, failure);
+                }
+                // Show the value a keep-best/worse reroll or min/max replaced, as in 15/(3)
+                if let Some(discarded) = d.discarded {
+                    value = format!("{}/({})", value, show(d, discarded));
+                }
+                if d.dropped {
+                    format!("({})", value)
                 } else {
-                    d.value.to_string()
+                    value
                 }
             })
             .collect::<Vec<_>>()
-            .join(", ");
+            .join(if glyphs { " " } else { ", " });
 
-        if success_condition.is_some() {
+        let mut formatted = if count_sets {
+            let set_word = if total == 1 { "set" } else { "sets" };
+            format!("{}[{}] = {} {}", roll, dice_str, total, set_word)
+        } else if scoring.is_some() {
             let success_word = if total == 1 { "success" } else { "successes" };
-            format!(
-                "{}d{}{}[{}] = {} {}",
-                roll.count, sides_str, modifiers_str, dice_str, total, success_word
-            )
+            format!("{}[{}] = {} {}", roll, dice_str, total, success_word)
         } else {
-            format!(
-                "{}d{}{}[{}] = {}",
-                roll.count, sides_str, modifiers_str, dice_str, total
-            )
+            format!("{}[{}] = {}", roll, dice_str, total)
+        };
+
+        // List sets as width x value, the way One-Roll Engine games write them
+        if !sets.is_empty() {
+            let sets: Vec<String> = sets.iter().map(|(value, width)| format!("{}x{}", width, value)).collect();
+            formatted.push_str(&format!(" ({})", sets.join(", ")));
         }
+        formatted
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    /// A deterministic RNG for testing.
-    struct TestRng {
-        values: Vec<u32>,
-        index: usize,
-    }
-
-    impl TestRng {
-        fn new(values: Vec<u32>) -> Self {
-            Self { values, index: 0 }
-        }
-    }
-
-    impl Rng for TestRng {
-        fn roll(&mut self, _max: u32) -> u32 {
-            let value = self.values[self.index % self.values.len()];
-            self.index += 1;
-            value
-        }
-    }
+    use crate::ast::Compare;
+    use crate::testing::TestRng;
 
     #[test]
     fn test_evaluate_number() {
@@ -484,7 +1223,7 @@ mod tests {
             modifiers: vec![],
         };
         let expr = Expr::Roll(roll);
-        let mut rng = TestRng::new(vec![3, 4]);
+        let mut rng = TestRng::new(&[3, 4]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 7);
     }
@@ -497,7 +1236,7 @@ mod tests {
             modifiers: vec![Modifier::KeepHighest(3)],
         };
         let expr = Expr::Roll(roll);
-        let mut rng = TestRng::new(vec![1, 5, 3, 6]); // Should keep 5, 3, 6 = 14
+        let mut rng = TestRng::new(&[1, 5, 3, 6]); // Should keep 5, 3, 6 = 14
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 14);
     }
@@ -513,7 +1252,7 @@ mod tests {
             })),
             right: Box::new(Expr::Number(5)),
         };
-        let mut rng = TestRng::new(vec![3, 4]);
+        let mut rng = TestRng::new(&[3, 4]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 12); // 3 + 4 + 5
     }
@@ -526,9 +1265,63 @@ mod tests {
             modifiers: vec![],
         };
         let expr = Expr::Roll(roll);
-        let mut rng = TestRng::new(vec![1, 2, 3, 2]); // -1, 0, 1, 0 = 0
+        let mut rng = TestRng::new(&[1, 2, 3, 2]); // -1, 0, 1, 0 = 0
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 0);
+        assert_eq!(result.expression, "4dF[-1, 0, 1, 0] = 0");
+    }
+
+    #[test]
+    fn test_evaluate_bands() {
+        let expr = crate::parser::parse("2d6 + 1 {<=6: miss, 7..9: weak, >=10: strong}").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[3, 4])).unwrap();
+        assert_eq!(result.band.as_deref(), Some("weak"));
+        assert_eq!(result.expression, "2d6[3, 4] = 7 + 1 = 8, weak");
+
+        // A total no band covers has no band
+        let expr = crate::parser::parse("1d6 {1: fumble}").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[4])).unwrap();
+        assert_eq!(result.band, None);
+        assert_eq!(result.expression, "1d6[4] = 4");
+
+        // A table inside parentheses still gives the result its band
+        let expr = crate::parser::parse("(2d6 {<=6: miss, >=7: hit})").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[3, 4])).unwrap();
+        assert_eq!(result.band.as_deref(), Some("hit"));
+    }
+
+    #[test]
+    fn test_evaluate_fate_glyphs() {
+        let fate = EvalConfig {
+            fate: true,
+            ..EvalConfig::default()
+        };
+        let expr = crate::parser::parse("4dF + 2").unwrap();
+        let result = evaluate_with_config(&expr, &mut TestRng::new(&[3, 1, 2, 3]), &fate).unwrap();
+        assert_eq!(result.expression, "4dF[+ - 0 +] = 1 + 2 = 3");
+
+        // Only Fudge dice take glyphs
+        let expr = crate::parser::parse("2dFkh1 + 1d6").unwrap();
+        let result = evaluate_with_config(&expr, &mut TestRng::new(&[1, 3, 1]), &fate).unwrap();
+        assert_eq!(result.expression, "2dFkh1[(-) +] = 1 + 1d6[1] = 1 = 2");
+    }
+
+    #[test]
+    fn test_evaluate_percentile_parts() {
+        let expr = crate::parser::parse("2d%").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[47, 100])).unwrap();
+        assert_eq!(result.expression, "2d%[47 (40+7), 100 (00+0)] = 147");
+
+        let result = evaluate_with_rng(&crate::parser::parse("d%").unwrap(), &mut TestRng::new(&[5])).unwrap();
+        assert_eq!(result.expression, "1d%[5 (00+5)] = 5");
+
+        // Only a die's single roll is split, not a compounded, rerolled or adjusted value
+        let result = evaluate_with_rng(&crate::parser::parse("1d%!!").unwrap(), &mut TestRng::new(&[100, 5])).unwrap();
+        assert_eq!(result.expression, "1d%!![105] = 105");
+        let result = evaluate_with_rng(&crate::parser::parse("1d%r<10").unwrap(), &mut TestRng::new(&[3, 47])).unwrap();
+        assert_eq!(result.expression, "1d%r<10[47] = 47");
+        let result = evaluate_with_rng(&crate::parser::parse("1d%min50").unwrap(), &mut TestRng::new(&[12])).unwrap();
+        assert_eq!(result.expression, "1d%min50[50/(12 (10+2))] = 50");
     }
 
     #[test]
@@ -539,7 +1332,7 @@ mod tests {
             modifiers: vec![Modifier::DropLowest(1)],
         };
         let expr = Expr::Roll(roll);
-        let mut rng = TestRng::new(vec![1, 5, 3, 6]); // Drop 1, keep 5+3+6 = 14
+        let mut rng = TestRng::new(&[1, 5, 3, 6]); // Drop 1, keep 5+3+6 = 14
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 14);
     }
@@ -552,7 +1345,7 @@ mod tests {
             modifiers: vec![Modifier::DropHighest(1)],
         };
         let expr = Expr::Roll(roll);
-        let mut rng = TestRng::new(vec![1, 5, 3, 6]); // Drop 6, keep 1+5+3 = 9
+        let mut rng = TestRng::new(&[1, 5, 3, 6]); // Drop 6, keep 1+5+3 = 9
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 9);
     }
@@ -568,7 +1361,7 @@ mod tests {
             })],
         };
         let expr = Expr::Roll(roll);
-        let mut rng = TestRng::new(vec![10, 7, 8, 3, 9]); // 10, 8, 9 >= 8 = 3 successes
+        let mut rng = TestRng::new(&[10, 7, 8, 3, 9]); // 10, 8, 9 >= 8 = 3 successes
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 3);
     }
@@ -584,7 +1377,7 @@ mod tests {
             })],
         };
         let expr = Expr::Roll(roll);
-        let mut rng = TestRng::new(vec![1, 2, 3]); // No 6s = 0 successes
+        let mut rng = TestRng::new(&[1, 2, 3]); // No 6s = 0 successes
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 0);
     }
@@ -600,13 +1393,207 @@ mod tests {
             })],
         };
         let expr = Expr::Roll(roll);
-        let mut rng = TestRng::new(vec![10, 5, 8, 3]); // 10*, 5, 8*, 3 = 2 successes
+        let mut rng = TestRng::new(&[10, 5, 8, 3]); // 10*, 5, 8*, 3 = 2 successes
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert!(result.expression.contains("successes"));
         assert!(result.expression.contains("10*")); // Success marked
         assert!(result.expression.contains("8*"));  // Success marked
     }
 
+    #[test]
+    fn test_evaluate_net_successes() {
+        // 10 and 8 succeed, the 1 subtracts one
+        let expr = crate::parser::parse("4d10>=8f1").unwrap();
+        let mut rng = TestRng::new(&[10, 1, 8, 5]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 1);
+        assert_eq!(result.expression, "4d10>=8f1[10*, 1x, 8*, 5] = 1 success");
+
+        // Net successes can go negative
+        let mut rng = TestRng::new(&[1, 1, 3, 5]);
+        assert_eq!(evaluate_with_rng(&expr, &mut rng).unwrap().total, -2);
+
+        let expr = crate::parser::parse("10d10>7f<3").unwrap();
+        let mut rng = TestRng::new(&[8, 2, 7, 1, 10, 3, 9, 5, 6, 4]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 1);
+        assert_eq!(
+            result.expression,
+            "10d10>7f<3[8*, 2x, 7, 1x, 10*, 3, 9*, 5, 6, 4] = 1 success"
+        );
+    }
+
+    #[test]
+    fn test_evaluate_modifier_order() {
+        let expr = crate::parser::parse("2d6kl1!").unwrap();
+        let canonical = EvalConfig {
+            order: ModifierOrder::Canonical,
+            ..EvalConfig::default()
+        };
+
+        // As written, the 6 is dropped before anything explodes
+        let mut rng = TestRng::new(&[6, 3, 2]);
+        let result = evaluate_with_config(&expr, &mut rng, &EvalConfig::default()).unwrap();
+        assert_eq!(result.total, 3);
+
+        // In canonical order the 6 explodes first, then the lowest die is kept
+        let mut rng = TestRng::new(&[6, 3, 2]);
+        let result = evaluate_with_config(&expr, &mut rng, &canonical).unwrap();
+        assert_eq!(result.total, 2);
+        assert_eq!(result.expression, "2d6kl1![(6), (3), 2] = 2");
+    }
+
+    #[test]
+    fn test_evaluate_keep_and_drop_conditions() {
+        let expr = crate::parser::parse("6d6d<3").unwrap();
+        let mut rng = TestRng::new(&[1, 4, 2, 6, 3, 5]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 18);
+        assert_eq!(result.expression, "6d6d<3[(1), 4, (2), 6, 3, 5] = 18");
+
+        let expr = crate::parser::parse("6d6k>=4").unwrap();
+        let mut rng = TestRng::new(&[1, 4, 2, 6, 3, 5]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 15);
+        assert_eq!(result.expression, "6d6k>=4[(1), 4, (2), 6, (3), 5] = 15");
+    }
+
+    #[test]
+    fn test_evaluate_matches() {
+        let expr = crate::parser::parse("6d10m").unwrap();
+        let mut rng = TestRng::new(&[7, 3, 7, 1, 7, 3]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 28);
+        assert_eq!(result.sets, vec![(7, 3), (3, 2)]);
+        assert_eq!(result.expression, "6d10m2[7, 3, 7, 1, 7, 3] = 28 (3x7, 2x3)");
+
+        let expr = crate::parser::parse("6d10mt").unwrap();
+        let mut rng = TestRng::new(&[7, 3, 7, 1, 7, 3]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 2);
+        assert_eq!(result.expression, "6d10mt2[7, 3, 7, 1, 7, 3] = 2 sets (3x7, 2x3)");
+
+        // Only kept dice form sets, and sets carry through arithmetic
+        let expr = crate::parser::parse("4d6kh2mt + 1").unwrap();
+        let mut rng = TestRng::new(&[6, 5, 6, 5]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 2);
+        assert_eq!(result.sets, vec![(6, 2)]);
+    }
+
+    #[test]
+    fn test_evaluate_dynamic_roll() {
+        let expr = crate::parser::parse("(1d4)d6kh2").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[3, 2, 6, 5])).unwrap();
+        assert_eq!(result.total, 11);
+        assert_eq!(result.dice.len(), 3);
+        assert_eq!(result.expression, "(1d4[3] = 3)d6 -> 3d6kh2[(2), 6, 5] = 11");
+
+        let expr = crate::parser::parse("1d(2d4)").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[1, 3, 2])).unwrap();
+        assert_eq!(result.total, 2);
+        assert_eq!(result.expression, "1d(2d4[1, 3] = 4) -> 1d4[2] = 2");
+
+        let expr = crate::parser::parse("@level d6").unwrap().substitute(&[("level".to_string(), 2)].into());
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[4, 1])).unwrap();
+        assert_eq!(result.expression, "2d6[4, 1] = 5");
+    }
+
+    #[test]
+    fn test_evaluate_dynamic_roll_guardrails() {
+        let roll = |input: &str| evaluate_with_rng(&crate::parser::parse(input).unwrap(), &mut TestRng::new(&[1]));
+        assert!(matches!(roll("(1d4 - 2)d6"), Err(Error::InvalidDiceCount(-1))));
+        assert!(matches!(roll("2d(1d4 - 1)"), Err(Error::InvalidDiceSides(0))));
+        assert!(matches!(roll("(1d4 - 1)d6"), Ok(RollResult { total: 0, .. })));
+        assert!(matches!(roll("(100000)d6"), Err(Error::DiceLimit(_))));
+        assert!(matches!(roll("1d(10000000000)"), Err(Error::SidesLimit(_))));
+        assert!(matches!(roll("@level d8"), Err(Error::UnknownVariable(_))));
+    }
+
+    #[test]
+    fn test_evaluate_reroll_keep() {
+        // First die: 3 rerolls to 15, keeping 15; second die: 12 stays
+        let expr = crate::parser::parse("2d20rb<10").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[3, 12, 15])).unwrap();
+        assert_eq!(result.total, 27);
+        assert_eq!(result.dice[0].rolls, vec![3, 15]);
+        assert_eq!(result.dice[0].discarded, Some(3));
+        assert_eq!(result.expression, "2d20rb<10[15/(3), 12] = 27");
+
+        // Keeping the better roll never rerolls a second time
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[5, 12, 2])).unwrap();
+        assert_eq!(result.expression, "2d20rb<10[5/(2), 12] = 17");
+
+        let expr = crate::parser::parse("2d6rw>4>=4").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[6, 5, 2, 6])).unwrap();
+        assert_eq!(result.total, 1);
+        assert_eq!(result.expression, "2d6rw>4>=4[2/(6), 5*/(6)] = 1 success");
+    }
+
+    #[test]
+    fn test_evaluate_per_die_min_max() {
+        let expr = crate::parser::parse("3d6min3").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[1, 5, 2])).unwrap();
+        assert_eq!(result.total, 11);
+        assert_eq!(result.dice[0].rolls, vec![1]);
+        assert_eq!(result.expression, "3d6min3[3/(1), 5, 3/(2)] = 11");
+
+        let expr = crate::parser::parse("2d20max15kh1").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[19, 12])).unwrap();
+        assert_eq!(result.total, 15);
+        assert_eq!(result.expression, "2d20max15kh1[15/(19), (12)] = 15");
+    }
+
+    #[test]
+    fn test_evaluate_wild_die() {
+        // The d8 rolls 5; the wild d6 aces 6 + 3
+        let expr = crate::parser::parse("1d8w").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[5, 6, 3])).unwrap();
+        assert_eq!(result.total, 9);
+        assert_eq!(result.dice[1].rolls, vec![6, 3]);
+        assert!(result.dice[1].wild && result.dice[0].dropped);
+        assert_eq!(result.expression, "1d8w6[(5), 9w] = 9");
+
+        // The trait die aces 8 + 2 and beats the wild die
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[8, 2, 4])).unwrap();
+        assert_eq!(result.expression, "1d8w6[10, (4w)] = 10");
+
+        // Three trait dice: the wild die replaces the lowest
+        let expr = crate::parser::parse("3d6w").unwrap();
+        let result = evaluate_with_rng(&expr, &mut TestRng::new(&[2, 4, 1, 5])).unwrap();
+        assert_eq!(result.expression, "3d6w6[2, 4, (1), 5w] = 11");
+
+        let expr = crate::parser::parse("1d8w0").unwrap();
+        assert!(matches!(evaluate(&expr), Err(Error::InvalidDiceSides(0))));
+    }
+
+    #[test]
+    fn test_evaluate_sort() {
+        let expr = crate::parser::parse("4d6kh3sd").unwrap();
+        let mut rng = TestRng::new(&[2, 6, 1, 5]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 13);
+        assert_eq!(result.expression, "4d6kh3sd[6, 5, 2, (1)] = 13");
+
+        // Sorting first still keeps the same dice
+        let expr = crate::parser::parse("4d6skh3").unwrap();
+        let mut rng = TestRng::new(&[5, 2, 6, 1]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 13);
+        assert_eq!(result.expression, "4d6skh3[(1), 2, 5, 6] = 13");
+        let values: Vec<i64> = result.dice.iter().map(|d| d.value).collect();
+        assert_eq!(values, vec![1, 2, 5, 6]);
+    }
+
+    #[test]
+    fn test_evaluate_double_successes() {
+        let expr = crate::parser::parse("4d10>=8dd10").unwrap();
+        let mut rng = TestRng::new(&[10, 9, 10, 2]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 5);
+        assert_eq!(result.expression, "4d10>=8dd10[10**, 9*, 10**, 2] = 5 successes");
+    }
+
     #[test]
     fn test_evaluate_penetrating_explode() {
         let roll = Roll {
@@ -621,7 +1608,7 @@ mod tests {
         let expr = Expr::Roll(roll);
         // Rolls: 6 (explode), 6 (explode), 4 (stop)
         // Added: 6 + (6-1) + (4-1) = 6 + 5 + 3 = 14
-        let mut rng = TestRng::new(vec![6, 6, 4]);
+        let mut rng = TestRng::new(&[6, 6, 4]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 14);
     }
@@ -640,7 +1627,7 @@ mod tests {
         let expr = Expr::Roll(roll);
         // Roll: 4 (no explosion)
         // Total: 4 (no -1 because no explosion occurred)
-        let mut rng = TestRng::new(vec![4]);
+        let mut rng = TestRng::new(&[4]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 4);
     }
@@ -659,7 +1646,7 @@ mod tests {
         let expr = Expr::Roll(roll);
         // Rolls: 6 (explode, create new die), 4 (stop)
         // Result: 2 dice with values 6 and 4
-        let mut rng = TestRng::new(vec![6, 4]);
+        let mut rng = TestRng::new(&[6, 4]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 10); // 6 + 4
         assert_eq!(result.dice.len(), 2); // Two separate dice
@@ -679,7 +1666,7 @@ mod tests {
         let expr = Expr::Roll(roll);
         // Rolls: 6 (explode), 6 (explode), 6 (explode), 4 (stop)
         // Result: 4 dice with values 6, 6, 6, 4
-        let mut rng = TestRng::new(vec![6, 6, 6, 4]);
+        let mut rng = TestRng::new(&[6, 6, 6, 4]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 22); // 6 + 6 + 6 + 4
         assert_eq!(result.dice.len(), 4); // Four separate dice
@@ -699,7 +1686,7 @@ mod tests {
         let expr = Expr::Roll(roll);
         // Rolls: 6 (explode), 6 (explode), 4 (stop)
         // Result: 1 die with value 6 + 6 + 4 = 16
-        let mut rng = TestRng::new(vec![6, 6, 4]);
+        let mut rng = TestRng::new(&[6, 6, 4]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 16); // 6 + 6 + 4
         assert_eq!(result.dice.len(), 1); // One die with compounded value
@@ -723,7 +1710,7 @@ mod tests {
         // Initial rolls: 6 (explode), 3
         // Explosion: 5 (stop)
         // Result: 3 dice (6, 5, 3), keep highest 2 (6, 5)
-        let mut rng = TestRng::new(vec![6, 3, 5]);
+        let mut rng = TestRng::new(&[6, 3, 5]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 11); // 6 + 5 (3 dropped)
         assert_eq!(result.dice.len(), 3); // Three dice total
@@ -744,11 +1731,247 @@ mod tests {
         let expr = Expr::Roll(roll);
         // Rolls: 6 (explode, create new die with -1), 4 (stop)
         // Result: 2 dice with values 6 and 3 (4-1 penetrating)
-        let mut rng = TestRng::new(vec![6, 4]);
+        let mut rng = TestRng::new(&[6, 4]);
         let result = evaluate_with_rng(&expr, &mut rng).unwrap();
         assert_eq!(result.total, 9); // 6 + 3 (4-1)
         assert_eq!(result.dice.len(), 2); // Two separate dice
         assert_eq!(result.dice[0].value, 6);
         assert_eq!(result.dice[1].value, 3); // 4-1 penetrating
     }
+
+    #[test]
+    fn test_evaluate_floor_division() {
+        let expr = crate::parser::parse("-7 // 2").unwrap();
+        assert_eq!(evaluate(&expr).unwrap().total, -4);
+
+        let expr = crate::parser::parse("-7 / 2").unwrap();
+        assert_eq!(evaluate(&expr).unwrap().total, -3);
+
+        let expr = crate::parser::parse("7 // 2").unwrap();
+        assert_eq!(evaluate(&expr).unwrap().total, 3);
+    }
+
+    #[test]
+    fn test_evaluate_rounding_functions() {
+        // 3d6 rolls 3 + 4 + 6 = 13, halved = 6.5
+        let cases = [("floor(3d6 / 2)", 6), ("ceil(3d6 / 2)", 7), ("round(3d6 / 2)", 7)];
+        for (input, expected) in cases {
+            let expr = crate::parser::parse(input).unwrap();
+            let mut rng = TestRng::new(&[3, 4, 6]);
+            let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+            assert_eq!(result.total, expected, "{}", input);
+            assert_eq!(result.dice.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_rounding_is_exact() {
+        // Inner divisions are not truncated inside a rounding function
+        let expr = crate::parser::parse("ceil(1/2 + 1/2)").unwrap();
+        assert_eq!(evaluate(&expr).unwrap().total, 1);
+
+        let expr = crate::parser::parse("round(-5 / 2)").unwrap();
+        assert_eq!(evaluate(&expr).unwrap().total, -3);
+
+        let expr = crate::parser::parse("floor(-5 / 2)").unwrap();
+        assert_eq!(evaluate(&expr).unwrap().total, -3);
+
+        let expr = crate::parser::parse("ceil(-5 / 2)").unwrap();
+        assert_eq!(evaluate(&expr).unwrap().total, -2);
+    }
+
+    #[test]
+    fn test_evaluate_rounding_output_format() {
+        let expr = crate::parser::parse("floor(7 / 2)").unwrap();
+        let result = evaluate(&expr).unwrap();
+        assert_eq!(result.expression, "floor(7 / 2 = 3.5) = 3");
+    }
+
+    #[test]
+    fn test_evaluate_rounding_division_by_zero() {
+        let expr = crate::parser::parse("floor(1 / (2 - 2))").unwrap();
+        assert!(matches!(evaluate(&expr), Err(Error::DivisionByZero)));
+    }
+
+    #[test]
+    fn test_evaluate_min_max() {
+        // 1d6 rolls 1, so 1d6 - 2 = -1 and the minimum of 1 applies
+        let expr = crate::parser::parse("max(1d6 - 2, 1)").unwrap();
+        let mut rng = TestRng::new(&[1]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 1);
+        assert_eq!(result.expression, "max(1d6[1] = 1 - 2 = -1, 1) = 1");
+
+        let expr = crate::parser::parse("min(1d20, 1d20)").unwrap();
+        let mut rng = TestRng::new(&[15, 4]);
+        let result = evaluate_with_rng(&expr, &mut rng).unwrap();
+        assert_eq!(result.total, 4);
+        assert_eq!(result.dice.len(), 2);
+    }
+
+    #[test]
+    fn test_evaluate_abs() {
+        let expr = crate::parser::parse("abs(1d6 - 7)").unwrap();
+        let mut rng = TestRng::new(&[2]);
+        assert_eq!(evaluate_with_rng(&expr, &mut rng).unwrap().total, 5);
+    }
+
+    #[test]
+    fn test_evaluate_clamp() {
+        let cases = [(vec![1, 1], 3), (vec![6, 6], 10), (vec![3, 4], 7)];
+        for (rolls, expected) in cases {
+            let expr = crate::parser::parse("clamp(2d6, 3, 10)").unwrap();
+            let mut rng = TestRng::new(&rolls);
+            assert_eq!(evaluate_with_rng(&expr, &mut rng).unwrap().total, expected);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_overflow() {
+        let expr = crate::parser::parse("4294967295 * 4294967295 * 4294967295").unwrap();
+        assert!(matches!(evaluate(&expr), Err(Error::Overflow)));
+    }
+
+    #[test]
+    fn test_evaluate_rounding_overflow() {
+        let expr = crate::parser::parse("round(4294967295 * 4294967295 / 3 * 4)").unwrap();
+        assert!(matches!(evaluate(&expr), Err(Error::Overflow)));
+    }
+
+    #[test]
+    fn test_evaluate_default_limits() {
+        let expr = crate::parser::parse("4294967295d4294967295").unwrap();
+        assert!(matches!(evaluate(&expr), Err(Error::SidesLimit(_))));
+
+        let expr = crate::parser::parse("4294967295d6").unwrap();
+        assert!(matches!(evaluate(&expr), Err(Error::DiceLimit(_))));
+
+        let expr = crate::parser::parse("1d0").unwrap();
+        assert!(matches!(evaluate(&expr), Err(Error::InvalidDiceSides(0))));
+    }
+
+    #[test]
+    fn test_evaluate_custom_limits() {
+        let limits = EvalLimits {
+            max_dice_per_roll: 10,
+            max_total_dice: 15,
+            max_sides: 20,
+        };
+        let mut rng = TestRng::new(&[1]);
+
+        let expr = crate::parser::parse("10d20").unwrap();
+        assert!(evaluate_with_limits(&expr, &mut rng, &limits).is_ok());
+
+        let expr = crate::parser::parse("11d6").unwrap();
+        assert!(matches!(
+            evaluate_with_limits(&expr, &mut rng, &limits),
+            Err(Error::DiceLimit(10))
+        ));
+
+        let expr = crate::parser::parse("8d6 + 8d6").unwrap();
+        assert!(matches!(
+            evaluate_with_limits(&expr, &mut rng, &limits),
+            Err(Error::TotalDiceLimit(15))
+        ));
+
+        let expr = crate::parser::parse("1d100").unwrap();
+        assert!(matches!(
+            evaluate_with_limits(&expr, &mut rng, &limits),
+            Err(Error::SidesLimit(20))
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_limits_count_exploded_dice() {
+        let limits = EvalLimits {
+            max_dice_per_roll: 3,
+            ..EvalLimits::default()
+        };
+        let expr = crate::parser::parse("2d6!").unwrap();
+        let mut rng = TestRng::new(&[6]);
+        assert!(matches!(
+            evaluate_with_limits(&expr, &mut rng, &limits),
+            Err(Error::DiceLimit(3))
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_explode_limit_error() {
+        let expr = crate::parser::parse("1d1!").unwrap();
+        let mut rng = TestRng::new(&[1]);
+        assert!(matches!(
+            evaluate_with_rng(&expr, &mut rng),
+            Err(Error::ExplodeLimit(100))
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_explode_limit_stop() {
+        let config = EvalConfig {
+            max_explosions: 3,
+            on_limit: LimitPolicy::Stop,
+            ..EvalConfig::default()
+        };
+
+        // Standard: the original die plus a chain of 3 exploded dice
+        let expr = crate::parser::parse("1d1!").unwrap();
+        let mut rng = TestRng::new(&[1]);
+        let result = evaluate_with_config(&expr, &mut rng, &config).unwrap();
+        assert_eq!(result.total, 4);
+        assert_eq!(result.dice.len(), 4);
+        assert!(result.dice.iter().all(|d| !d.capped));
+
+        // Compounding: one die with 3 explosions added
+        let expr = crate::parser::parse("1d6!!").unwrap();
+        let mut rng = TestRng::new(&[6]);
+        let result = evaluate_with_config(&expr, &mut rng, &config).unwrap();
+        assert_eq!(result.total, 24);
+        assert_eq!(result.dice.len(), 1);
+    }
+
+    #[test]
+    fn test_evaluate_explode_limit_cap() {
+        let config = EvalConfig {
+            max_explosions: 2,
+            on_limit: LimitPolicy::Cap,
+            ..EvalConfig::default()
+        };
+        let expr = crate::parser::parse("2d6!").unwrap();
+        // First die: 6 -> 6 -> 6 (capped); second die: 2
+        let mut rng = TestRng::new(&[6, 2, 6, 6]);
+        let result = evaluate_with_config(&expr, &mut rng, &config).unwrap();
+        assert_eq!(result.total, 20);
+        assert_eq!(result.expression, "2d6![6, 2, 6, 6^] = 20");
+    }
+
+    #[test]
+    fn test_evaluate_reroll_limit_policies() {
+        let expr = crate::parser::parse("1d6r<7").unwrap();
+
+        let mut rng = TestRng::new(&[4]);
+        assert!(matches!(
+            evaluate_with_rng(&expr, &mut rng),
+            Err(Error::RerollLimit(100))
+        ));
+
+        let config = EvalConfig {
+            max_rerolls: 5,
+            on_limit: LimitPolicy::Stop,
+            ..EvalConfig::default()
+        };
+        let mut rng = TestRng::new(&[4]);
+        let result = evaluate_with_config(&expr, &mut rng, &config).unwrap();
+        assert_eq!(result.total, 4);
+        assert_eq!(result.dice[0].rolls.len(), 6);
+        assert!(!result.dice[0].capped);
+
+        let config = EvalConfig {
+            on_limit: LimitPolicy::Cap,
+            ..config
+        };
+        let mut rng = TestRng::new(&[4]);
+        let result = evaluate_with_config(&expr, &mut rng, &config).unwrap();
+        assert!(result.dice[0].capped);
+        assert_eq!(result.expression, "1d6r<7[4^] = 4");
+    }
 }
@@ -1,8 +1,10 @@
 // ABOUTME: Monte Carlo simulation for dice expressions.
 // ABOUTME: Runs many trials to compute probability distributions and statistics.
 
+use crate::ast::Expr;
 use crate::error::Result;
 use crate::parser;
+use crate::plan::RollPlan;
 use crate::roller::{evaluate_with_rng, FastRng};
 use std::collections::HashMap;
 
@@ -21,6 +23,10 @@ pub struct SimResult {
     pub std_dev: f64,
     /// Number of trials run.
     pub n: usize,
+    /// How many trials fell in each outcome band, by label in the order the
+    /// bands are written. Empty unless the expression has an outcome table;
+    /// trials that no band covers aren't counted.
+    pub bands: Vec<(String, usize)>,
 }
 
 impl SimResult {
@@ -39,6 +45,24 @@ impl SimResult {
             .collect()
     }
 
+    /// Returns the probability of each outcome band, in the order the bands
+    /// are written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let result = diceman::simulate("2d6 + 1 {<=6: miss, 7..9: weak, >=10: strong}", 10000).unwrap();
+    /// for (label, p) in result.band_probabilities() {
+    ///     println!("{}: {:.2}", label, p);  // miss: ~0.28, weak: ~0.44, strong: ~0.28
+    /// }
+    /// ```
+    pub fn band_probabilities(&self) -> Vec<(String, f64)> {
+        self.bands
+            .iter()
+            .map(|(label, count)| (label.clone(), *count as f64 / self.n as f64))
+            .collect()
+    }
+
     /// Returns the mode (most common outcome).
     pub fn mode(&self) -> Option<i64> {
         self.distribution
@@ -70,6 +94,30 @@ impl SimResult {
     }
 }
 
+/// Result of simulating how often an expression rolls sets of matching dice.
+#[derive(Debug, Clone)]
+pub struct SetSimResult {
+    /// Width of the widest set in each trial: width -> count. Trials
+    /// without a set are counted under 0.
+    pub widest: HashMap<u32, usize>,
+    /// Number of trials run.
+    pub n: usize,
+}
+
+impl SetSimResult {
+    /// Returns the probability of at least one set of `width` or more
+    /// matching dice (2 for a pair, 3 for a triple).
+    pub fn at_least(&self, width: u32) -> f64 {
+        let hits: usize = self
+            .widest
+            .iter()
+            .filter(|(&w, _)| w > 0 && w >= width)
+            .map(|(_, &count)| count)
+            .sum();
+        hits as f64 / self.n as f64
+    }
+}
+
 /// Run a Monte Carlo simulation on a dice expression.
 ///
 /// # Arguments
@@ -79,58 +127,102 @@ impl SimResult {
 /// # Returns
 /// A `SimResult` containing the distribution and statistics.
 pub fn simulate(expr: &str, n: usize) -> Result<SimResult> {
-    let parsed = parser::parse(expr)?;
-    let mut rng = FastRng::new();
+    run(&parser::parse(expr)?, n, &mut FastRng::new())
+}
 
-    let mut distribution: HashMap<i64, usize> = HashMap::new();
-    let mut sum: i64 = 0;
-    let mut sum_sq: i64 = 0;
-    let mut min = i64::MAX;
-    let mut max = i64::MIN;
+/// Run a simulation with a seeded RNG for reproducibility.
+pub fn simulate_seeded(expr: &str, n: usize, seed: u64) -> Result<SimResult> {
+    run(&parser::parse(expr)?, n, &mut FastRng::with_seed(seed))
+}
 
-    for _ in 0..n {
-        let result = evaluate_with_rng(&parsed, &mut rng)?;
-        let total = result.total;
+/// Run a Monte Carlo simulation on an already parsed expression, such as
+/// one with its variables bound by [`Expr::substitute`].
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// let expr = diceman::parse("1d1 + @bonus").unwrap();
+/// let bound = expr.substitute(&HashMap::from([("bonus".to_string(), 5_000_000_000)]));
+/// let result = diceman::simulate_expr(&bound, 10).unwrap();
+/// assert_eq!(result.min, 5_000_000_001);
+/// ```
+pub fn simulate_expr(expr: &Expr, n: usize) -> Result<SimResult> {
+    run(expr, n, &mut FastRng::new())
+}
 
-        *distribution.entry(total).or_insert(0) += 1;
-        sum += total;
-        sum_sq += total * total;
-        min = min.min(total);
-        max = max.max(total);
-    }
+/// Simulate how often an expression rolls sets of matching dice.
+///
+/// Only rolls that look for matches (`m`, `mt`) report sets, and only sets
+/// at least as wide as the modifier asks for: use `5d10m` to find pairs.
+///
+/// # Examples
+///
+/// ```
+/// let sets = diceman::simulate_sets("5d10m", 10000).unwrap();
+/// println!("P(pair): {:.2}", sets.at_least(2));  // ~0.70
+/// println!("P(triple): {:.2}", sets.at_least(3));  // ~0.09
+/// ```
+pub fn simulate_sets(expr: &str, n: usize) -> Result<SetSimResult> {
+    run_sets(&parser::parse(expr)?, n, &mut FastRng::new())
+}
 
-    let mean = sum as f64 / n as f64;
-    let variance = (sum_sq as f64 / n as f64) - (mean * mean);
-    let std_dev = variance.sqrt();
+/// Simulate sets of matching dice with a seeded RNG for reproducibility.
+pub fn simulate_sets_seeded(expr: &str, n: usize, seed: u64) -> Result<SetSimResult> {
+    run_sets(&parser::parse(expr)?, n, &mut FastRng::with_seed(seed))
+}
 
-    Ok(SimResult {
-        distribution,
-        min,
-        max,
-        mean,
-        std_dev,
-        n,
-    })
+/// Simulate sets of matching dice for an already parsed expression.
+pub fn simulate_sets_expr(expr: &Expr, n: usize) -> Result<SetSimResult> {
+    run_sets(expr, n, &mut FastRng::new())
 }
 
-/// Run a simulation with a seeded RNG for reproducibility.
-pub fn simulate_seeded(expr: &str, n: usize, seed: u64) -> Result<SimResult> {
-    let parsed = parser::parse(expr)?;
-    let mut rng = FastRng::with_seed(seed);
+fn run_sets(expr: &Expr, n: usize, rng: &mut FastRng) -> Result<SetSimResult> {
+    // Sets need the individual dice, so this evaluates rather than using a plan
+    let mut widest: HashMap<u32, usize> = HashMap::new();
+    for _ in 0..n {
+        let result = evaluate_with_rng(expr, rng)?;
+        let width = result.sets.iter().map(|&(_, width)| width).max().unwrap_or(0);
+        *widest.entry(width).or_insert(0) += 1;
+    }
+
+    Ok(SetSimResult { widest, n })
+}
+
+fn run(parsed: &Expr, n: usize, rng: &mut FastRng) -> Result<SimResult> {
+    // Only totals are needed, as with `roll_total`, but a plan compiled once
+    // skips re-walking the tree on every trial and benches slightly faster
+    let mut plan = RollPlan::new(parsed);
 
     let mut distribution: HashMap<i64, usize> = HashMap::new();
-    let mut sum: i64 = 0;
-    let mut sum_sq: i64 = 0;
+    let mut sum: i128 = 0;
+    let mut sum_sq: i128 = 0;
     let mut min = i64::MAX;
     let mut max = i64::MIN;
 
+    // Bands sharing a label are counted together
+    let mut bands: Vec<(String, usize)> = Vec::new();
+    if let Some(table) = parsed.outcome_table() {
+        for band in table {
+            if !bands.iter().any(|(label, _)| *label == band.label) {
+                bands.push((band.label.clone(), 0));
+            }
+        }
+    }
+
     for _ in 0..n {
-        let result = evaluate_with_rng(&parsed, &mut rng)?;
-        let total = result.total;
+        let total = plan.roll(rng)?;
+
+        if let Some(label) = parsed.band(total) {
+            if let Some((_, count)) = bands.iter_mut().find(|(l, _)| l == label) {
+                *count += 1;
+            }
+        }
 
         *distribution.entry(total).or_insert(0) += 1;
-        sum += total;
-        sum_sq += total * total;
+        sum += total as i128;
+        sum_sq += (total as i128) * (total as i128);
         min = min.min(total);
         max = max.max(total);
     }
@@ -146,6 +238,7 @@ pub fn simulate_seeded(expr: &str, n: usize, seed: u64) -> Result<SimResult> {
         mean,
         std_dev,
         n,
+        bands,
     })
 }
 
@@ -199,6 +292,15 @@ mod tests {
         assert!((result.mean - 7.0).abs() < 0.3);
     }
 
+    #[test]
+    fn test_simulate_function_call() {
+        let result = simulate_seeded("max(1d6 - 2, 1)", 1000, 7).unwrap();
+
+        // Results below 1 are raised to 1
+        assert_eq!(result.min, 1);
+        assert_eq!(result.max, 4);
+    }
+
     #[test]
     fn test_sorted_outcomes() {
         let result = simulate_seeded("1d6", 600, 123).unwrap();
@@ -218,12 +320,53 @@ mod tests {
         assert_eq!(probs[&5], 1.0);
     }
 
+    #[test]
+    fn test_band_probabilities() {
+        let result = simulate_seeded("2d6 + 1 {<=6: miss, 7..9: weak, >=10: strong}", 20000, 7).unwrap();
+        let labels: Vec<&str> = result.bands.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["miss", "weak", "strong"]);
+        assert_eq!(result.bands.iter().map(|(_, count)| count).sum::<usize>(), 20000);
+
+        // 16 of the 36 rolls of 2d6 land on 6 to 8
+        let probabilities = result.band_probabilities();
+        assert!((probabilities[1].1 - 16.0 / 36.0).abs() < 0.02);
+
+        // Bands sharing a label are counted together; uncovered totals aren't counted
+        let result = simulate_seeded("1d6 {1: odd, 2: even, 3: odd, 4: even}", 600, 7).unwrap();
+        assert_eq!(result.bands.len(), 2);
+        assert!(result.bands.iter().map(|(_, count)| count).sum::<usize>() < 600);
+        assert!(simulate("1d6", 10).unwrap().bands.is_empty());
+
+        // A table inside parentheses is counted too
+        let result = simulate_seeded("(2d6 {<=6: miss, >=7: hit})", 600, 7).unwrap();
+        let labels: Vec<&str> = result.bands.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["miss", "hit"]);
+        assert_eq!(result.bands.iter().map(|(_, count)| count).sum::<usize>(), 600);
+    }
+
     #[test]
     fn test_mode() {
         let result = simulate("5", 100).unwrap();
         assert_eq!(result.mode(), Some(5));
     }
 
+    #[test]
+    fn test_simulate_sets() {
+        let result = simulate_sets_seeded("5d10m", 20000, 5).unwrap();
+        assert_eq!(result.widest.values().sum::<usize>(), 20000);
+
+        // P(no pair in 5d10) = 10*9*8*7*6 / 10^5 = 0.3024
+        assert!((result.at_least(2) - 0.6976).abs() < 0.02);
+        assert!(result.at_least(3) < result.at_least(2));
+
+        // Without a match modifier nothing is reported
+        let result = simulate_sets_seeded("5d10", 100, 5).unwrap();
+        assert_eq!(result.at_least(2), 0.0);
+
+        let result = simulate_sets_seeded("3d1m", 100, 5).unwrap();
+        assert_eq!(result.at_least(3), 1.0);
+    }
+
     #[test]
     fn test_median() {
         let result = simulate("5", 100).unwrap();
@@ -0,0 +1,1012 @@
+// ABOUTME: Analytic summary statistics and bounds for dice expressions.
+// ABOUTME: Computes mean, variance, min and max without rolling, for tooltips and previews.
+
+use crate::ast::{Compare, Condition, DynamicRoll, DynamicSides, Expr, Func, Modifier, Op, Roll};
+use crate::plan::roll_total;
+use crate::roller::{counted_sets, find_sets, FastRng, Scoring};
+
+/// Exact summary statistics of an expression's distribution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    /// Expected total.
+    pub mean: f64,
+    /// Variance of the total.
+    pub variance: f64,
+    /// Lowest possible total.
+    pub min: i64,
+    /// Highest possible total.
+    pub max: i64,
+}
+
+impl Stats {
+    fn constant(n: i64) -> Self {
+        Stats {
+            mean: n as f64,
+            variance: 0.0,
+            min: n,
+            max: n,
+        }
+    }
+
+    /// Standard deviation of the total.
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// Most distinct pools enumerated for a keep/drop roll.
+const MAX_POOLS: u128 = 100_000;
+
+/// Largest die whose faces are enumerated one by one (for rerolls and keep/drop).
+const MAX_FACES: i64 = 1000;
+
+/// Stand-in for an unbounded total. Large enough that no real total reaches
+/// it, small enough that sums and differences of two never overflow `i128`.
+const INF: i128 = 1 << 100;
+
+impl Expr {
+    /// Compute the mean, variance, min and max of this expression exactly,
+    /// without rolling.
+    ///
+    /// Plain rolls, success counts and rerolls are handled analytically, and
+    /// keep/drop rolls by enumerating every pool when there are at most
+    /// 100,000 distinct ones. A rolled count of dice (`(1d4)d6`) is handled
+    /// when each die counts on its own. Sums, differences and products of those combine
+    /// exactly, since separate rolls are independent. Returns `None` for
+    /// anything else, such as explosions, division by a roll, or `min`/`max`
+    /// over rolls; use [`simulate`](crate::simulate) for those.
+    ///
+    /// Reroll and explosion limits are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let stats = diceman::parse("2d6 + 3").unwrap().stats().unwrap();
+    /// assert_eq!(stats.mean, 10.0);
+    /// assert_eq!((stats.min, stats.max), (5, 15));
+    ///
+    /// assert!(diceman::parse("1d6!").unwrap().stats().is_none());
+    /// ```
+    pub fn stats(&self) -> Option<Stats> {
+        match self {
+            // Nothing is rolled, so any seed gives the same answer
+            _ if self.is_constant() => roll_total(self, &mut FastRng::with_seed(0))
+                .ok()
+                .map(Stats::constant),
+            Expr::Roll(roll) => roll_stats(roll),
+            Expr::DynamicRoll(roll) => dynamic_stats(roll),
+            Expr::Group(inner) | Expr::Bands { expr: inner, .. } => inner.stats(),
+            Expr::BinOp { op, left, right } => combine(*op, left.stats()?, right.stats()?),
+            _ => None,
+        }
+    }
+
+    /// The lowest and highest totals this expression can roll, where `None`
+    /// means unbounded.
+    ///
+    /// Unlike [`stats`](Expr::stats), this handles every expression. Bounds
+    /// are always safe but may be wider than the true range, and assume the
+    /// default [`LimitPolicy::Error`](crate::LimitPolicy::Error): a die that
+    /// hits the reroll limit fails the roll rather than keeping its value.
+    /// The explosion limit is ignored, so explosions are unbounded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let bounds = |s: &str| diceman::parse(s).unwrap().bounds();
+    /// assert_eq!(bounds("4d6kh3"), (Some(3), Some(18)));
+    /// assert_eq!(bounds("1d6!"), (Some(1), None));
+    /// assert_eq!(bounds("4d6!kh3"), (Some(3), Some(18)));
+    /// ```
+    pub fn bounds(&self) -> (Option<i64>, Option<i64>) {
+        let (lo, hi) = range(self, false);
+        (finite(lo), finite(hi))
+    }
+}
+
+fn combine(op: Op, a: Stats, b: Stats) -> Option<Stats> {
+    match op {
+        Op::Add => Some(Stats {
+            mean: a.mean + b.mean,
+            variance: a.variance + b.variance,
+            min: a.min.checked_add(b.min)?,
+            max: a.max.checked_add(b.max)?,
+        }),
+        Op::Sub => Some(Stats {
+            mean: a.mean - b.mean,
+            variance: a.variance + b.variance,
+            min: a.min.checked_sub(b.max)?,
+            max: a.max.checked_sub(b.min)?,
+        }),
+        Op::Mul => {
+            // Var(XY) = E[X²]E[Y²] - E[X]²E[Y]² for independent X and Y
+            let (a_sq, b_sq) = (a.mean * a.mean, b.mean * b.mean);
+            let variance = (a.variance + a_sq) * (b.variance + b_sq) - a_sq * b_sq;
+            let corners = [
+                a.min.checked_mul(b.min)?,
+                a.min.checked_mul(b.max)?,
+                a.max.checked_mul(b.min)?,
+                a.max.checked_mul(b.max)?,
+            ];
+            Some(Stats {
+                mean: a.mean * b.mean,
+                variance: variance.max(0.0),
+                min: *corners.iter().min()?,
+                max: *corners.iter().max()?,
+            })
+        }
+        Op::Div | Op::FloorDiv => None,
+    }
+}
+
+fn roll_stats(roll: &Roll) -> Option<Stats> {
+    let (lo, hi) = roll.sides.faces();
+    if hi < lo {
+        return None;
+    }
+
+    let mut rerolls = Vec::new();
+    let mut selections = Vec::new();
+    let mut filters = Vec::new();
+    for modifier in &roll.modifiers {
+        match modifier {
+            // A reroll after keep/drop only applies to the kept dice
+            Modifier::Reroll { .. } | Modifier::RerollKeep { .. } | Modifier::Min(_) | Modifier::Max(_)
+                if selections.is_empty() && filters.is_empty() =>
+            {
+                rerolls.push(modifier);
+            }
+            Modifier::KeepHighest(_)
+            | Modifier::KeepLowest(_)
+            | Modifier::DropHighest(_)
+            | Modifier::DropLowest(_)
+                if filters.is_empty() =>
+            {
+                selections.push(modifier)
+            }
+            // Conditions keep or drop each die on its own, unless dice were
+            // already dropped by count
+            Modifier::KeepWhere(condition) if selections.is_empty() => filters.push((*condition, true)),
+            Modifier::DropWhere(condition) if selections.is_empty() => filters.push((*condition, false)),
+            Modifier::CountSuccesses(_)
+            | Modifier::CountFailures(_)
+            | Modifier::DoubleSuccesses(_)
+            | Modifier::Sort { .. }
+            | Modifier::Matches { .. } => {}
+            _ => return None,
+        }
+    }
+    let scoring = Scoring::from_modifiers(&roll.modifiers);
+    // Counting sets needs the whole pool, so it always enumerates
+    let sets = counted_sets(&roll.modifiers);
+
+    let n = roll.count as f64;
+    let faces = (hi - lo + 1) as f64;
+    if sets.is_none() && rerolls.is_empty() && selections.is_empty() && filters.is_empty() {
+        let count = roll.count as i64;
+        match scoring {
+            None => {
+                return Some(Stats {
+                    mean: n * (lo + hi) as f64 / 2.0,
+                    variance: n * (faces * faces - 1.0) / 12.0,
+                    min: count.checked_mul(lo)?,
+                    max: count.checked_mul(hi)?,
+                })
+            }
+            // Plain success counting is binomial
+            Some(Scoring {
+                success,
+                double: None,
+                failure: None,
+            }) => {
+                let matching = count_where(&success, lo, hi);
+                let p = matching as f64 / faces;
+                return Some(Stats {
+                    mean: n * p,
+                    variance: n * p * (1.0 - p),
+                    min: if matching == hi - lo + 1 { count } else { 0 },
+                    max: if matching == 0 { 0 } else { count },
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    if hi - lo >= MAX_FACES {
+        return None;
+    }
+    // Highest face first, so enumerated pools come out sorted for keep/drop
+    let mut die: Vec<(i64, f64)> = (lo..=hi).rev().map(|v| (v, 1.0 / faces)).collect();
+    let mut clamped = false;
+    for modifier in rerolls {
+        die = match *modifier {
+            Modifier::Min(n) => {
+                clamped = true;
+                clamp_die(&die, |v| v.max(n))
+            }
+            Modifier::Max(n) => {
+                clamped = true;
+                clamp_die(&die, |v| v.min(n))
+            }
+            // Rerolls land on the die's faces, which may no longer be its values
+            _ if clamped => return None,
+            Modifier::Reroll { once, condition } => {
+                reroll(&die, once, &condition.unwrap_or_else(Condition::reroll_default))?
+            }
+            Modifier::RerollKeep { best, condition } => {
+                reroll_keep(&die, best, &condition.unwrap_or_else(Condition::reroll_default))
+            }
+            _ => unreachable!("only rerolls are collected"),
+        };
+    }
+    die.retain(|&(_, p)| p > 0.0);
+
+    let kept = |v: i64| filters.iter().all(|(c, keep)| c.compare.check(v, c.value) == *keep);
+    let score = |v: i64| match scoring {
+        _ if !kept(v) => 0,
+        Some(scoring) => scoring.score(v),
+        None => v,
+    };
+    if sets.is_none() && selections.is_empty() {
+        let mean: f64 = die.iter().map(|&(v, p)| p * score(v) as f64).sum();
+        let square: f64 = die.iter().map(|&(v, p)| p * (score(v) * score(v)) as f64).sum();
+        let count = roll.count as i64;
+        return Some(Stats {
+            mean: n * mean,
+            variance: n * (square - mean * mean).max(0.0),
+            min: count.checked_mul(die.iter().map(|&(v, _)| score(v)).min()?)?,
+            max: count.checked_mul(die.iter().map(|&(v, _)| score(v)).max()?)?,
+        });
+    }
+
+    // Multisets of `count` dice over the die's faces
+    let pools = (1..=roll.count as u128).try_fold(1u128, |pools, k| {
+        let pools = pools.checked_mul(die.len() as u128 + k - 1)? / k;
+        (pools <= MAX_POOLS).then_some(pools)
+    });
+    pools?;
+
+    let (start, end) = kept_window(&selections, roll.count as usize);
+    let mut moments = Moments::default();
+    let mut pool = Vec::with_capacity(roll.count as usize);
+    visit_pools(&die, roll.count, 1.0, &mut pool, &mut |pool, weight| {
+        let window = &pool[start..end];
+        let total = match sets {
+            Some(width) => find_sets(window.iter().copied().filter(|&v| kept(v)), width).len() as i64,
+            None => window.iter().map(|&v| score(v)).sum(),
+        };
+        moments.add(total, weight);
+    });
+    moments.stats()
+}
+
+/// Stats of a roll whose count is itself rolled: with `N` dice each scoring
+/// `X`, the mean is `E[N]E[X]` and the variance `E[N]Var(X) + Var(N)E[X]²`.
+///
+/// That only holds when each die is scored on its own, so rolled sides,
+/// keeping or dropping by count, and counting sets aren't supported.
+fn dynamic_stats(roll: &DynamicRoll) -> Option<Stats> {
+    let DynamicSides::Fixed(sides) = roll.sides else {
+        return None;
+    };
+    let compares_dice = roll.modifiers.iter().any(|m| {
+        matches!(
+            m,
+            Modifier::KeepHighest(_) | Modifier::KeepLowest(_) | Modifier::DropHighest(_) | Modifier::DropLowest(_)
+        )
+    });
+    if compares_dice || counted_sets(&roll.modifiers).is_some() {
+        return None;
+    }
+    let count = roll.count.stats()?;
+    if count.min < 0 {
+        return None;
+    }
+    let die = roll_stats(&Roll {
+        count: 1,
+        sides,
+        modifiers: roll.modifiers.clone(),
+    })?;
+    Some(Stats {
+        mean: count.mean * die.mean,
+        variance: count.mean * die.variance + count.variance * die.mean * die.mean,
+        min: die.min.checked_mul(count.min)?.min(die.min.checked_mul(count.max)?),
+        max: die.max.checked_mul(count.min)?.max(die.max.checked_mul(count.max)?),
+    })
+}
+
+/// Apply a reroll to a single die's distribution.
+fn reroll(die: &[(i64, f64)], once: bool, condition: &Condition) -> Option<Vec<(i64, f64)>> {
+    let matches = |v: i64| condition.compare.check(v, condition.value);
+    let rerolled: f64 = die.iter().filter(|&&(v, _)| matches(v)).map(|&(_, p)| p).sum();
+    // A reroll lands on any face; rerolling until done lands on a non-matching one
+    let targets = if once {
+        die.len()
+    } else {
+        die.iter().filter(|&&(v, _)| !matches(v)).count()
+    };
+    if targets == 0 {
+        return None;
+    }
+    Some(
+        die.iter()
+            .map(|&(v, p)| {
+                let kept = if matches(v) { 0.0 } else { p };
+                let landed = if once || !matches(v) { rerolled / targets as f64 } else { 0.0 };
+                (v, kept + landed)
+            })
+            .collect(),
+    )
+}
+
+/// Raise or lower a single die's values, merging the ones that meet.
+fn clamp_die(die: &[(i64, f64)], bound: impl Fn(i64) -> i64) -> Vec<(i64, f64)> {
+    let mut clamped: Vec<(i64, f64)> = Vec::with_capacity(die.len());
+    // Clamping never reorders values, so equal ones end up next to each other
+    for &(v, p) in die {
+        let v = bound(v);
+        match clamped.last_mut() {
+            Some((last, q)) if *last == v => *q += p,
+            _ => clamped.push((v, p)),
+        }
+    }
+    clamped
+}
+
+/// Apply a keep-best or keep-worse reroll to a single die's distribution,
+/// whose faces run highest first.
+fn reroll_keep(die: &[(i64, f64)], best: bool, condition: &Condition) -> Vec<(i64, f64)> {
+    // The reroll lands on any face
+    let fresh = 1.0 / die.len() as f64;
+    let rerolled: Vec<f64> = die
+        .iter()
+        .map(|&(v, p)| if condition.compare.check(v, condition.value) { p } else { 0.0 })
+        .collect();
+    let total: f64 = rerolled.iter().sum();
+    // Chance of rerolling a face above the current one
+    let mut above = 0.0;
+    die.iter()
+        .enumerate()
+        .map(|(i, &(v, p))| {
+            let below = (total - above - rerolled[i]).max(0.0);
+            // A rerolled face is kept unless the reroll lands past it, and
+            // rerolled faces on the other side move here when it lands here
+            let (stays, arrives) = if best {
+                ((die.len() - i) as f64, below)
+            } else {
+                ((i + 1) as f64, above)
+            };
+            above += rerolled[i];
+            (v, p - rerolled[i] + (rerolled[i] * stays + arrives) * fresh)
+        })
+        .collect()
+}
+
+/// The range of positions, in a pool sorted highest first, that survive
+/// `selections`. Keep and drop always remove dice from the ends of the
+/// remaining pool, so this doesn't depend on the values rolled.
+fn kept_window(selections: &[&Modifier], count: usize) -> (usize, usize) {
+    let (mut start, mut end) = (0, count);
+    for selection in selections {
+        match **selection {
+            Modifier::KeepHighest(k) => end = end.min(start + k as usize),
+            Modifier::KeepLowest(k) => start = start.max(end.saturating_sub(k as usize)),
+            Modifier::DropHighest(k) => start = end.min(start + k as usize),
+            Modifier::DropLowest(k) => end = start.max(end.saturating_sub(k as usize)),
+            _ => {}
+        }
+    }
+    (start, end)
+}
+
+/// Call `f` with every distinct pool of `remaining` dice (sorted highest
+/// first) and its probability.
+fn visit_pools(
+    die: &[(i64, f64)],
+    remaining: u32,
+    weight: f64,
+    pool: &mut Vec<i64>,
+    f: &mut impl FnMut(&[i64], f64),
+) {
+    let Some((&(value, p), rest)) = die.split_first() else {
+        f(pool, weight);
+        return;
+    };
+    let len = pool.len();
+    if rest.is_empty() {
+        pool.resize(len + remaining as usize, value);
+        f(pool, weight * p.powi(remaining as i32));
+        pool.truncate(len);
+        return;
+    }
+    // weight * C(remaining, c) * p^c for c copies of this face
+    let mut w = weight;
+    for c in 0..=remaining {
+        if c > 0 {
+            w *= p * (remaining - c + 1) as f64 / c as f64;
+            pool.push(value);
+        }
+        visit_pools(rest, remaining - c, w, pool, f);
+    }
+    pool.truncate(len);
+}
+
+#[derive(Default)]
+struct Moments {
+    weight: f64,
+    sum: f64,
+    sum_sq: f64,
+    range: Option<(i64, i64)>,
+}
+
+impl Moments {
+    fn add(&mut self, total: i64, weight: f64) {
+        self.weight += weight;
+        self.sum += weight * total as f64;
+        self.sum_sq += weight * (total as f64) * (total as f64);
+        self.range = Some(match self.range {
+            Some((lo, hi)) => (lo.min(total), hi.max(total)),
+            None => (total, total),
+        });
+    }
+
+    fn stats(&self) -> Option<Stats> {
+        let (min, max) = self.range?;
+        let mean = self.sum / self.weight;
+        Some(Stats {
+            mean,
+            variance: (self.sum_sq / self.weight - mean * mean).max(0.0),
+            min,
+            max,
+        })
+    }
+}
+
+/// Number of faces in `lo..=hi` that satisfy `condition`.
+fn count_where(condition: &Condition, lo: i64, hi: i64) -> i64 {
+    let (faces, v) = (hi - lo + 1, condition.value);
+    let below = |n: i64| n.clamp(0, faces);
+    match condition.compare {
+        Compare::Equal => (lo..=hi).contains(&v) as i64,
+        Compare::NotEqual => faces - (lo..=hi).contains(&v) as i64,
+        Compare::LessThan => below(v.saturating_sub(lo)),
+        Compare::LessOrEqual => below(v.saturating_sub(lo).saturating_add(1)),
+        Compare::GreaterThan => below(hi.saturating_sub(v)),
+        Compare::GreaterOrEqual => below(hi.saturating_sub(v).saturating_add(1)),
+    }
+}
+
+/// Interval of possible totals, with `±INF` for unbounded ends. `exact` is
+/// true inside a rounding function, where division isn't truncated.
+fn range(expr: &Expr, exact: bool) -> (i128, i128) {
+    match expr {
+        Expr::Number(n) => (*n as i128, *n as i128),
+        Expr::Roll(roll) => roll_range(roll),
+        Expr::DynamicRoll(roll) => dynamic_range(roll),
+        Expr::Var(_) => (-INF, INF),
+        Expr::Group(inner) | Expr::Bands { expr: inner, .. } => range(inner, exact),
+        Expr::BinOp { op, left, right } => {
+            let (a, b) = (range(left, exact), range(right, exact));
+            match op {
+                Op::Add => (clamp(a.0 + b.0), clamp(a.1 + b.1)),
+                Op::Sub => (clamp(a.0 - b.1), clamp(a.1 - b.0)),
+                Op::Mul => hull_of(
+                    [(a.0, b.0), (a.0, b.1), (a.1, b.0), (a.1, b.1)]
+                        .map(|(x, y)| clamp(x.saturating_mul(y))),
+                ),
+                // A fraction between -1 and 1 can make the quotient as large as it likes
+                Op::Div | Op::FloorDiv if exact && fractional(right) && b.0 <= 1 && b.1 >= -1 => {
+                    (-INF, INF)
+                }
+                Op::Div | Op::FloorDiv => divide(*op, a, b, exact),
+            }
+        }
+        Expr::Call { func, args } => {
+            let args: Vec<_> = args.iter().map(|arg| range(arg, func.is_rounding())).collect();
+            let Some(&first) = args.first() else {
+                return (0, 0);
+            };
+            match func {
+                // Exact division already widened the argument to whole numbers
+                Func::Floor | Func::Ceil | Func::Round => first,
+                Func::Min => args.iter().fold(first, |r, a| (r.0.min(a.0), r.1.min(a.1))),
+                Func::Max => args.iter().fold(first, |r, a| (r.0.max(a.0), r.1.max(a.1))),
+                Func::Abs if first.0 >= 0 => first,
+                Func::Abs if first.1 <= 0 => (-first.1, -first.0),
+                Func::Abs => (0, first.1.max(-first.0)),
+                Func::Clamp => match args[..] {
+                    [x, lo, hi] => (x.0.max(lo.0).min(hi.0), x.1.max(lo.1).min(hi.1)),
+                    _ => (-INF, INF),
+                },
+            }
+        }
+    }
+}
+
+fn roll_range(roll: &Roll) -> (i128, i128) {
+    let (lo, hi) = roll.sides.faces();
+    if hi < lo {
+        // Rolling a zero-sided die always fails
+        return (-INF, INF);
+    }
+    let count = roll.count as i128;
+    let explode = Condition::explode_default(&roll.sides);
+    dice_range((count, count), (lo as i128, hi as i128), explode, &roll.modifiers)
+}
+
+/// Range of a roll with a rolled count or sides, covering every count and
+/// die it can roll.
+fn dynamic_range(roll: &DynamicRoll) -> (i128, i128) {
+    let count = range(&roll.count, false);
+    if count.1 < 0 {
+        // A negative count always fails
+        return (-INF, INF);
+    }
+    let count = (count.0.max(0), count.1);
+    match &roll.sides {
+        DynamicSides::Fixed(sides) => {
+            let (lo, hi) = sides.faces();
+            if hi < lo {
+                return (-INF, INF);
+            }
+            dice_range(count, (lo as i128, hi as i128), Condition::explode_default(sides), &roll.modifiers)
+        }
+        DynamicSides::Expr(sides) => {
+            let sides = range(sides, false);
+            if sides.1 < 1 || unbounded(sides.1) {
+                return (-INF, INF);
+            }
+            // Whatever the die, it explodes by default on no less than the smallest maximum
+            let smallest = sides.0.clamp(1, i64::MAX as i128) as i64;
+            dice_range(count, (1, sides.1), Condition::ge(smallest), &roll.modifiers)
+        }
+    }
+}
+
+/// Range of rolling between `count.0` and `count.1` dice with faces in
+/// `faces`, where explosions without a condition use `explode`.
+fn dice_range(
+    mut count: (i128, i128),
+    faces: (i128, i128),
+    explode: Condition,
+    modifiers: &[Modifier],
+) -> (i128, i128) {
+    // Range of a single die's value
+    let mut value = faces;
+
+    for modifier in modifiers {
+        match modifier {
+            Modifier::Reroll { once, condition } => {
+                let condition = condition.unwrap_or_else(Condition::reroll_default);
+                value = match values_where(&condition, faces, false) {
+                    Some(kept) if !once && value == faces => kept,
+                    _ => hull(value, faces),
+                };
+            }
+            // Aces have no upper limit, and the wild die may roll a 1
+            Modifier::WildDie(_) => value = (value.0.min(1), INF),
+            Modifier::Min(n) => value = (value.0.max(*n as i128), value.1.max(*n as i128)),
+            Modifier::Max(n) => value = (value.0.min(*n as i128), value.1.min(*n as i128)),
+            Modifier::RerollKeep { best, condition } => {
+                let condition = condition.unwrap_or_else(Condition::reroll_default);
+                if values_where(&condition, value, true).is_some() {
+                    value = if *best {
+                        (value.0, value.1.max(faces.1))
+                    } else {
+                        (value.0.min(faces.0), value.1)
+                    };
+                }
+            }
+            Modifier::Explode {
+                compounding,
+                penetrating,
+                condition,
+            } => {
+                let condition = condition.unwrap_or(explode);
+                if values_where(&condition, hull(value, faces), true).is_none() {
+                    continue;
+                }
+                let added = if *penetrating { (faces.0 - 1, faces.1 - 1) } else { faces };
+                if *compounding {
+                    if added.0 < 0 {
+                        value.0 = -INF;
+                    }
+                    if added.1 > 0 {
+                        value.1 = INF;
+                    }
+                } else {
+                    count.1 = INF;
+                    value = hull(value, added);
+                }
+            }
+            Modifier::KeepHighest(k) | Modifier::KeepLowest(k) => {
+                count = (count.0.min(*k as i128), count.1.min(*k as i128));
+            }
+            Modifier::DropHighest(k) | Modifier::DropLowest(k) => {
+                count.0 = (count.0 - *k as i128).max(0);
+                if !unbounded(count.1) {
+                    count.1 = (count.1 - *k as i128).max(0);
+                }
+            }
+            Modifier::KeepWhere(condition) | Modifier::DropWhere(condition) => {
+                let keep = matches!(modifier, Modifier::KeepWhere(_));
+                match values_where(condition, value, keep) {
+                    Some(kept) => {
+                        if values_where(condition, value, !keep).is_some() {
+                            count.0 = 0;
+                        }
+                        value = kept;
+                    }
+                    None => count = (0, 0),
+                }
+            }
+            Modifier::CountSuccesses(_)
+            | Modifier::CountFailures(_)
+            | Modifier::DoubleSuccesses(_)
+            | Modifier::Sort { .. }
+            | Modifier::Matches { .. } => {}
+        }
+    }
+
+    if let Some(width) = counted_sets(modifiers) {
+        let most = if unbounded(count.1) { INF } else { count.1 / width.max(1) as i128 };
+        return (0, most);
+    }
+
+    // Each die adds its value, or its score when counting successes
+    let each = match Scoring::from_modifiers(modifiers) {
+        Some(scoring) => score_range(&scoring, value),
+        None => value,
+    };
+    let lo = if each.0 >= 0 { count.0 } else { count.1 };
+    let hi = if each.1 <= 0 { count.0 } else { count.1 };
+    (
+        clamp(lo.saturating_mul(each.0)),
+        clamp(hi.saturating_mul(each.1)),
+    )
+}
+
+/// Range of a single die's score, given the range of its value.
+fn score_range(scoring: &Scoring, value: (i128, i128)) -> (i128, i128) {
+    let any = |condition: Option<Condition>| {
+        condition.is_some_and(|c| values_where(&c, value, true).is_some())
+    };
+    let all = |condition: Option<Condition>| {
+        condition.is_some_and(|c| values_where(&c, value, false).is_none())
+    };
+    let success = Some(scoring.success);
+    let lo = all(success) as i128 - any(scoring.failure) as i128;
+    let hi = any(success) as i128 + (any(success) && any(scoring.double)) as i128
+        - all(scoring.failure) as i128;
+    (lo, hi)
+}
+
+/// Bounds of `left / right` (or `//`), trying every divisor at the ends of
+/// `right` and next to zero.
+fn divide(op: Op, left: (i128, i128), right: (i128, i128), exact: bool) -> (i128, i128) {
+    let divisors = [right.0, right.1, -1, 1]
+        .into_iter()
+        .filter(|&d| d != 0 && right.0 <= d && d <= right.1);
+    let mut result: Option<(i128, i128)> = None;
+    for d in divisors {
+        for x in [left.0, left.1] {
+            let (lo, hi) = if unbounded(x) {
+                // Unbounded stays unbounded whatever it's divided by
+                let sign = x.signum() * d.signum();
+                (sign * INF, sign * INF)
+            } else if exact || op == Op::FloorDiv {
+                let lo = floor_div(x, d);
+                let hi = if exact && op == Op::Div { ceil_div(x, d) } else { lo };
+                (lo, hi)
+            } else {
+                (x / d, x / d)
+            };
+            result = Some(match result {
+                Some(r) => (r.0.min(lo), r.1.max(hi)),
+                None => (lo, hi),
+            });
+        }
+    }
+    // Dividing by zero always fails
+    result.unwrap_or((-INF, INF))
+}
+
+/// Whether `expr` can be a fraction when evaluated exactly. Ranges of such
+/// expressions are widened to whole numbers, so they can't be used as divisors.
+fn fractional(expr: &Expr) -> bool {
+    match expr {
+        Expr::BinOp { op: Op::Div, .. } => true,
+        Expr::BinOp { op: Op::FloorDiv, .. } => false,
+        Expr::BinOp { left, right, .. } => fractional(left) || fractional(right),
+        Expr::Group(inner) => fractional(inner),
+        _ => false,
+    }
+}
+
+fn floor_div(x: i128, d: i128) -> i128 {
+    let q = x / d;
+    if x % d != 0 && (x < 0) != (d < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn ceil_div(x: i128, d: i128) -> i128 {
+    let q = x / d;
+    if x % d != 0 && (x < 0) == (d < 0) {
+        q + 1
+    } else {
+        q
+    }
+}
+
+/// The smallest range containing every value in `range` for which
+/// `condition` is `matching`, or `None` if there are none.
+fn values_where(condition: &Condition, range: (i128, i128), matching: bool) -> Option<(i128, i128)> {
+    // The set's ends are either the range's ends or next to the condition's value
+    let v = condition.value as i128;
+    let candidates = [range.0, v - 1, v, v + 1, range.1];
+    let test = |x: i128| {
+        let result = match condition.compare {
+            Compare::Equal => x == v,
+            Compare::NotEqual => x != v,
+            Compare::LessThan => x < v,
+            Compare::LessOrEqual => x <= v,
+            Compare::GreaterThan => x > v,
+            Compare::GreaterOrEqual => x >= v,
+        };
+        result == matching && range.0 <= x && x <= range.1
+    };
+    let lo = candidates.iter().copied().filter(|&x| test(x)).min()?;
+    let hi = candidates.iter().copied().filter(|&x| test(x)).max()?;
+    Some((lo, hi))
+}
+
+fn hull(a: (i128, i128), b: (i128, i128)) -> (i128, i128) {
+    (a.0.min(b.0), a.1.max(b.1))
+}
+
+fn hull_of(values: [i128; 4]) -> (i128, i128) {
+    let lo = values.iter().copied().min().unwrap_or_default();
+    let hi = values.iter().copied().max().unwrap_or_default();
+    (lo, hi)
+}
+
+fn clamp(value: i128) -> i128 {
+    value.clamp(-INF, INF)
+}
+
+/// Whether a bound is (a finite offset from) `±INF`. Real totals fit in
+/// `i64`, far below this.
+fn unbounded(bound: i128) -> bool {
+    bound.abs() >= INF / 2
+}
+
+/// Convert a bound to `i64`, or `None` if it is unbounded. Totals past
+/// `i64` overflow when rolled, so finite bounds are clamped to its range.
+fn finite(bound: i128) -> Option<i64> {
+    (!unbounded(bound)).then(|| bound.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::roller::evaluate_with_rng;
+    use crate::sim::simulate_seeded;
+    use crate::testing::expr;
+    use proptest::prelude::*;
+
+    fn stats(input: &str) -> Option<Stats> {
+        parse(input).unwrap().stats()
+    }
+
+    fn bounds(input: &str) -> (Option<i64>, Option<i64>) {
+        parse(input).unwrap().bounds()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_plain_roll_stats() {
+        let s = stats("2d6").unwrap();
+        assert_close(s.mean, 7.0);
+        assert_close(s.variance, 35.0 / 6.0);
+        assert_eq!((s.min, s.max), (2, 12));
+
+        let s = stats("4dF").unwrap();
+        assert_close(s.mean, 0.0);
+        assert_eq!((s.min, s.max), (-4, 4));
+    }
+
+    #[test]
+    fn test_linear_combination_stats() {
+        let s = stats("1d20 + 5").unwrap();
+        assert_close(s.mean, 15.5);
+        assert_close(s.variance, 33.25);
+
+        let s = stats("(3d6 - 1d4) * 2").unwrap();
+        assert_close(s.mean, 16.0);
+        assert_close(s.variance, 4.0 * (8.75 + 1.25));
+        assert_eq!((s.min, s.max), (-2, 34));
+    }
+
+    #[test]
+    fn test_success_stats() {
+        let s = stats("5d10>=8").unwrap();
+        assert_close(s.mean, 1.5);
+        assert_close(s.variance, 1.05);
+        assert_eq!((s.min, s.max), (0, 5));
+    }
+
+    #[test]
+    fn test_reroll_stats() {
+        // Rerolling until 3+ is uniform over 3..=6
+        let s = stats("1d6r<3").unwrap();
+        assert_close(s.mean, 4.5);
+        assert_eq!((s.min, s.max), (3, 6));
+
+        // Rerolling once: 1/3 of the time a fresh d6
+        let s = stats("1d6ro<3").unwrap();
+        assert_close(s.mean, (3.0 + 4.0 + 5.0 + 6.0) / 6.0 + 3.5 / 3.0);
+        assert_eq!((s.min, s.max), (1, 6));
+    }
+
+    #[test]
+    fn test_reroll_keep_stats() {
+        // Rerolling everything and keeping the better roll is advantage
+        assert_close(stats("1d20rb<=20").unwrap().mean, 13.825);
+        assert_close(stats("1d20rw<=20").unwrap().mean, 7.175);
+
+        // A 1 or 2 rerolls to the better of itself and a fresh d6
+        let s = stats("1d6rb<3").unwrap();
+        assert_close(s.mean, (3.0 + 4.0 + 5.0 + 6.0) / 6.0 + 3.5 / 6.0 + 22.0 / 36.0);
+        assert_eq!((s.min, s.max), (1, 6));
+        assert_eq!(bounds("1d6rw>4"), (Some(1), Some(6)));
+        assert_eq!(bounds("1d6r>4rw<3"), (Some(1), Some(4)));
+    }
+
+    #[test]
+    fn test_min_max_stats() {
+        // 1s and 2s count as 3s
+        let s = stats("2d6min3").unwrap();
+        assert_close(s.mean, 2.0 * (3.0 * 3.0 + 4.0 + 5.0 + 6.0) / 6.0);
+        assert_eq!((s.min, s.max), (6, 12));
+        assert_eq!(stats("1d20min10").unwrap().min, 10);
+        assert_close(stats("4d6max1").unwrap().mean, 4.0);
+        assert_eq!(stats("4d6min3kh3").unwrap().min, 9);
+        // Rerolling after a clamp isn't supported
+        assert!(stats("1d6min3r<4").is_none());
+        assert_eq!(bounds("1d6min3r<4"), (Some(1), Some(6)));
+        assert_eq!(bounds("2d6max4"), (Some(2), Some(8)));
+    }
+
+    #[test]
+    fn test_wild_die_bounds() {
+        assert!(stats("1d8w").is_none());
+        assert_eq!(bounds("1d8w + 2"), (Some(3), None));
+        assert_eq!(bounds("2d4w>=4"), (Some(0), Some(2)));
+    }
+
+    #[test]
+    fn test_keep_drop_stats() {
+        let s = stats("4d6kh3").unwrap();
+        assert_close(s.mean, 15869.0 / 1296.0);
+        assert_eq!((s.min, s.max), (3, 18));
+
+        let s = stats("2d20kl1").unwrap();
+        assert_close(s.mean, 7.175);
+
+        // Drop-lowest matches keep-highest of the rest
+        assert_eq!(stats("4d6dl1"), stats("4d6kh3"));
+        assert!(stats("40d20kh3").is_none());
+    }
+
+    #[test]
+    fn test_sort_and_match_stats() {
+        assert_eq!(stats("4d6sd"), stats("4d6"));
+        assert_eq!(stats("4d6m"), stats("4d6"));
+
+        // 3d6 has at most one set; it is all different with p = 120/216
+        let s = stats("3d6mt").unwrap();
+        assert_close(s.mean, 96.0 / 216.0);
+        assert_eq!((s.min, s.max), (0, 1));
+
+        assert_eq!(bounds("6d10mt3"), (Some(0), Some(2)));
+        assert_eq!(bounds("6d10!mt"), (Some(0), None));
+    }
+
+    #[test]
+    fn test_keep_and_drop_condition_stats() {
+        // Each die adds 3 to 6, each with p = 1/6
+        let s = stats("6d6d<3").unwrap();
+        assert_close(s.mean, 6.0 * 18.0 / 6.0);
+        assert_eq!((s.min, s.max), (0, 36));
+        assert_eq!(stats("6d6k>=3"), stats("6d6d<3"));
+        // Keeping by count after a condition isn't supported
+        assert!(stats("6d6d<3kh2").is_none());
+
+        assert_eq!(bounds("6d6d<3"), (Some(0), Some(36)));
+        assert_eq!(bounds("6d6k>6"), (Some(0), Some(0)));
+        assert_eq!(bounds("4d6r<3d<3"), (Some(12), Some(24)));
+    }
+
+    #[test]
+    fn test_dynamic_roll_stats() {
+        // A random sum of 1d4 d6s
+        let s = stats("(1d4)d6").unwrap();
+        assert_close(s.mean, 2.5 * 3.5);
+        assert_close(s.variance, 2.5 * 35.0 / 12.0 + 1.25 * 3.5 * 3.5);
+        assert_eq!((s.min, s.max), (1, 24));
+        assert_eq!(stats("(3)d6>=5"), stats("3d6>=5"));
+        assert!(stats("(1d4)d6kh1").is_none());
+        assert!(stats("1d(1d6)").is_none());
+        assert!(stats("@level d8").is_none());
+
+        assert_eq!(bounds("(1d4)d6kh1"), (Some(1), Some(6)));
+        assert_eq!(bounds("2d(2d4)"), (Some(2), Some(16)));
+        assert_eq!(bounds("(1d4 - 2)d6"), (Some(0), Some(12)));
+        assert_eq!(bounds("1d(1d4 + 2)!"), (Some(1), None));
+        assert_eq!(bounds("@level d8 + 1"), (Some(1), None));
+    }
+
+    #[test]
+    fn test_stats_unsupported() {
+        assert!(stats("1d6!").is_none());
+        assert!(stats("1d6 / 2").is_none());
+        assert!(stats("max(1d6, 3)").is_none());
+        assert_eq!(stats("floor(7 / 2) + 1"), Some(Stats::constant(4)));
+    }
+
+    #[test]
+    fn test_stats_match_simulation() {
+        for input in ["4d6kh3 + 1d4", "3d10r<2>=8", "2d20kh1 - 2", "6d6dh2dl2"] {
+            let s = stats(input).unwrap();
+            let sim = simulate_seeded(input, 50_000, 5).unwrap();
+            assert!((s.mean - sim.mean).abs() < 0.1, "{}: {} vs {}", input, s.mean, sim.mean);
+            assert!((s.std_dev() - sim.std_dev).abs() < 0.1, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(bounds("2d6 + 3"), (Some(5), Some(15)));
+        assert_eq!(bounds("1d6!"), (Some(1), None));
+        assert_eq!(bounds("1d6!!"), (Some(1), None));
+        assert_eq!(bounds("4d6!kh3"), (Some(3), Some(18)));
+        assert_eq!(bounds("2d6 - 1d6!"), (None, Some(11)));
+        assert_eq!(bounds("1d6r<3"), (Some(3), Some(6)));
+        assert_eq!(bounds("10d10!>=8"), (Some(10), None));
+        assert_eq!(bounds("5d10>=8"), (Some(0), Some(5)));
+        assert_eq!(bounds("4d6>0"), (Some(4), Some(4)));
+        assert_eq!(bounds("1dF!"), (Some(-1), Some(1)));
+    }
+
+    #[test]
+    fn test_bounds_of_arithmetic_and_functions() {
+        assert_eq!(bounds("max(1d6 - 2, 1)"), (Some(1), Some(4)));
+        assert_eq!(bounds("floor(1d6 / 2)"), (Some(0), Some(3)));
+        assert_eq!(bounds("100 / 1d4"), (Some(25), Some(100)));
+        assert_eq!(bounds("abs(1d6 - 4)"), (Some(0), Some(3)));
+        assert_eq!(bounds("1d6! / 2"), (Some(0), None));
+        assert_eq!(bounds("clamp(1d6!, 2, 5)"), (Some(2), Some(5)));
+        assert_eq!(bounds("floor(1 / (1 / 2))"), (None, None));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_totals_within_bounds(expr in expr(), seed in any::<u64>()) {
+            if let Ok(result) = evaluate_with_rng(&expr, &mut FastRng::with_seed(seed)) {
+                let (lo, hi) = expr.bounds();
+                prop_assert!(lo.is_none_or(|lo| lo <= result.total), "{}: {} < {:?}", expr, result.total, lo);
+                prop_assert!(hi.is_none_or(|hi| result.total <= hi), "{}: {} > {:?}", expr, result.total, hi);
+                if let Some(stats) = expr.stats() {
+                    prop_assert!(stats.min <= result.total && result.total <= stats.max, "{}", expr);
+                }
+            }
+        }
+    }
+}
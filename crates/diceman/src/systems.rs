@@ -0,0 +1,224 @@
+// ABOUTME: Presets for dice-pool systems that read more than a single total.
+// ABOUTME: Shadowrun, World of Darkness, Blades in the Dark, Savage Worlds, Fate and percentile rolls with structured outcomes.
+
+use crate::ast::{Condition, Expr, Roll};
+use crate::error::Result;
+use crate::roller::{evaluate_with_rng, Rng, RollResult};
+
+pub mod fate;
+pub mod percentile;
+pub mod savage_worlds;
+
+/// Outcome of a success-counting dice pool.
+#[derive(Debug, Clone)]
+pub struct PoolOutcome {
+    /// Net successes (hits), never negative.
+    pub successes: i64,
+    /// Whether the roll glitched (Shadowrun) or botched (World of Darkness).
+    pub glitch: bool,
+    /// Whether the glitch came with no successes at all.
+    pub critical_glitch: bool,
+    /// The underlying roll.
+    pub result: RollResult,
+}
+
+/// Roll a Shadowrun pool of `pool` d6s, counting 5s and 6s as hits.
+///
+/// The roll glitches when half or more of the dice show 1, and critically
+/// glitches when it glitches with no hits.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{systems, FastRng};
+///
+/// let outcome = systems::shadowrun(8, &mut FastRng::with_seed(1)).unwrap();
+/// assert!(outcome.result.expression.starts_with("8d6>=5["));
+/// assert!(!outcome.critical_glitch || outcome.successes == 0);
+/// ```
+pub fn shadowrun(pool: u32, rng: &mut impl Rng) -> Result<PoolOutcome> {
+    let result = roll(Roll::new(pool, 6).count_successes(Condition::ge(5)), rng)?;
+    let ones = result.dice.iter().filter(|die| die.value == 1).count();
+    let glitch = pool > 0 && ones * 2 >= pool as usize;
+    Ok(PoolOutcome {
+        successes: result.total,
+        glitch,
+        critical_glitch: glitch && result.total == 0,
+        result,
+    })
+}
+
+/// Roll a World of Darkness pool of `pool` d10s against `difficulty`.
+///
+/// Each 1 cancels a success, and with `specialty` each 10 counts twice
+/// (`10d10>=6f1dd10`). The roll botches when no die succeeds and at least one
+/// shows 1; a botch sets both `glitch` and `critical_glitch`.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{systems, FastRng};
+///
+/// let outcome = systems::world_of_darkness(6, 7, true, &mut FastRng::with_seed(1)).unwrap();
+/// assert!(outcome.result.expression.starts_with("6d10>=7f1dd10["));
+/// assert!(outcome.successes >= 0);
+/// ```
+pub fn world_of_darkness(
+    pool: u32,
+    difficulty: i64,
+    specialty: bool,
+    rng: &mut impl Rng,
+) -> Result<PoolOutcome> {
+    let mut dice = Roll::new(pool, 10)
+        .count_successes(Condition::ge(difficulty))
+        .count_failures(Condition::eq(1));
+    if specialty {
+        dice = dice.double_successes(Condition::eq(10));
+    }
+    let result = roll(dice, rng)?;
+    let hits = result.dice.iter().any(|die| die.value >= difficulty);
+    let ones = result.dice.iter().any(|die| die.value == 1);
+    let botch = !hits && ones;
+    Ok(PoolOutcome {
+        successes: result.total.max(0),
+        glitch: botch,
+        critical_glitch: botch,
+        result,
+    })
+}
+
+/// How well a Blades in the Dark action roll went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// Two or more 6s.
+    Critical,
+    /// The highest die is a 6.
+    Success,
+    /// The highest die is a 4 or 5.
+    Partial,
+    /// The highest die is 1 to 3.
+    Failure,
+}
+
+/// Outcome of a Blades in the Dark action roll.
+#[derive(Debug, Clone)]
+pub struct BladesOutcome {
+    /// The outcome tier, read from the highest die.
+    pub tier: Tier,
+    /// The underlying roll.
+    pub result: RollResult,
+}
+
+/// Roll a Blades in the Dark action with `pool` d6s, keeping the highest.
+///
+/// With no dice, two are rolled and the lowest is kept; a critical is then
+/// impossible.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{systems, FastRng};
+///
+/// let outcome = systems::blades(0, &mut FastRng::with_seed(1)).unwrap();
+/// assert!(outcome.result.expression.starts_with("2d6kl1["));
+/// assert_ne!(outcome.tier, systems::Tier::Critical);
+/// ```
+pub fn blades(pool: u32, rng: &mut impl Rng) -> Result<BladesOutcome> {
+    let (dice, desperate) = match pool {
+        0 => (Roll::new(2, 6).keep_lowest(1), true),
+        n => (Roll::new(n, 6).keep_highest(1), false),
+    };
+    let result = roll(dice, rng)?;
+    let sixes = result.dice.iter().filter(|die| die.value == 6).count();
+    let tier = match result.total {
+        6 if sixes >= 2 && !desperate => Tier::Critical,
+        6 => Tier::Success,
+        4 | 5 => Tier::Partial,
+        _ => Tier::Failure,
+    };
+    Ok(BladesOutcome { tier, result })
+}
+
+fn roll(dice: Roll, rng: &mut impl Rng) -> Result<RollResult> {
+    evaluate_with_rng(&Expr::from(dice), rng)
+}
+
+/// `dice` plus a flat modifier, written as `- 2` rather than `+ -2`.
+fn with_modifier(dice: Roll, modifier: i64) -> Expr {
+    match modifier {
+        0 => Expr::from(dice),
+        m if m < 0 && m != i64::MIN => Expr::from(dice) - -m,
+        m => Expr::from(dice) + m,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRng;
+    use crate::roller::FastRng;
+
+    #[test]
+    fn test_shadowrun_glitches() {
+        let outcome = shadowrun(4, &mut TestRng::new(&[1, 1, 1, 5])).unwrap();
+        assert_eq!(outcome.successes, 1);
+        assert!(outcome.glitch && !outcome.critical_glitch);
+
+        let outcome = shadowrun(3, &mut TestRng::new(&[1, 1, 3])).unwrap();
+        assert!(outcome.glitch && outcome.critical_glitch);
+
+        // Exactly half is a glitch, one fewer is not
+        let outcome = shadowrun(4, &mut TestRng::new(&[1, 1, 6, 6])).unwrap();
+        assert_eq!(outcome.successes, 2);
+        assert!(outcome.glitch && !outcome.critical_glitch);
+
+        let outcome = shadowrun(5, &mut TestRng::new(&[1, 1, 6, 6, 3])).unwrap();
+        assert!(!outcome.glitch);
+    }
+
+    #[test]
+    fn test_world_of_darkness() {
+        let outcome = world_of_darkness(4, 7, false, &mut TestRng::new(&[8, 10, 1, 3])).unwrap();
+        assert_eq!(outcome.successes, 1);
+        assert!(!outcome.glitch);
+
+        let outcome = world_of_darkness(4, 7, true, &mut TestRng::new(&[8, 10, 1, 3])).unwrap();
+        assert_eq!(outcome.successes, 2);
+
+        // Ones can cancel every success without botching
+        let outcome = world_of_darkness(3, 7, false, &mut TestRng::new(&[7, 1, 1])).unwrap();
+        assert_eq!(outcome.successes, 0);
+        assert!(!outcome.glitch);
+
+        let outcome = world_of_darkness(3, 7, false, &mut TestRng::new(&[2, 1, 6])).unwrap();
+        assert!(outcome.glitch && outcome.critical_glitch);
+    }
+
+    #[test]
+    fn test_blades_tiers() {
+        let cases: [(u32, &[u32], Tier); 5] = [
+            (3, &[6, 2, 6], Tier::Critical),
+            (2, &[6, 3], Tier::Success),
+            (2, &[4, 1], Tier::Partial),
+            (1, &[3], Tier::Failure),
+            (0, &[6, 6], Tier::Success),
+        ];
+        for (pool, faces, tier) in cases {
+            assert_eq!(blades(pool, &mut TestRng::new(faces)).unwrap().tier, tier, "{:?}", faces);
+        }
+    }
+
+    #[test]
+    fn test_blades_desperate_keeps_lowest() {
+        let outcome = blades(0, &mut TestRng::new(&[6, 2])).unwrap();
+        assert_eq!(outcome.result.total, 2);
+        assert_eq!(outcome.tier, Tier::Failure);
+    }
+
+    #[test]
+    fn test_outcomes_are_seeded() {
+        let a = shadowrun(12, &mut FastRng::with_seed(3)).unwrap();
+        let b = shadowrun(12, &mut FastRng::with_seed(3)).unwrap();
+        assert_eq!(a.result.expression, b.result.expression);
+    }
+}
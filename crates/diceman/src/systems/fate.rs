@@ -0,0 +1,189 @@
+// ABOUTME: Fate rolls: the adjective ladder, and 4dF plus a skill against a difficulty.
+// ABOUTME: Reports shifts and whether the action failed, tied, succeeded or succeeded with style.
+
+use std::fmt;
+
+use super::with_modifier;
+use crate::ast::Roll;
+use crate::error::Result;
+use crate::roller::{evaluate_with_config, EvalConfig, Rng, RollResult};
+
+/// The Fate Core ladder, from Terrible (-2) to Legendary (+8).
+const LADDER: [&str; 11] = [
+    "Terrible",
+    "Poor",
+    "Mediocre",
+    "Average",
+    "Fair",
+    "Good",
+    "Great",
+    "Superb",
+    "Fantastic",
+    "Epic",
+    "Legendary",
+];
+
+/// A value on the Fate ladder, displayed as `Good (+3)`.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::systems::fate::Rung;
+///
+/// assert_eq!(Rung(3).to_string(), "Good (+3)");
+/// assert_eq!(Rung(-1).name(), "Poor");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Rung(pub i64);
+
+impl Rung {
+    /// The rung's adjective. Values past either end of the ladder take the
+    /// name of that end.
+    pub fn name(self) -> &'static str {
+        LADDER[(self.0.clamp(-2, 8) + 2) as usize]
+    }
+}
+
+impl fmt::Display for Rung {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:+})", self.name(), self.0)
+    }
+}
+
+/// How an action went against its difficulty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The total fell short of the difficulty.
+    Fail,
+    /// The total matched the difficulty.
+    Tie,
+    /// The total beat the difficulty by one or two shifts.
+    Succeed,
+    /// The total beat the difficulty by three or more shifts.
+    SucceedWithStyle,
+}
+
+impl Outcome {
+    /// The outcome of an action that beat its difficulty by `shifts`.
+    pub fn from_shifts(shifts: i64) -> Self {
+        match shifts {
+            i64::MIN..=-1 => Outcome::Fail,
+            0 => Outcome::Tie,
+            1 | 2 => Outcome::Succeed,
+            _ => Outcome::SucceedWithStyle,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Outcome::Fail => "Fail",
+            Outcome::Tie => "Tie",
+            Outcome::Succeed => "Succeed",
+            Outcome::SucceedWithStyle => "Succeed with Style",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Outcome of a Fate roll against a difficulty.
+#[derive(Debug, Clone)]
+pub struct FateOutcome {
+    /// The roll's total, dice plus skill.
+    pub total: i64,
+    /// How far the total beat the difficulty; negative when it fell short.
+    pub shifts: i64,
+    /// The outcome, read from the shifts.
+    pub outcome: Outcome,
+    /// The underlying roll.
+    pub result: RollResult,
+}
+
+impl FateOutcome {
+    /// Compare any roll's total against `difficulty`, as in
+    /// `4dF+@skill vs @difficulty`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::systems::fate::{FateOutcome, Outcome};
+    ///
+    /// let result = diceman::roll("4dF + 9").unwrap();
+    /// let outcome = FateOutcome::against(result, 2);
+    /// assert_eq!(outcome.outcome, Outcome::SucceedWithStyle);
+    /// ```
+    pub fn against(result: RollResult, difficulty: i64) -> Self {
+        let shifts = result.total.saturating_sub(difficulty);
+        Self {
+            total: result.total,
+            shifts,
+            outcome: Outcome::from_shifts(shifts),
+            result,
+        }
+    }
+}
+
+/// Roll `4dF` plus `skill` against `difficulty`.
+///
+/// The dice are printed as Fate glyphs: `4dF[+ - 0 +] = 1 + 2 = 3`.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{systems::fate, FastRng};
+///
+/// let outcome = fate::action(2, 1, &mut FastRng::with_seed(1)).unwrap();
+/// assert!(outcome.result.expression.starts_with("4dF["));
+/// assert_eq!(outcome.shifts, outcome.total - 1);
+/// ```
+pub fn action(skill: i64, difficulty: i64, rng: &mut impl Rng) -> Result<FateOutcome> {
+    let config = EvalConfig {
+        fate: true,
+        ..EvalConfig::default()
+    };
+    let result = evaluate_with_config(&with_modifier(Roll::fudge(4), skill), rng, &config)?;
+    Ok(FateOutcome::against(result, difficulty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn test_ladder() {
+        assert_eq!(Rung(0).to_string(), "Mediocre (+0)");
+        assert_eq!(Rung(-2).to_string(), "Terrible (-2)");
+        assert_eq!(Rung(8).to_string(), "Legendary (+8)");
+        // Past the ends
+        assert_eq!(Rung(11).to_string(), "Legendary (+11)");
+        assert_eq!(Rung(-5).name(), "Terrible");
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let cases = [
+            (-3, Outcome::Fail),
+            (0, Outcome::Tie),
+            (2, Outcome::Succeed),
+            (3, Outcome::SucceedWithStyle),
+        ];
+        for (shifts, outcome) in cases {
+            assert_eq!(Outcome::from_shifts(shifts), outcome, "{}", shifts);
+        }
+    }
+
+    #[test]
+    fn test_action() {
+        // +, -, 0, + with Fair (+2) skill against Fair (+2)
+        let outcome = action(2, 2, &mut TestRng::new(&[3, 1, 2, 3])).unwrap();
+        assert_eq!(outcome.result.expression, "4dF[+ - 0 +] = 1 + 2 = 3");
+        assert_eq!(outcome.shifts, 1);
+        assert_eq!(outcome.outcome, Outcome::Succeed);
+
+        let outcome = action(-1, 0, &mut TestRng::new(&[2])).unwrap();
+        assert_eq!(outcome.result.expression, "4dF[0 0 0 0] = 0 - 1 = -1");
+        assert_eq!(outcome.outcome, Outcome::Fail);
+    }
+}
@@ -0,0 +1,238 @@
+// ABOUTME: Percentile roll-under checks (Call of Cthulhu, BRP) with bonus and penalty tens dice.
+// ABOUTME: Reports the tens and units dice and the success level: regular, hard, extreme, critical or fumble.
+
+use std::fmt;
+
+use super::roll;
+use crate::ast::Roll;
+use crate::error::{Error, Result};
+use crate::roller::{Rng, RollResult};
+
+/// The most bonus or penalty dice a check can take.
+pub const MAX_BONUS_DICE: u32 = 2;
+
+/// How well a percentile check went, from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SuccessLevel {
+    /// A roll of 100, or 96 and up when the skill is below 50.
+    Fumble,
+    /// Over the skill.
+    Failure,
+    /// At or under the skill.
+    Regular,
+    /// At or under half the skill.
+    Hard,
+    /// At or under a fifth of the skill.
+    Extreme,
+    /// A roll of 01.
+    Critical,
+}
+
+impl SuccessLevel {
+    /// The success level of rolling `total` (1 to 100) under `skill`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::systems::percentile::SuccessLevel;
+    ///
+    /// assert_eq!(SuccessLevel::of(30, 60), SuccessLevel::Hard);
+    /// assert_eq!(SuccessLevel::of(97, 40), SuccessLevel::Fumble);
+    /// assert_eq!(SuccessLevel::of(97, 60), SuccessLevel::Failure);
+    /// assert_eq!(SuccessLevel::of(100, 120), SuccessLevel::Fumble);
+    /// ```
+    pub fn of(total: i64, skill: i64) -> Self {
+        match total {
+            // 100 fumbles even under a skill over 100
+            100 => SuccessLevel::Fumble,
+            1 => SuccessLevel::Critical,
+            t if t <= skill / 5 => SuccessLevel::Extreme,
+            t if t <= skill / 2 => SuccessLevel::Hard,
+            t if t <= skill => SuccessLevel::Regular,
+            96..=99 if skill < 50 => SuccessLevel::Fumble,
+            _ => SuccessLevel::Failure,
+        }
+    }
+
+    /// Whether the check succeeded at any level.
+    pub fn is_success(self) -> bool {
+        self >= SuccessLevel::Regular
+    }
+}
+
+impl fmt::Display for SuccessLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SuccessLevel::Fumble => "Fumble",
+            SuccessLevel::Failure => "Failure",
+            SuccessLevel::Regular => "Regular Success",
+            SuccessLevel::Hard => "Hard Success",
+            SuccessLevel::Extreme => "Extreme Success",
+            SuccessLevel::Critical => "Critical Success",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Outcome of a percentile check.
+#[derive(Debug, Clone)]
+pub struct PercentileOutcome {
+    /// The rolled value, 1 to 100, from the kept tens die and the units die.
+    pub total: i64,
+    /// Every tens die rolled (0, 10, ..., 90): the percentile die's own,
+    /// then any bonus or penalty dice.
+    pub tens: Vec<i64>,
+    /// Which of `tens` was kept.
+    pub kept: usize,
+    /// The units die, 0 to 9.
+    pub units: i64,
+    /// The skill rolled under.
+    pub skill: i64,
+    /// The success level, read from the total and skill.
+    pub level: SuccessLevel,
+    /// The underlying `1d%` roll, before any bonus or penalty dice.
+    pub result: RollResult,
+}
+
+/// `47 (tens 40, (20); units 7) vs 60: Regular Success`, with the tens dice
+/// not kept in parentheses.
+impl fmt::Display for PercentileOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tens: Vec<String> = self
+            .tens
+            .iter()
+            .enumerate()
+            .map(|(i, tens)| if i == self.kept { format!("{:02}", tens) } else { format!("({:02})", tens) })
+            .collect();
+        write!(
+            f,
+            "{} (tens {}; units {}) vs {}: {}",
+            self.total,
+            tens.join(", "),
+            self.units,
+            self.skill,
+            self.level
+        )
+    }
+}
+
+/// Roll `d%` under `skill`, with up to two `bonus` tens dice keeping the best
+/// tens, or penalty dice keeping the worst when `bonus` is negative.
+///
+/// The percentile die is read as a tens die and a units die. Each extra tens
+/// die pairs with the same units die, and a tens and units of zero read as
+/// 100, so it is never the best.
+///
+/// Returns [`Error::InvalidDiceCount`] for more than [`MAX_BONUS_DICE`]
+/// bonus or penalty dice.
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{systems::percentile, FastRng};
+///
+/// let outcome = percentile::check(60, 1, &mut FastRng::with_seed(1)).unwrap();
+/// assert_eq!(outcome.tens.len(), 2);
+/// assert!(outcome.result.expression.starts_with("1d%["));
+/// assert_eq!(outcome.level, percentile::SuccessLevel::of(outcome.total, 60));
+/// ```
+pub fn check(skill: i64, bonus: i32, rng: &mut impl Rng) -> Result<PercentileOutcome> {
+    if bonus.unsigned_abs() > MAX_BONUS_DICE {
+        return Err(Error::InvalidDiceCount(bonus.into()));
+    }
+    let result = roll(Roll::percent(1), rng)?;
+    let units = result.total % 10;
+    let mut tens = vec![result.total % 100 / 10 * 10];
+    for _ in 0..bonus.unsigned_abs() {
+        tens.push((rng.roll(10) as i64 - 1) * 10);
+    }
+
+    let value = |tens: i64| match tens + units {
+        0 => 100,
+        value => value,
+    };
+    let candidates = tens.iter().map(|&tens| value(tens)).enumerate();
+    let (kept, total) = if bonus >= 0 {
+        candidates.min_by_key(|&(_, value)| value)
+    } else {
+        candidates.max_by_key(|&(_, value)| value)
+    }
+    .unwrap_or((0, result.total));
+
+    Ok(PercentileOutcome {
+        total,
+        tens,
+        kept,
+        units,
+        skill,
+        level: SuccessLevel::of(total, skill),
+        result,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn test_success_levels() {
+        let cases = [
+            (1, 10, SuccessLevel::Critical),
+            (12, 60, SuccessLevel::Extreme),
+            (13, 60, SuccessLevel::Hard),
+            (30, 60, SuccessLevel::Hard),
+            (60, 60, SuccessLevel::Regular),
+            (61, 60, SuccessLevel::Failure),
+            (99, 60, SuccessLevel::Failure),
+            (100, 60, SuccessLevel::Fumble),
+            (96, 49, SuccessLevel::Fumble),
+            // A skill over 100 still fumbles nothing but 100
+            (99, 120, SuccessLevel::Regular),
+            (100, 120, SuccessLevel::Fumble),
+        ];
+        for (total, skill, level) in cases {
+            assert_eq!(SuccessLevel::of(total, skill), level, "{} vs {}", total, skill);
+        }
+        assert!(SuccessLevel::Hard.is_success());
+        assert!(!SuccessLevel::Fumble.is_success());
+    }
+
+    #[test]
+    fn test_check() {
+        let outcome = check(60, 0, &mut TestRng::new(&[47])).unwrap();
+        assert_eq!(outcome.result.expression, "1d%[47 (40+7)] = 47");
+        assert_eq!((outcome.total, outcome.units), (47, 7));
+        assert_eq!(outcome.level, SuccessLevel::Regular);
+        assert_eq!(outcome.to_string(), "47 (tens 40; units 7) vs 60: Regular Success");
+    }
+
+    #[test]
+    fn test_bonus_and_penalty_dice() {
+        // 47, then a bonus tens die of 2 (a face of 3 on the d10)
+        let outcome = check(60, 1, &mut TestRng::new(&[47, 3])).unwrap();
+        assert_eq!(outcome.tens, [40, 20]);
+        assert_eq!((outcome.kept, outcome.total), (1, 27));
+        assert_eq!(outcome.level, SuccessLevel::Hard);
+        assert_eq!(outcome.to_string(), "27 (tens (40), 20; units 7) vs 60: Hard Success");
+
+        // Two penalty dice keep the worst tens
+        let outcome = check(60, -2, &mut TestRng::new(&[47, 3, 9])).unwrap();
+        assert_eq!(outcome.total, 87);
+        assert_eq!(outcome.level, SuccessLevel::Failure);
+
+        // Tens and units of zero are 100: worst for a bonus die, kept by a penalty die
+        let outcome = check(60, 1, &mut TestRng::new(&[100, 6])).unwrap();
+        assert_eq!(outcome.total, 50);
+        let outcome = check(60, -1, &mut TestRng::new(&[50, 1])).unwrap();
+        assert_eq!(outcome.total, 100);
+        assert_eq!(outcome.level, SuccessLevel::Fumble);
+
+        for bonus in [3, -3, i32::MIN] {
+            assert!(matches!(
+                check(60, bonus, &mut TestRng::new(&[47])),
+                Err(Error::InvalidDiceCount(n)) if n == i64::from(bonus)
+            ));
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// ABOUTME: Savage Worlds trait rolls: an acing trait die and wild die, keeping the higher.
+// ABOUTME: Reports success, raises and critical failures against a target number.
+
+use super::with_modifier;
+use crate::ast::Roll;
+use crate::error::Result;
+use crate::roller::{evaluate_with_rng, Rng, RollResult};
+
+/// The standard target number for a trait roll.
+pub const TARGET: i64 = 4;
+
+/// Outcome of a Savage Worlds trait roll.
+#[derive(Debug, Clone)]
+pub struct TraitOutcome {
+    /// The higher of the trait and wild die, aces included, plus the modifier.
+    pub total: i64,
+    /// Whether the total reached the standard target number of 4. Always
+    /// false on a critical failure.
+    pub success: bool,
+    /// Every full 4 points over the standard target number; 0 on a critical failure.
+    pub raises: u32,
+    /// Whether both dice came up 1 before any modifier (snake eyes).
+    pub critical_failure: bool,
+    /// The underlying roll.
+    pub result: RollResult,
+}
+
+impl TraitOutcome {
+    /// Raises against another target number, or `None` if the roll missed it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::{systems::savage_worlds, FastRng};
+    ///
+    /// let outcome = savage_worlds::trait_roll(8, 6, 0, &mut FastRng::with_seed(1)).unwrap();
+    /// assert_eq!(outcome.raises_against(savage_worlds::TARGET).unwrap_or(0), outcome.raises);
+    /// ```
+    pub fn raises_against(&self, target: i64) -> Option<u32> {
+        let over = self.total.checked_sub(target)?;
+        (over >= 0).then(|| u32::try_from(over / 4).unwrap_or(u32::MAX))
+    }
+}
+
+/// Roll a trait die with `die` sides and a wild die with `wild_die` sides,
+/// both acing, keep the higher, and add `modifier` (`1d8w6 + 1`).
+///
+/// # Examples
+///
+/// ```
+/// use diceman::{systems::savage_worlds, FastRng};
+///
+/// let outcome = savage_worlds::trait_roll(8, 6, 1, &mut FastRng::with_seed(1)).unwrap();
+/// assert!(outcome.result.expression.starts_with("1d8w6["));
+/// assert!(outcome.total >= 2);
+/// ```
+pub fn trait_roll(die: u32, wild_die: u32, modifier: i64, rng: &mut impl Rng) -> Result<TraitOutcome> {
+    let expr = with_modifier(Roll::new(1, die).wild_die(wild_die), modifier);
+    let result = evaluate_with_rng(&expr, rng)?;
+    let critical_failure = result.dice.iter().all(|die| die.rolls[0] == 1);
+    let mut outcome = TraitOutcome {
+        total: result.total,
+        success: !critical_failure && result.total >= TARGET,
+        raises: 0,
+        critical_failure,
+        result,
+    };
+    if outcome.success {
+        outcome.raises = outcome.raises_against(TARGET).unwrap_or(0);
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn test_trait_roll_keeps_higher_die() {
+        // d8 rolls 3; the wild d6 aces 6 + 6 + 1
+        let outcome = trait_roll(8, 6, 0, &mut TestRng::new(&[3, 6, 6, 1])).unwrap();
+        assert_eq!(outcome.total, 13);
+        assert!(outcome.success);
+        assert_eq!(outcome.raises, 2);
+        assert!(!outcome.critical_failure);
+    }
+
+    #[test]
+    fn test_trait_roll_modifier() {
+        let outcome = trait_roll(6, 6, -2, &mut TestRng::new(&[5, 2])).unwrap();
+        assert_eq!(outcome.total, 3);
+        assert!(!outcome.success);
+        assert_eq!(outcome.raises, 0);
+        assert_eq!(outcome.raises_against(2), Some(0));
+        assert!(outcome.result.expression.ends_with("- 2 = 3"));
+    }
+
+    #[test]
+    fn test_snake_eyes() {
+        // A modifier can't save a roll where both dice show 1
+        let outcome = trait_roll(8, 6, 8, &mut TestRng::new(&[1, 1])).unwrap();
+        // 9 would otherwise be a success with a raise
+        assert_eq!(outcome.total, 9);
+        assert!(outcome.critical_failure);
+        assert!(!outcome.success);
+        assert_eq!(outcome.raises, 0);
+    }
+}
@@ -0,0 +1,714 @@
+// ABOUTME: Random tables: entries picked by a roll over ranges or weights, for encounters and loot.
+// ABOUTME: Rolls inline expressions, nested tables and "roll again" entries; loads from TOML, JSON or CSV.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::ast::{Expr, Roll};
+use crate::error::{Error, Result};
+use crate::parser;
+use crate::roller::{evaluate_with_rng, Rng, RollResult};
+use crate::validate;
+
+/// The most rolls on tables a single [`TableSet::roll`] makes, which bounds
+/// "roll again" entries and tables that roll on each other.
+pub const MAX_TABLE_ROLLS: u32 = 100;
+
+/// What a table entry gives when it is picked.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Entry {
+    /// The result, with any inline rolls written as `[[2d4]]`.
+    pub text: String,
+    /// Another table to roll on as well.
+    pub table: Option<String>,
+    /// How many more times to roll on this entry's own table ("roll twice").
+    pub again: u32,
+}
+
+impl Entry {
+    /// An entry giving `text` (e.g., "[[2d4]] goblins").
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Self::default()
+        }
+    }
+
+    /// An entry that rolls on another table.
+    pub fn roll_on(table: impl Into<String>) -> Self {
+        Self {
+            table: Some(table.into()),
+            ..Self::default()
+        }
+    }
+
+    /// An entry that rolls `times` more times on its own table.
+    pub fn roll_again(times: u32) -> Self {
+        Self {
+            again: times,
+            ..Self::default()
+        }
+    }
+}
+
+/// A random table: a roll, and the entries its totals pick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// The name other entries use to roll on this table.
+    pub name: String,
+    /// The roll that picks an entry.
+    pub roll: Expr,
+    /// Each entry with the totals of `roll` that pick it. When ranges
+    /// overlap, the first entry wins.
+    pub entries: Vec<(RangeInclusive<i64>, Entry)>,
+}
+
+impl Table {
+    /// A table picking from `entries` with `roll`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::table::{Entry, Table};
+    /// use diceman::Roll;
+    ///
+    /// let table = Table::new(
+    ///     "weather",
+    ///     Roll::new(2, 6),
+    ///     [(2..=4, Entry::text("Storm")), (5..=12, Entry::text("Clear"))],
+    /// );
+    /// assert_eq!(table.entry(3), Some(&Entry::text("Storm")));
+    /// ```
+    pub fn new(
+        name: impl Into<String>,
+        roll: impl Into<Expr>,
+        entries: impl IntoIterator<Item = (RangeInclusive<i64>, Entry)>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            roll: roll.into(),
+            entries: entries.into_iter().collect(),
+        }
+    }
+
+    /// A table picking each entry with a chance proportional to its weight.
+    ///
+    /// The entries cover consecutive totals of one die with as many sides as
+    /// the weights add up to. Entries weighing 0 are never picked.
+    ///
+    /// Returns [`Error::InvalidTable`] if every weight is 0 or they add up to
+    /// more sides than a die can have.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::table::{Entry, Table};
+    ///
+    /// let table = Table::weighted("loot", [(3, Entry::text("Copper")), (1, Entry::text("Gold"))]).unwrap();
+    /// assert_eq!(table.roll.to_string(), "1d4");
+    /// assert_eq!(table.entry(4), Some(&Entry::text("Gold")));
+    /// ```
+    pub fn weighted(name: impl Into<String>, entries: impl IntoIterator<Item = (u32, Entry)>) -> Result<Self> {
+        let name = name.into();
+        let invalid = |message: &str| Error::InvalidTable(format!("table '{}': {}", name, message));
+        let mut next: i64 = 1;
+        let mut ranged = Vec::new();
+        for (weight, entry) in entries {
+            if weight > 0 {
+                let end = next + weight as i64 - 1;
+                ranged.push((next..=end, entry));
+                next = end + 1;
+            }
+        }
+        let sides = u32::try_from(next - 1).map_err(|_| invalid("weights add up to more than one die can roll"))?;
+        if sides == 0 {
+            return Err(invalid("has no weight to pick from"));
+        }
+        Ok(Self::new(name, Roll::new(1, sides), ranged))
+    }
+
+    /// The entry a roll of `total` picks.
+    pub fn entry(&self, total: i64) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|(range, _)| range.contains(&total))
+            .map(|(_, entry)| entry)
+    }
+}
+
+/// A set of tables that can roll on each other by name.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TableSet {
+    tables: Vec<Table>,
+}
+
+impl TableSet {
+    /// A set of `tables`. When two share a name, the first one is used.
+    pub fn new(tables: impl IntoIterator<Item = Table>) -> Self {
+        Self {
+            tables: tables.into_iter().collect(),
+        }
+    }
+
+    /// The table called `name`.
+    pub fn get(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+
+    /// Every table in the set.
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    /// Check that every table an entry rolls on exists, every inline roll
+    /// parses, and each table's roll can pick every entry and lands on one
+    /// whatever it rolls, so mistakes show up before the first roll.
+    ///
+    /// A roll with no highest or lowest total, such as an exploding one, is
+    /// only checked as far as its totals are known.
+    pub fn check(&self) -> Result<()> {
+        for table in &self.tables {
+            let invalid = |message: String| Error::InvalidTable(format!("table '{}': {}", table.name, message));
+            let (lo, hi) = table.roll.bounds();
+            for (range, _) in &table.entries {
+                let below = hi.is_some_and(|hi| *range.start() > hi);
+                let above = lo.is_some_and(|lo| *range.end() < lo);
+                if range.is_empty() || below || above {
+                    return Err(invalid(format!(
+                        "{} never rolls {}..{}",
+                        table.roll,
+                        range.start(),
+                        range.end()
+                    )));
+                }
+            }
+            if let (Some(lo), Some(hi)) = (lo, hi) {
+                if let Some(total) = validate::uncovered(table.entries.iter().map(|(range, _)| range), lo, hi) {
+                    return Err(invalid(format!("no entry for a roll of {}", total)));
+                }
+            }
+            for (_, entry) in &table.entries {
+                if let Some(nested) = &entry.table {
+                    if self.get(nested).is_none() {
+                        return Err(invalid(format!("rolls on unknown table '{}'", nested)));
+                    }
+                }
+                for piece in pieces(&entry.text).map_err(|e| invalid(e.to_string()))? {
+                    if let Piece::Roll(expr) = piece {
+                        parser::parse(expr).map_err(|e| invalid(format!("[[{}]]: {}", expr, e)))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Roll on the table called `name`, following nested tables and
+    /// "roll again" entries.
+    ///
+    /// "Roll again" results can themselves roll again; all together a roll
+    /// makes at most [`MAX_TABLE_ROLLS`] rolls on tables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::table::{Entry, Table, TableSet};
+    /// use diceman::FastRng;
+    ///
+    /// let tables = TableSet::new([
+    ///     Table::weighted("encounters", [(3, Entry::text("[[2d4]] goblins")), (1, Entry::roll_on("loot"))]).unwrap(),
+    ///     Table::weighted("loot", [(1, Entry::text("[[3d6]] gold pieces"))]).unwrap(),
+    /// ]);
+    /// let result = tables.roll("encounters", &mut FastRng::with_seed(1)).unwrap();
+    /// println!("{}", result);
+    /// ```
+    pub fn roll(&self, name: &str, rng: &mut impl Rng) -> Result<TableRoll> {
+        let mut rolls = 0;
+        self.roll_table(name, rng, &mut rolls)
+    }
+
+    fn roll_table(&self, name: &str, rng: &mut impl Rng, rolls: &mut u32) -> Result<TableRoll> {
+        *rolls += 1;
+        if *rolls > MAX_TABLE_ROLLS {
+            return Err(Error::TableLimit(MAX_TABLE_ROLLS));
+        }
+        let table = self.get(name).ok_or_else(|| Error::UnknownTable(name.to_string()))?;
+        let roll = evaluate_with_rng(&table.roll, rng)?;
+        let entry = table.entry(roll.total).ok_or_else(|| Error::NoTableEntry {
+            table: name.to_string(),
+            roll: roll.total,
+        })?;
+
+        let mut text = String::new();
+        let mut inline = Vec::new();
+        for piece in pieces(&entry.text)? {
+            match piece {
+                Piece::Text(literal) => text.push_str(literal),
+                Piece::Roll(expr) => {
+                    let result = evaluate_with_rng(&parser::parse(expr)?, rng)?;
+                    text.push_str(&result.total.to_string());
+                    inline.push(result);
+                }
+            }
+        }
+
+        let mut nested = Vec::new();
+        if let Some(other) = &entry.table {
+            nested.push(self.roll_table(other, rng, rolls)?);
+        }
+        for _ in 0..entry.again {
+            nested.push(self.roll_table(name, rng, rolls)?);
+        }
+        Ok(TableRoll {
+            table: name.to_string(),
+            roll,
+            text,
+            inline,
+            nested,
+        })
+    }
+}
+
+/// The result of rolling on a table.
+#[derive(Debug, Clone)]
+pub struct TableRoll {
+    /// The table rolled on.
+    pub table: String,
+    /// The roll that picked the entry.
+    pub roll: RollResult,
+    /// The entry's text, with each inline roll replaced by its total.
+    pub text: String,
+    /// The entry's inline rolls, in order.
+    pub inline: Vec<RollResult>,
+    /// Rolls on the entry's nested table, then any extra rolls on this one.
+    pub nested: Vec<TableRoll>,
+}
+
+impl TableRoll {
+    /// Every result's text, depth first, skipping entries with none.
+    pub fn texts(&self) -> Vec<&str> {
+        let mut texts = Vec::new();
+        if !self.text.is_empty() {
+            texts.push(self.text.as_str());
+        }
+        for nested in &self.nested {
+            texts.extend(nested.texts());
+        }
+        texts
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(f, "{:indent$}{}: {}", "", self.table, self.roll.expression, indent = depth * 2)?;
+        if !self.text.is_empty() {
+            write!(f, " -> {}", self.text)?;
+        }
+        for nested in &self.nested {
+            writeln!(f)?;
+            nested.write(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// One line per table rolled on, with nested rolls indented under the
+/// entry that made them: `encounters: 1d4[4] = 4 -> A chest`.
+impl fmt::Display for TableRoll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// A piece of an entry's text.
+enum Piece<'a> {
+    Text(&'a str),
+    /// The expression inside `[[...]]`.
+    Roll(&'a str),
+}
+
+/// Split an entry's text into literal text and `[[expr]]` inline rolls.
+fn pieces(text: &str) -> Result<Vec<Piece<'_>>> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let len = rest[start + 2..].find("]]").ok_or_else(|| Error::Expected {
+            expected: "']]' to close an inline roll".to_string(),
+            found: rest[start..].to_string(),
+        })?;
+        pieces.push(Piece::Text(&rest[..start]));
+        pieces.push(Piece::Roll(&rest[start + 2..start + 2 + len]));
+        rest = &rest[start + 2 + len + 2..];
+    }
+    pieces.push(Piece::Text(rest));
+    Ok(pieces)
+}
+
+#[cfg(any(feature = "toml", feature = "json", feature = "csv"))]
+mod load {
+    use super::{Entry, Table};
+    use crate::ast::Roll;
+    use crate::error::{Error, Result};
+    use crate::parser;
+    use serde::Deserialize;
+    #[cfg(any(feature = "toml", feature = "json"))]
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::ops::RangeInclusive;
+
+    /// A table as written in a file.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct RawTable {
+        roll: Option<String>,
+        entries: Vec<RawEntry>,
+    }
+
+    /// An entry as written in a file: a range or a weight, and what it gives.
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct RawEntry {
+        range: Option<RawRange>,
+        weight: Option<u32>,
+        #[serde(default)]
+        text: String,
+        table: Option<String>,
+        again: Option<u32>,
+    }
+
+    /// A range written as a single number or as "1-8" / "1..8".
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawRange {
+        Number(i64),
+        Text(String),
+    }
+
+    impl RawRange {
+        fn parse(&self) -> Option<RangeInclusive<i64>> {
+            let text = match self {
+                RawRange::Number(n) => return Some(*n..=*n),
+                RawRange::Text(text) => text.trim(),
+            };
+            // Skip the first character so a leading minus isn't read as the separator
+            let dash = text.get(1..).and_then(|rest| rest.find('-')).map(|i| (i + 1, 1));
+            let split = text.find("..").map(|i| (i, 2)).or(dash);
+            let (start, end) = match split {
+                Some((i, len)) => (text[..i].trim().parse().ok()?, text[i + len..].trim().parse().ok()?),
+                None => {
+                    let n = text.parse().ok()?;
+                    (n, n)
+                }
+            };
+            (start <= end).then_some(start..=end)
+        }
+    }
+
+    impl fmt::Display for RawRange {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                RawRange::Number(n) => write!(f, "{}", n),
+                RawRange::Text(text) => write!(f, "{}", text),
+            }
+        }
+    }
+
+    /// Build every table in a file, keyed by name, and check them together.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    pub(super) fn tables(raw: BTreeMap<String, RawTable>) -> Result<super::TableSet> {
+        let tables = raw
+            .into_iter()
+            .map(|(name, table)| build(&name, table.roll.as_deref(), table.entries))
+            .collect::<Result<Vec<_>>>()?;
+        let set = super::TableSet::new(tables);
+        set.check()?;
+        Ok(set)
+    }
+
+    /// Build a table whose entries all have ranges or all have weights.
+    pub(super) fn build(name: &str, roll: Option<&str>, entries: Vec<RawEntry>) -> Result<Table> {
+        let invalid = |message: String| Error::InvalidTable(format!("table '{}': {}", name, message));
+        let Some(first) = entries.first() else {
+            return Err(invalid("has no entries".to_string()));
+        };
+        let weighted = first.weight.is_some();
+
+        let mut ranged = Vec::new();
+        let mut weights = Vec::new();
+        for (i, raw) in entries.into_iter().enumerate() {
+            let entry = Entry {
+                text: raw.text,
+                table: raw.table,
+                again: raw.again.unwrap_or(0),
+            };
+            match (raw.range, raw.weight) {
+                (Some(range), None) if !weighted => {
+                    let parsed = range
+                        .parse()
+                        .ok_or_else(|| invalid(format!("entry {} has an invalid range '{}'", i + 1, range)))?;
+                    ranged.push((parsed, entry));
+                }
+                (None, Some(weight)) if weighted => weights.push((weight, entry)),
+                (None, None) => return Err(invalid(format!("entry {} needs a range or a weight", i + 1))),
+                _ => return Err(invalid("mixes ranges and weights".to_string())),
+            }
+        }
+
+        if weighted {
+            if roll.is_some() {
+                return Err(invalid("weighted entries pick their own roll, so it can't set one".to_string()));
+            }
+            return Table::weighted(name, weights);
+        }
+        let roll = match roll {
+            Some(roll) => parser::parse(roll).map_err(|e| invalid(format!("roll '{}': {}", roll, e)))?,
+            // Without a roll, one die covers every range
+            None => {
+                let highest = ranged.iter().map(|(range, _)| *range.end()).max().unwrap_or(0);
+                let sides = u32::try_from(highest)
+                    .ok()
+                    .filter(|&sides| sides > 0)
+                    .ok_or_else(|| invalid("needs a roll, as its ranges don't fit one die".to_string()))?;
+                Roll::new(1, sides).into()
+            }
+        };
+        Ok(Table::new(name, roll, ranged))
+    }
+}
+
+impl TableSet {
+    /// Load tables from TOML, one table per top-level key.
+    ///
+    /// Each table has a list of `entries`, all with a `range` (a number or
+    /// `"1-8"`) or all with a `weight`, plus any of `text`, `table` (another
+    /// table to roll on) and `again` (extra rolls on this table). Ranged
+    /// tables may set the `roll` that picks an entry; it defaults to one die
+    /// covering the highest range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diceman::table::TableSet;
+    ///
+    /// let tables = TableSet::from_toml(r#"
+    ///     [encounters]
+    ///     roll = "1d20"
+    ///     entries = [
+    ///         { range = "1-12", text = "[[2d4]] goblins" },
+    ///         { range = "13-19", text = "A merchant", table = "loot" },
+    ///         { range = 20, again = 2 },
+    ///     ]
+    ///
+    ///     [loot]
+    ///     entries = [{ weight = 3, text = "[[3d6]] gold" }, { weight = 1, text = "A potion" }]
+    /// "#).unwrap();
+    /// assert_eq!(tables.tables().len(), 2);
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(source: &str) -> Result<Self> {
+        let raw = toml::from_str(source).map_err(|e| Error::InvalidTable(e.to_string()))?;
+        load::tables(raw)
+    }
+
+    /// Load tables from JSON: an object with one table per key, laid out
+    /// like [`TableSet::from_toml`].
+    #[cfg(feature = "json")]
+    pub fn from_json(source: &str) -> Result<Self> {
+        let raw = serde_json::from_str(source).map_err(|e| Error::InvalidTable(e.to_string()))?;
+        load::tables(raw)
+    }
+
+    /// Load a single table called `name` from CSV, one entry per row, with
+    /// a header naming the `range` or `weight`, `text`, `table` and `again`
+    /// columns it uses. The roll is one die covering the highest range.
+    #[cfg(feature = "csv")]
+    pub fn from_csv(name: &str, source: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(source.as_bytes());
+        let entries = reader
+            .deserialize()
+            .collect::<std::result::Result<Vec<load::RawEntry>, _>>()
+            .map_err(|e| Error::InvalidTable(format!("table '{}': {}", name, e)))?;
+        let set = TableSet::new([load::build(name, None, entries)?]);
+        set.check()?;
+        Ok(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRng;
+
+    fn encounters() -> TableSet {
+        TableSet::new([
+            Table::new(
+                "encounters",
+                Roll::new(1, 6),
+                [
+                    (1..=3, Entry::text("[[2d4]] goblins")),
+                    (4..=5, Entry {
+                        text: "A merchant".to_string(),
+                        table: Some("loot".to_string()),
+                        again: 0,
+                    }),
+                    (6..=6, Entry::roll_again(2)),
+                ],
+            ),
+            Table::weighted("loot", [(3, Entry::text("[[1d6]] gold")), (1, Entry::text("A potion"))]).unwrap(),
+        ])
+    }
+
+    #[test]
+    fn test_roll_inline() {
+        let result = encounters().roll("encounters", &mut TestRng::new(&[2, 3, 4])).unwrap();
+        assert_eq!(result.text, "7 goblins");
+        assert_eq!(result.inline.len(), 1);
+        assert_eq!(result.to_string(), "encounters: 1d6[2] = 2 -> 7 goblins");
+    }
+
+    #[test]
+    fn test_roll_nested_table() {
+        // The merchant, then a 4 on the d4 loot table
+        let result = encounters().roll("encounters", &mut TestRng::new(&[5, 4])).unwrap();
+        assert_eq!(result.texts(), ["A merchant", "A potion"]);
+        assert_eq!(
+            result.to_string(),
+            "encounters: 1d6[5] = 5 -> A merchant\n  loot: 1d4[4] = 4 -> A potion"
+        );
+    }
+
+    #[test]
+    fn test_roll_again() {
+        // A 6 rolls twice more: goblins (1, then 1 + 1 on 2d4), then the merchant and a potion
+        let result = encounters().roll("encounters", &mut TestRng::new(&[6, 1, 1, 1, 5, 4])).unwrap();
+        assert_eq!(result.text, "");
+        assert_eq!(result.texts(), ["2 goblins", "A merchant", "A potion"]);
+
+        // Always rolling again runs into the limit
+        let tables = TableSet::new([Table::new("loop", Roll::new(1, 1), [(1..=1, Entry::roll_again(1))])]);
+        assert!(matches!(tables.roll("loop", &mut TestRng::new(&[1])), Err(Error::TableLimit(100))));
+    }
+
+    #[test]
+    fn test_roll_errors() {
+        let tables = encounters();
+        assert!(matches!(tables.roll("treasure", &mut TestRng::new(&[1])), Err(Error::UnknownTable(name)) if name == "treasure"));
+
+        let tables = TableSet::new([Table::new("gaps", Roll::new(1, 6), [(1..=3, Entry::text("Low"))])]);
+        assert!(matches!(
+            tables.roll("gaps", &mut TestRng::new(&[5])),
+            Err(Error::NoTableEntry { roll: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn test_weighted() {
+        let table =
+            Table::weighted("w", [(2, Entry::text("a")), (0, Entry::text("never")), (1, Entry::text("b"))]).unwrap();
+        assert_eq!(table.roll.to_string(), "1d3");
+        assert_eq!(table.entries.len(), 2);
+        assert_eq!(table.entry(3), Some(&Entry::text("b")));
+
+        let invalid = |weights: &[u32]| {
+            let entries = weights.iter().map(|&weight| (weight, Entry::text("x")));
+            Table::weighted("w", entries).unwrap_err().to_string()
+        };
+        assert!(invalid(&[0, 0]).contains("has no weight"));
+        assert!(invalid(&[]).contains("has no weight"));
+        assert!(invalid(&[u32::MAX, 1]).contains("more than one die can roll"));
+        assert!(Table::weighted("w", [(u32::MAX, Entry::text("x"))]).is_ok());
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(encounters().check().is_ok());
+
+        let tables = TableSet::new([Table::weighted("a", [(1, Entry::roll_on("b"))]).unwrap()]);
+        assert!(tables.check().unwrap_err().to_string().contains("unknown table 'b'"));
+
+        let tables = TableSet::new([Table::weighted("a", [(1, Entry::text("[[2d]] bats"))]).unwrap()]);
+        assert!(tables.check().is_err());
+        let tables = TableSet::new([Table::weighted("a", [(1, Entry::text("[[2d4 bats"))]).unwrap()]);
+        assert!(tables.check().unwrap_err().to_string().contains("']]'"));
+
+        // Every total the roll can make needs an entry, and every entry a total
+        let check = |roll: &str, ranges: &[RangeInclusive<i64>]| {
+            let entries = ranges.iter().map(|range| (range.clone(), Entry::text("x")));
+            let table = Table::new("t", parser::parse(roll).unwrap(), entries);
+            TableSet::new([table]).check().map_err(|e| e.to_string())
+        };
+        assert!(check("2d6", &[2..=6, 7..=12]).is_ok());
+        assert!(check("1d6", &[1..=2, 4..=6]).unwrap_err().contains("no entry for a roll of 3"));
+        assert!(check("1d6", &[2..=6]).unwrap_err().contains("no entry for a roll of 1"));
+        assert!(check("1d6", &[1..=5]).unwrap_err().contains("no entry for a roll of 6"));
+        assert!(check("1d6", &[1..=6, 7..=8]).unwrap_err().contains("1d6 never rolls 7..8"));
+        assert!(check("2d6", &[1..=1, 2..=12]).unwrap_err().contains("2d6 never rolls 1..1"));
+        #[allow(clippy::reversed_empty_ranges)]
+        let backwards = check("1d6", &[1..=6, 5..=4]);
+        assert!(backwards.unwrap_err().contains("never rolls 5..4"));
+        // Exploding rolls have no highest total, so an open end isn't a gap
+        assert!(check("1d6!", &[1..=6]).is_ok());
+        assert!(check("1d6!", &[0..=0, 1..=6]).unwrap_err().contains("never rolls 0..0"));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_from_toml() {
+        let tables = TableSet::from_toml(
+            r#"
+            [encounters]
+            roll = "2d6"
+            entries = [
+                { range = "2-6", text = "Wolves" },
+                { range = "7..11", table = "loot" },
+                { range = 12, again = 2 },
+            ]
+
+            [loot]
+            entries = [{ weight = 3, text = "Copper" }, { weight = 1, text = "Gold" }]
+            "#,
+        )
+        .unwrap();
+        let encounters = tables.get("encounters").unwrap();
+        assert_eq!(encounters.roll.to_string(), "2d6");
+        assert_eq!(encounters.entries[1], (7..=11, Entry::roll_on("loot")));
+        assert_eq!(encounters.entries[2].1.again, 2);
+        assert_eq!(tables.get("loot").unwrap().roll.to_string(), "1d4");
+
+        // Ranges default to one die covering the highest
+        let tables = TableSet::from_toml("[t]\nentries = [{ range = \"1-3\", text = \"a\" }, { range = \"4-8\", text = \"b\" }]").unwrap();
+        assert_eq!(tables.get("t").unwrap().roll.to_string(), "1d8");
+
+        let invalid = |source: &str| TableSet::from_toml(source).unwrap_err().to_string();
+        assert!(invalid("[t]\nentries = [{ range = 1 }, { weight = 1 }]").contains("mixes ranges and weights"));
+        assert!(invalid("[t]\nentries = [{ range = \"3-1\" }]").contains("invalid range '3-1'"));
+        assert!(invalid("[t]\nroll = \"1d6\"\nentries = [{ weight = 1 }]").contains("can't set one"));
+        assert!(invalid("[t]\nentries = [{ range = 1, table = \"u\" }]").contains("unknown table 'u'"));
+        assert!(invalid("[t]\nentries = [{ range = 1, colour = \"red\" }]").contains("unknown field"));
+        assert!(invalid("[t]\nentries = []").contains("has no entries"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_json() {
+        let tables =
+            TableSet::from_json(r#"{"loot": {"entries": [{"weight": 1, "text": "[[2d6]] silver"}, {"weight": 1, "again": 2}]}}"#)
+                .unwrap();
+        let loot = tables.get("loot").unwrap();
+        assert_eq!(loot.roll.to_string(), "1d2");
+        assert_eq!(loot.entries[1].1, Entry::roll_again(2));
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_from_csv() {
+        let tables = TableSet::from_csv("weather", "range,text\n1-4, Rain\n5-6,\"Sun, at last\"\n").unwrap();
+        let weather = tables.get("weather").unwrap();
+        assert_eq!(weather.roll.to_string(), "1d6");
+        assert_eq!(weather.entries[1], (5..=6, Entry::text("Sun, at last")));
+
+        let tables = TableSet::from_csv("loot", "weight,text,again\n5,Copper,\n1,,2\n").unwrap();
+        assert_eq!(tables.get("loot").unwrap().entries[1], (6..=6, Entry::roll_again(2)));
+    }
+}
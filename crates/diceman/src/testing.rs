@@ -0,0 +1,197 @@
+// ABOUTME: Shared test helpers: a scripted RNG and proptest strategies for dice expressions.
+// ABOUTME: Produces arbitrary ASTs with small counts and sides so they roll quickly.
+
+use crate::ast::{Band, Compare, Condition, DynamicRoll, DynamicSides, Expr, Func, Modifier, Op, Roll, Sides};
+use crate::roller::Rng;
+use proptest::prelude::*;
+
+/// A deterministic RNG that hands out the given rolls in order, cycling when
+/// it runs out.
+pub(crate) struct TestRng {
+    values: Vec<u32>,
+    index: usize,
+}
+
+impl TestRng {
+    pub(crate) fn new(values: &[u32]) -> Self {
+        Self {
+            values: values.to_vec(),
+            index: 0,
+        }
+    }
+}
+
+impl Rng for TestRng {
+    fn roll(&mut self, _max: u32) -> u32 {
+        let value = self.values[self.index % self.values.len()];
+        self.index += 1;
+        value
+    }
+}
+
+pub(crate) fn condition() -> impl Strategy<Value = Condition> {
+    let compare = prop_oneof![
+        Just(Compare::Equal),
+        Just(Compare::NotEqual),
+        Just(Compare::LessThan),
+        Just(Compare::LessOrEqual),
+        Just(Compare::GreaterThan),
+        Just(Compare::GreaterOrEqual),
+    ];
+    (compare, 0..20i64).prop_map(|(compare, value)| Condition { compare, value })
+}
+
+pub(crate) fn modifier() -> impl Strategy<Value = Modifier> {
+    prop_oneof![
+        (0..5u32).prop_map(Modifier::KeepHighest),
+        (0..5u32).prop_map(Modifier::KeepLowest),
+        (0..5u32).prop_map(Modifier::DropHighest),
+        (0..5u32).prop_map(Modifier::DropLowest),
+        condition().prop_map(Modifier::KeepWhere),
+        condition().prop_map(Modifier::DropWhere),
+        (any::<bool>(), any::<bool>(), proptest::option::of(condition())).prop_map(
+            |(compounding, penetrating, condition)| Modifier::Explode {
+                compounding,
+                penetrating,
+                condition,
+            }
+        ),
+        (any::<bool>(), proptest::option::of(condition()))
+            .prop_map(|(once, condition)| Modifier::Reroll { once, condition }),
+        (any::<bool>(), proptest::option::of(condition()))
+            .prop_map(|(best, condition)| Modifier::RerollKeep { best, condition }),
+        (0..8u32).prop_map(Modifier::WildDie),
+        (-2..8i64).prop_map(Modifier::Min),
+        (-2..8i64).prop_map(Modifier::Max),
+        condition().prop_map(Modifier::CountSuccesses),
+        any::<bool>().prop_map(|descending| Modifier::Sort { descending }),
+        (0..4u32, any::<bool>()).prop_map(|(width, count_sets)| Modifier::Matches { width, count_sets }),
+    ]
+}
+
+pub(crate) fn roll() -> impl Strategy<Value = Roll> {
+    let sides = prop_oneof![
+        (1..30u32).prop_map(Sides::Number),
+        Just(Sides::Percent),
+        Just(Sides::Fudge),
+    ];
+    // Failure and double conditions only parse straight after a success condition
+    let scoring = (
+        condition(),
+        proptest::option::of(condition()),
+        proptest::option::of(condition()),
+    )
+        .prop_map(|(success, failure, double)| {
+            let mut modifiers = vec![Modifier::CountSuccesses(success)];
+            modifiers.extend(failure.map(Modifier::CountFailures));
+            modifiers.extend(double.map(Modifier::DoubleSuccesses));
+            modifiers
+        });
+    (
+        0..6u32,
+        sides,
+        proptest::collection::vec(modifier(), 0..3),
+        proptest::option::of(scoring),
+    )
+        .prop_map(|(count, sides, mut modifiers, scoring)| {
+            modifiers.extend(scoring.into_iter().flatten());
+            Roll {
+                count,
+                sides,
+                modifiers,
+            }
+        })
+}
+
+/// A dynamic roll's count or sides: small literals (some of them failing),
+/// small rolls, and unbound variables.
+fn dynamic_operand() -> impl Strategy<Value = Expr> {
+    prop_oneof![
+        (-1..5i64).prop_map(Expr::Number),
+        (0..3u32, 1..5u32).prop_map(|(count, sides)| Expr::Group(Box::new(Roll::new(count, sides).into()))),
+        "[a-z][a-z0-9_]{0,3}".prop_map(Expr::Var),
+    ]
+}
+
+pub(crate) fn dynamic_roll() -> impl Strategy<Value = DynamicRoll> {
+    (dynamic_operand(), proptest::option::of(dynamic_operand()), roll()).prop_map(|(count, sides, roll)| DynamicRoll {
+        count: Box::new(count),
+        sides: match sides {
+            Some(sides) => DynamicSides::Expr(Box::new(sides)),
+            None => DynamicSides::Fixed(roll.sides),
+        },
+        modifiers: roll.modifiers,
+    })
+}
+
+/// An outcome band over small totals, labelled with words and spaces or,
+/// now and then, text that has to be quoted.
+fn band() -> impl Strategy<Value = Band> {
+    let range = prop_oneof![
+        (-20..20i64).prop_map(|n| i64::MIN..=n),
+        (-20..20i64).prop_map(|n| n..=i64::MAX),
+        (-20..20i64, 0..10i64).prop_map(|(start, len)| start..=start + len),
+    ];
+    let label = prop_oneof![
+        4 => "[a-z]([a-z ]{0,6}[a-z])?",
+        1 => "[a-z ,{}\"\\\\]{0,6}",
+    ];
+    (range, label).prop_map(|(range, label)| Band::new(range, label))
+}
+
+/// An arbitrary expression, sometimes with an outcome table at the top or
+/// nested inside.
+pub(crate) fn expr() -> impl Strategy<Value = Expr> {
+    let bands = proptest::option::weighted(0.2, proptest::collection::vec(band(), 1..4));
+    (arithmetic(), bands).prop_map(|(expr, bands)| match bands {
+        Some(bands) => Expr::Bands {
+            expr: Box::new(expr),
+            bands,
+        },
+        None => expr,
+    })
+}
+
+fn arithmetic() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        prop_oneof![
+            4 => 0..1000i64,
+            1 => any::<i64>(),
+            1 => prop_oneof![Just(i64::MIN), Just(i64::MAX), Just(u32::MAX as i64 + 1)],
+        ]
+        .prop_map(Expr::Number),
+        roll().prop_map(Expr::Roll),
+        dynamic_roll().prop_map(Expr::DynamicRoll),
+    ];
+    leaf.prop_recursive(4, 24, 3, |inner| {
+        let op = prop_oneof![
+            Just(Op::Add),
+            Just(Op::Sub),
+            Just(Op::Mul),
+            Just(Op::Div),
+            Just(Op::FloorDiv),
+        ];
+        let func = prop_oneof![
+            Just(Func::Floor),
+            Just(Func::Ceil),
+            Just(Func::Round),
+            Just(Func::Min),
+            Just(Func::Max),
+            Just(Func::Abs),
+            Just(Func::Clamp),
+        ];
+        prop_oneof![
+            (op, inner.clone(), inner.clone()).prop_map(|(op, left, right)| Expr::BinOp {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            }),
+            inner.clone().prop_map(|e| Expr::Group(Box::new(e))),
+            (inner.clone(), proptest::collection::vec(band(), 1..3)).prop_map(|(e, bands)| e.bands(bands)),
+            (func, proptest::collection::vec(inner, 3)).prop_map(|(func, mut args)| {
+                args.truncate(*func.arity().start());
+                Expr::Call { func, args }
+            }),
+        ]
+    })
+}
@@ -0,0 +1,696 @@
+// ABOUTME: Static validation pass for parsed dice expressions.
+// ABOUTME: Flags impossible or infinite conditions and no-op modifiers before rolling.
+
+use crate::ast::{Band, Compare, Condition, DynamicRoll, DynamicSides, Expr, Modifier, Op, Roll, Sides};
+use crate::roller::stage;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The expression rolls, but part of it does nothing or always does the same thing.
+    Warning,
+    /// The expression can never roll successfully.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in an expression by [`validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Whether this is a warning or an error.
+    pub severity: Severity,
+    /// A human-readable description of the problem.
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn warning(message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+
+    fn error(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    /// Returns true if this diagnostic is an error.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Check an expression for problems without rolling it.
+///
+/// Errors are expressions that always fail at roll time (e.g. `1d1!` explodes
+/// forever). Warnings are modifiers that can never take effect or always take
+/// effect (e.g. `3d6kh5` keeps every die).
+///
+/// # Examples
+///
+/// ```
+/// let expr = diceman::parse("1d6r<=6").unwrap();
+/// let diagnostics = diceman::validate(&expr);
+/// assert!(diagnostics[0].is_error());
+/// ```
+pub fn validate(expr: &Expr) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check_expr(expr, &mut diagnostics);
+    diagnostics
+}
+
+fn check_expr(expr: &Expr, out: &mut Vec<Diagnostic>) {
+    match expr {
+        Expr::Number(_) | Expr::Var(_) => {}
+        Expr::Roll(roll) => check_roll(roll, out),
+        Expr::DynamicRoll(roll) => check_dynamic_roll(roll, out),
+        Expr::BinOp { op, left, right } => {
+            check_expr(left, out);
+            check_expr(right, out);
+            if matches!(op, Op::Div | Op::FloorDiv) && is_zero(right) {
+                out.push(Diagnostic::error("division by zero".to_string()));
+            }
+        }
+        Expr::Group(inner) => check_expr(inner, out),
+        Expr::Call { args, .. } => {
+            for arg in args {
+                check_expr(arg, out);
+            }
+        }
+        Expr::Bands { expr, bands } => {
+            check_expr(expr, out);
+            check_bands(expr, bands, out);
+        }
+    }
+}
+
+/// Check an outcome table against the totals its expression can roll.
+fn check_bands(expr: &Expr, bands: &[Band], out: &mut Vec<Diagnostic>) {
+    let (lo, hi) = expr.bounds();
+    let (lo, hi) = (lo.unwrap_or(i64::MIN), hi.unwrap_or(i64::MAX));
+    for (i, band) in bands.iter().enumerate() {
+        let (start, end) = (*band.range.start(), *band.range.end());
+        if start > end {
+            out.push(Diagnostic::warning(format!("band '{}' covers no totals", band)));
+            continue;
+        }
+        if end < lo {
+            out.push(Diagnostic::warning(format!(
+                "band '{}' never matches: {} never rolls below {}",
+                band, expr, lo
+            )));
+        } else if start > hi {
+            out.push(Diagnostic::warning(format!(
+                "band '{}' never matches: {} never rolls above {}",
+                band, expr, hi
+            )));
+        }
+        let earlier = bands[..i]
+            .iter()
+            .find(|e| *e.range.start() <= end && start <= *e.range.end());
+        if let Some(earlier) = earlier {
+            out.push(Diagnostic::warning(format!(
+                "band '{}' overlaps '{}', which comes first and wins",
+                band, earlier
+            )));
+        }
+    }
+    if let Some(total) = uncovered(bands.iter().map(|band| &band.range), lo, hi) {
+        out.push(Diagnostic::warning(format!("no band covers a total of {}", total)));
+    }
+}
+
+/// The lowest total from `lo` to `hi` that none of `ranges` covers. Also
+/// used for the entries of random tables.
+pub(crate) fn uncovered<'a>(
+    ranges: impl Iterator<Item = &'a RangeInclusive<i64>> + Clone,
+    lo: i64,
+    hi: i64,
+) -> Option<i64> {
+    let mut next = lo;
+    loop {
+        match ranges.clone().find(|range| range.contains(&next)) {
+            Some(range) if *range.end() >= hi => return None,
+            Some(range) => next = range.end() + 1,
+            None => return Some(next),
+        }
+    }
+}
+
+fn is_zero(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(n) => *n == 0,
+        Expr::Group(inner) => is_zero(inner),
+        _ => false,
+    }
+}
+
+/// Check that a dynamic roll's count can't be negative and its dice can't
+/// have fewer than one side, then check its modifiers as far as the count
+/// and sides are known. Unbounded counts and sides, such as variables,
+/// aren't flagged.
+fn check_dynamic_roll(roll: &DynamicRoll, out: &mut Vec<Diagnostic>) {
+    check_expr(&roll.count, out);
+    let name = roll.to_string();
+    let (lo, hi) = roll.count.bounds();
+    match (lo, hi) {
+        (_, Some(hi)) if hi < 0 => out.push(Diagnostic::error(format!(
+            "{}: dice count is always negative, so the roll always fails",
+            name
+        ))),
+        (Some(lo), _) if lo < 0 => out.push(Diagnostic::warning(format!(
+            "{}: dice count can be negative ({}), which fails the roll",
+            name, lo
+        ))),
+        _ => {}
+    }
+    let count = match (lo, hi) {
+        (Some(lo), Some(hi)) if lo == hi => u32::try_from(lo).ok(),
+        _ => None,
+    };
+
+    let sides = match &roll.sides {
+        DynamicSides::Fixed(sides) => Some(*sides),
+        DynamicSides::Expr(sides) => {
+            check_expr(sides, out);
+            let (lo, hi) = sides.bounds();
+            match (lo, hi) {
+                (_, Some(hi)) if hi < 1 => out.push(Diagnostic::error(format!(
+                    "{}: dice must have at least one side, but never do",
+                    name
+                ))),
+                (Some(lo), _) if lo < 1 => out.push(Diagnostic::warning(format!(
+                    "{}: dice can have {} sides, which fails the roll",
+                    name, lo
+                ))),
+                _ => {}
+            }
+            match (lo, hi) {
+                (Some(lo), Some(hi)) if lo == hi && lo > 0 => u32::try_from(lo).ok().map(Sides::Number),
+                _ => None,
+            }
+        }
+    };
+
+    check_modifiers(&name, &roll.modifiers, count, sides.as_ref(), out);
+}
+
+fn check_roll(roll: &Roll, out: &mut Vec<Diagnostic>) {
+    let name = format!("{}d{}", roll.count, roll.sides);
+
+    if roll.sides == Sides::Number(0) {
+        out.push(Diagnostic::error(format!("{}: dice must have at least one side", name)));
+        return;
+    }
+    if roll.count == 0 {
+        out.push(Diagnostic::warning(format!("{}: rolls no dice, so always totals 0", name)));
+    }
+    check_modifiers(&name, &roll.modifiers, Some(roll.count), Some(&roll.sides), out);
+}
+
+/// Check a roll's modifiers. `count` and `sides` are `None` when they are
+/// rolled, which skips the checks that depend on them.
+fn check_modifiers(
+    name: &str,
+    modifiers: &[Modifier],
+    count: Option<u32>,
+    sides: Option<&Sides>,
+    out: &mut Vec<Diagnostic>,
+) {
+    let faces = sides.map(Sides::faces);
+
+    // Standard explosions add dice, so keep/drop counts can't be checked statically
+    let adds_dice = modifiers.iter().any(|m| matches!(m, Modifier::Explode { compounding: false, .. }));
+
+    let mut counts_successes = false;
+    let mut sorts = false;
+    // Explosions can push dice past the die's faces
+    let mut exploded = false;
+    let mut wild = false;
+    for modifier in modifiers {
+        if wild && matches!(modifier, Modifier::Reroll { .. } | Modifier::RerollKeep { .. } | Modifier::Explode { .. }) {
+            let die = sides.map_or("one of the roll's dice".to_string(), |sides| format!("a d{}", sides));
+            out.push(Diagnostic::warning(format!(
+                "{}: {} comes after the wild die, so it rolls the wild die as {}",
+                name, modifier, die
+            )));
+        }
+        match modifier {
+            Modifier::WildDie(0) => out.push(Diagnostic::error(format!(
+                "{}: the wild die must have at least one side",
+                name
+            ))),
+            Modifier::WildDie(_) if wild => out.push(Diagnostic::warning(format!(
+                "{}: rolls more than one wild die",
+                name
+            ))),
+            Modifier::WildDie(_) => {}
+            Modifier::Min(n) | Modifier::Max(n) => {
+                if let Some((lo, hi)) = faces.filter(|_| !exploded) {
+                    let (unchanged, all_changed) = match modifier {
+                        Modifier::Min(_) => (*n <= lo, *n >= hi),
+                        _ => (*n >= hi, *n <= lo),
+                    };
+                    if unchanged {
+                        out.push(Diagnostic::warning(format!(
+                            "{}: {} doesn't change any face",
+                            name, modifier
+                        )));
+                    } else if all_changed {
+                        out.push(Diagnostic::warning(format!(
+                            "{}: {} changes every face to {}",
+                            name, modifier, n
+                        )));
+                    }
+                }
+            }
+            Modifier::Reroll { once, condition } => {
+                let condition = condition.unwrap_or_else(Condition::reroll_default);
+                match coverage(&condition, faces) {
+                    Coverage::All if !once => out.push(Diagnostic::error(format!(
+                        "{}: reroll condition {} matches every face, so dice reroll until the reroll limit",
+                        name, condition
+                    ))),
+                    Coverage::All => out.push(Diagnostic::warning(format!(
+                        "{}: reroll condition {} matches every face, so every die is rerolled",
+                        name, condition
+                    ))),
+                    Coverage::None => out.push(Diagnostic::warning(format!(
+                        "{}: reroll condition {} matches no face, so nothing is rerolled",
+                        name, condition
+                    ))),
+                    Coverage::Some => {}
+                }
+            }
+            Modifier::RerollKeep { best, condition } => {
+                if let Some((lo, hi)) = faces {
+                    let condition = condition.unwrap_or_else(Condition::reroll_default);
+                    // The better roll is never worse than the highest face, nor
+                    // the worse roll better than the lowest
+                    let (changed, face, kept) = if *best {
+                        ((lo, hi - 1), "highest", "better")
+                    } else {
+                        ((lo + 1, hi), "lowest", "worse")
+                    };
+                    match coverage(&condition, faces) {
+                        Coverage::None => out.push(Diagnostic::warning(format!(
+                            "{}: reroll condition {} matches no face, so nothing is rerolled",
+                            name, condition
+                        ))),
+                        _ if matches!(coverage_of(&condition, changed), Coverage::None) => {
+                            out.push(Diagnostic::warning(format!(
+                                "{}: reroll condition {} matches only the {} face, so keeping the {} roll changes nothing",
+                                name, condition, face, kept
+                            )))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Modifier::Explode { condition, .. } => {
+                // Rolled sides have no fixed maximum to explode on
+                let condition = condition.or_else(|| sides.map(Condition::explode_default));
+                match condition.map(|condition| (coverage(&condition, faces), condition)) {
+                    Some((Coverage::All, condition)) => out.push(Diagnostic::error(format!(
+                        "{}: explode condition {} matches every face, so dice explode until the explosion limit",
+                        name, condition
+                    ))),
+                    Some((Coverage::None, condition)) => out.push(Diagnostic::warning(format!(
+                        "{}: explode condition {} matches no face, so nothing explodes",
+                        name, condition
+                    ))),
+                    _ => {}
+                }
+            }
+            Modifier::KeepHighest(n) | Modifier::KeepLowest(n) => {
+                if *n == 0 {
+                    out.push(Diagnostic::warning(format!(
+                        "{}: keeps no dice, so always totals 0",
+                        name
+                    )));
+                } else if let Some(count) = count.filter(|&count| !adds_dice && *n >= count) {
+                    out.push(Diagnostic::warning(format!(
+                        "{}: keeps {} of {} dice, so nothing is dropped",
+                        name, n, count
+                    )));
+                }
+            }
+            Modifier::DropHighest(n) | Modifier::DropLowest(n) => {
+                if *n == 0 {
+                    out.push(Diagnostic::warning(format!("{}: drops no dice", name)));
+                } else if let Some(count) = count.filter(|&count| !adds_dice && *n >= count) {
+                    out.push(Diagnostic::warning(format!(
+                        "{}: drops {} of {} dice, so always totals 0",
+                        name, n, count
+                    )));
+                }
+            }
+            Modifier::KeepWhere(condition) => match coverage(condition, faces) {
+                Coverage::All => out.push(Diagnostic::warning(format!(
+                    "{}: keep condition {} matches every face, so nothing is dropped",
+                    name, condition
+                ))),
+                Coverage::None => out.push(Diagnostic::warning(format!(
+                    "{}: keep condition {} matches no face, so always totals 0",
+                    name, condition
+                ))),
+                Coverage::Some => {}
+            },
+            Modifier::DropWhere(condition) => match coverage(condition, faces) {
+                Coverage::All => out.push(Diagnostic::warning(format!(
+                    "{}: drop condition {} matches every face, so always totals 0",
+                    name, condition
+                ))),
+                Coverage::None => out.push(Diagnostic::warning(format!(
+                    "{}: drop condition {} matches no face, so nothing is dropped",
+                    name, condition
+                ))),
+                Coverage::Some => {}
+            },
+            Modifier::CountSuccesses(condition) => match coverage(condition, faces) {
+                Coverage::All => out.push(Diagnostic::warning(format!(
+                    "{}: success condition {} matches every face, so every die is a success",
+                    name, condition
+                ))),
+                Coverage::None => out.push(Diagnostic::warning(format!(
+                    "{}: success condition {} matches no face, so there are never any successes",
+                    name, condition
+                ))),
+                Coverage::Some => {}
+            },
+            Modifier::CountFailures(condition) | Modifier::DoubleSuccesses(condition) => {
+                let (kind, effect) = match modifier {
+                    Modifier::CountFailures(_) => ("failure", "nothing is subtracted"),
+                    _ => ("double", "nothing counts double"),
+                };
+                if !counts_successes {
+                    out.push(Diagnostic::warning(format!(
+                        "{}: {} condition {} has no success condition before it, so it is ignored",
+                        name, kind, condition
+                    )));
+                } else if let Coverage::None = coverage(condition, faces) {
+                    out.push(Diagnostic::warning(format!(
+                        "{}: {} condition {} matches no face, so {}",
+                        name, kind, condition, effect
+                    )));
+                }
+            }
+            Modifier::Sort { .. } => {
+                if sorts {
+                    out.push(Diagnostic::warning(format!(
+                        "{}: sorts more than once, so only the last sort applies",
+                        name
+                    )));
+                }
+                sorts = true;
+            }
+            Modifier::Matches { width, count_sets } => {
+                if let Some(count) = count.filter(|&count| !adds_dice && *width > count) {
+                    out.push(Diagnostic::warning(format!(
+                        "{}: looks for sets of {} among {} dice, so never finds one",
+                        name, width, count
+                    )));
+                }
+                let scores = modifiers.iter().any(|m| matches!(m, Modifier::CountSuccesses(_)));
+                if *count_sets && scores {
+                    out.push(Diagnostic::warning(format!(
+                        "{}: totals its sets, so the success condition is ignored",
+                        name
+                    )));
+                }
+            }
+        }
+        counts_successes |= matches!(modifier, Modifier::CountSuccesses(_));
+        exploded |= matches!(modifier, Modifier::Explode { .. } | Modifier::WildDie(_));
+        wild |= matches!(modifier, Modifier::WildDie(_));
+    }
+
+    // Success counting, sorting and matching don't change the dice, so only
+    // the order of the others matters
+    let changes_dice: Vec<&Modifier> = modifiers.iter().filter(|m| stage(m) < 3).collect();
+    let reordered = changes_dice.iter().enumerate().find_map(|(i, later)| {
+        let earlier = changes_dice[..i].iter().find(|earlier| stage(earlier) > stage(later))?;
+        Some((earlier, later))
+    });
+    if let Some((earlier, later)) = reordered {
+        out.push(Diagnostic::warning(format!(
+            "{}: {} applies before {} as written but after it in canonical order, so the two orders can roll differently",
+            name, earlier, later
+        )));
+    }
+}
+
+/// How many faces of a die satisfy a condition.
+enum Coverage {
+    None,
+    Some,
+    All,
+}
+
+/// Rolled sides could have any faces, so every condition counts as `Some`.
+fn coverage(condition: &Condition, faces: Option<(i64, i64)>) -> Coverage {
+    match faces {
+        Some(faces) => coverage_of(condition, faces),
+        None => Coverage::Some,
+    }
+}
+
+/// How many values in `lo..=hi` satisfy a condition.
+fn coverage_of(condition: &Condition, (lo, hi): (i64, i64)) -> Coverage {
+    let v = condition.value;
+    let (all, none) = match condition.compare {
+        Compare::Equal => (lo == v && hi == v, v < lo || v > hi),
+        Compare::NotEqual => (v < lo || v > hi, lo == v && hi == v),
+        Compare::LessThan => (hi < v, lo >= v),
+        Compare::LessOrEqual => (hi <= v, lo > v),
+        Compare::GreaterThan => (lo > v, hi <= v),
+        Compare::GreaterOrEqual => (lo >= v, hi < v),
+    };
+    if all {
+        Coverage::All
+    } else if none {
+        Coverage::None
+    } else {
+        Coverage::Some
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn check(input: &str) -> Vec<Diagnostic> {
+        validate(&parse(input).unwrap())
+    }
+
+    #[test]
+    fn test_valid_expressions() {
+        for input in ["4d6kh3", "1d6!", "2d6r<3", "5d10>=8", "4dF + 2", "max(1d6 - 2, 1)", "4d6!kh3"] {
+            assert_eq!(check(input), vec![], "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_always_rerolls() {
+        let diagnostics = check("1d6r<=6");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_error());
+
+        // Rerolling once always terminates
+        let diagnostics = check("1d6ro<=6");
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_never_rerolls() {
+        let diagnostics = check("1d6r>6");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_reroll_keep() {
+        assert_eq!(check("1d20rb<=20 + 2d6rw>4"), vec![]);
+        assert!(check("1d6rb>6")[0].message.contains("matches no face"));
+        let diagnostics = check("1d20rw");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("only the lowest face, so keeping the worse roll changes nothing"));
+        assert!(check("1d6rb=6")[0].message.contains("only the highest face"));
+    }
+
+    #[test]
+    fn test_min_max() {
+        assert_eq!(check("2d6min3 + 1d20max19"), vec![]);
+        assert!(check("2d6min1")[0].message.contains("min1 doesn't change any face"));
+        assert!(check("2d6max6")[0].message.contains("max6 doesn't change any face"));
+        assert!(check("1d20min20")[0].message.contains("changes every face to 20"));
+        // Compounding explosions can go past 6, so the max matters
+        assert!(check("2d6!!max6").iter().all(|d| !d.message.contains("doesn't change")));
+    }
+
+    #[test]
+    fn test_wild_die() {
+        assert_eq!(check("1d8w + 1d12w8 + 3d6w"), vec![]);
+        assert!(check("1d8w0")[0].is_error());
+        assert!(check("1d8ww")[0].message.contains("more than one wild die"));
+        assert!(check("1d8wr")[0].message.contains("r comes after the wild die, so it rolls the wild die as a d8"));
+    }
+
+    #[test]
+    fn test_always_explodes() {
+        let diagnostics = check("1d1!");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_error());
+
+        assert!(check("1d6!!>0")[0].is_error());
+    }
+
+    #[test]
+    fn test_never_explodes() {
+        // The default explode condition is the side count, which no fudge face reaches
+        let diagnostics = check("4dF!");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_keep_more_than_rolled() {
+        let diagnostics = check("3d6kh5");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "3d6: keeps 5 of 3 dice, so nothing is dropped");
+    }
+
+    #[test]
+    fn test_drop_everything() {
+        assert_eq!(check("3d6dl3").len(), 1);
+        assert_eq!(check("3d6dh0").len(), 1);
+    }
+
+    #[test]
+    fn test_impossible_success_condition() {
+        let diagnostics = check("2dF>=7");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("never any successes"));
+    }
+
+    #[test]
+    fn test_zero_sides_and_division() {
+        assert!(check("1d0")[0].is_error());
+        assert!(check("1d6 / (0)")[0].is_error());
+    }
+
+    #[test]
+    fn test_failure_and_double_conditions() {
+        assert_eq!(check("10d10>=8f1dd10"), vec![]);
+        assert!(check("10d10>=8f11")[0].message.contains("nothing is subtracted"));
+        assert!(check("10d10>=8dd>10")[0].message.contains("nothing counts double"));
+
+        let roll = Roll::new(4, 10).modifier(Modifier::CountFailures(Condition::eq(1)));
+        assert!(validate(&Expr::from(roll))[0].message.contains("no success condition"));
+    }
+
+    #[test]
+    fn test_sorted_twice() {
+        assert_eq!(check("8d6sd"), vec![]);
+        assert!(check("8d6ssd")[0].message.contains("only the last sort applies"));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert_eq!(check("6d10mt"), vec![]);
+        assert!(check("2d10m3")[0].message.contains("never finds one"));
+        assert_eq!(check("2d10!m3"), vec![]);
+        assert!(check("6d10>=8mt")[0].message.contains("success condition is ignored"));
+    }
+
+    #[test]
+    fn test_keep_and_drop_conditions() {
+        assert_eq!(check("6d6d<3k>=4"), vec![]);
+        assert!(check("6d6k>6")[0].message.contains("always totals 0"));
+        assert!(check("6d6d<1")[0].message.contains("nothing is dropped"));
+    }
+
+    #[test]
+    fn test_written_order_differs_from_canonical() {
+        assert_eq!(check("4d6r!kh3>=4s"), vec![]);
+        assert_eq!(check("4d6>=4kh3"), vec![]);
+        let diagnostics = check("4d6kh3!");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("kh3 applies before ! as written"));
+        assert!(check("2d6!r<3")[0].message.contains("! applies before r<3"));
+    }
+
+    #[test]
+    fn test_dynamic_rolls() {
+        assert_eq!(check("(1d4)d6 + 1d(2d4) + @level d8"), vec![]);
+        assert!(check("(1d4 - 2)d6")[0].message.contains("count can be negative (-1)"));
+        assert!(check("(0 - 1)d6")[0].is_error());
+        assert!(check("2d(1d4 - 1)")[0].message.contains("can have 0 sides"));
+        assert!(check("2d(1 - 3)")[0].is_error());
+        // The count and sides are checked like any other expression
+        assert_eq!(check("(3d6kh5)d6").len(), 1);
+    }
+
+    #[test]
+    fn test_dynamic_roll_modifiers() {
+        assert_eq!(check("(1d4)d6!kh1 + 1d(2d4)r + @level d8>=5f1"), vec![]);
+        // Fixed sides are checked like a plain roll's
+        assert!(check("(1d4)d6r<=6")[0].is_error());
+        assert!(check("(1d4)d6>7")[0].message.contains("matches no face"));
+        // A constant count or sides is known too
+        assert!(check("(1 + 2)d6kh3")[0].message.contains("keeps 3 of 3 dice"));
+        assert!(check("2d(3 + 3)min1")[0].message.contains("min1 doesn't change any face"));
+        // Rolled counts and sides skip the checks that need them
+        assert_eq!(check("(1d4)d6kh3 + 2d(1d6)!>=2"), vec![]);
+        // The rest don't depend on the dice
+        assert!(check("(1d4)d6w0")[0].is_error());
+        assert!(check("2d(1d6)mt>=5")[0].message.contains("totals its sets"));
+        assert!(check("@n d6sasd")[0].message.contains("sorts more than once"));
+    }
+
+    #[test]
+    fn test_nested_expressions() {
+        assert_eq!(check("max(3d6kh5, 1d1!) + 1").len(), 2);
+    }
+
+    #[test]
+    fn test_bands() {
+        assert_eq!(check("2d6 + 1 {<=6: miss, 7..9: weak, >=10: strong}"), vec![]);
+        assert_eq!(
+            check("2d6 {<=6: miss, 7..9: weak}")[0].message,
+            "no band covers a total of 10"
+        );
+        assert!(check("2d6 {<=1: never, >=2: always}")[0]
+            .message
+            .contains("never rolls below 2"));
+        assert!(check("1d6 {<=3: low, 3..6: high}")[0]
+            .message
+            .contains("'3..6: high' overlaps '<=3: low'"));
+        // Explosions have no highest total to cover
+        assert_eq!(check("1d6! {<=3: low, >=4: high}"), vec![]);
+        assert_eq!(check("1d6! {<=3: low, 4..6: high}")[0].message, "no band covers a total of 7");
+        // The expression itself is still checked
+        assert_eq!(check("3d6kh5 {>=3: any}").len(), 1);
+    }
+}